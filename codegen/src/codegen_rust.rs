@@ -796,6 +796,8 @@ impl<'model> RustCodeGen<'model> {
         "#,
         );
 
+        // (dispatch name, input shape, output shape) of each method, for the service descriptor
+        let mut descriptors = Vec::new();
         for method_id in service.operations() {
             // we don't add operations defined in another namespace
             if let Some(ref ns) = self.namespace {
@@ -805,6 +807,11 @@ impl<'model> RustCodeGen<'model> {
             }
             let method_ident = method_id.shape_name();
             let (op, method_traits) = get_operation(model, method_id, service_id)?;
+            descriptors.push((
+                self.op_dispatch_name(method_ident),
+                op.input().as_ref().map(ToString::to_string),
+                op.output().as_ref().map(ToString::to_string),
+            ));
             w.write(b"\"");
             w.write(&self.op_dispatch_name(method_ident));
             w.write(b"\" => {\n");
@@ -863,11 +870,61 @@ impl<'model> RustCodeGen<'model> {
         w.write(b"_ => Err(RpcError::MethodNotHandled(format!(\"");
         self.write_ident(w, service_id);
         w.write(b"::{}\", message.method))),\n");
-        w.write(b"}\n}\n}\n\n"); // end match, end fn dispatch, end trait
+        w.write(b"}\n}\n"); // end match, end fn dispatch
+        self.write_service_descriptor(w, service_id, service_traits, &descriptors)?;
+        w.write(b"}\n\n"); // end trait
 
         Ok(())
     }
 
+    /// writes the Receiver method that returns the service's contract id and methods
+    fn write_service_descriptor(
+        &mut self,
+        w: &mut Writer,
+        service_id: &Identifier,
+        service_traits: &AppliedTraits,
+        methods: &[(String, Option<String>, Option<String>)],
+    ) -> Result<()> {
+        fn opt_str(val: Option<&str>) -> String {
+            match val {
+                Some(s) => format!("Some(Cow::Borrowed({:?}))", s),
+                None => "None".to_string(),
+            }
+        }
+        let contract_id = match get_trait(service_traits, crate::model::wasmbus_trait())? {
+            Some(Wasmbus { contract_id, .. }) => contract_id,
+            None => None,
+        };
+        let core = &self.import_core;
+        w.write(&format!(
+            r#"
+            /// Describes the {} service: its contract id and methods
+            fn service_descriptor(&self) -> &'static {}::ServiceDescriptor {{
+                static DESCRIPTOR: {}::ServiceDescriptor = {}::ServiceDescriptor {{
+                    name: Cow::Borrowed("{}"),
+                    contract_id: {},
+                    methods: Cow::Borrowed(&[
+            "#,
+            service_id,
+            core,
+            core,
+            core,
+            service_id,
+            opt_str(contract_id.as_deref()),
+        ));
+        for (name, input, output) in methods.iter() {
+            w.write(&format!(
+                "{}::MethodDescriptor {{ name: Cow::Borrowed({:?}), input: {}, output: {} }},\n",
+                core,
+                name,
+                opt_str(input.as_deref()),
+                opt_str(output.as_deref()),
+            ));
+        }
+        w.write(b"]),\n};\n&DESCRIPTOR\n}\n");
+        Ok(())
+    }
+
     /// writes the service sender struct and constructor
     // pub struct FooSender{ ... }
     fn write_service_sender(
@@ -1096,7 +1153,7 @@ impl<'model> RustCodeGen<'model> {
 /// is_optional_type determines whether the field should be wrapped in Option<>
 /// the value is true if it has an explicit `box` trait, or if it's
 /// un-annotated and not one of (boolean, byte, short, integer, long, float, double)
-pub(crate) fn is_optional_type(field: &MemberShape) -> bool {
+pub fn is_optional_type(field: &MemberShape) -> bool {
     field.is_boxed()
         || (!field.is_required()
             && ![
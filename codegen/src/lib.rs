@@ -18,6 +18,7 @@ mod loader;
 pub(crate) mod model;
 pub mod render;
 pub mod writer;
+pub use codegen_rust::is_optional_type;
 pub use gen::templates_from_dir;
 pub use gen::Generator;
 
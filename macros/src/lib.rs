@@ -1,29 +1,134 @@
 use proc_macro::TokenStream;
 use proc_macro2::{Span, TokenStream as TokenStream2};
 use proc_macro_error::{abort, proc_macro_error};
-use quote::{format_ident, quote, ToTokens};
+use quote::{format_ident, quote, quote_spanned};
+use std::collections::HashMap;
 use syn::{
-    parse::Result as ParseResult, parse_macro_input, spanned::Spanned, Attribute, Fields, Ident,
-    Meta, NestedMeta,
+    parse::Result as ParseResult, parse_macro_input, punctuated::Punctuated, spanned::Spanned,
+    Attribute, Fields, Ident, Meta, NestedMeta, Token,
 };
 
-/// extract traits from attribute
-///  `#[services(Piano,Tuba)]` returns vec![ Piano, Tuba ]
-///  items in the vec are syn::Path, and may have more than one path segment,
-///    as in instruments::Piano
+/// a service listed in `#[services(..)]`: the service trait, which may be qualified
+/// with its module path, as in `kv::KeyValue`, and an optional name used for dispatch,
+/// as in `kv::KeyValue as "Store"`. Without a name, the last path segment is used.
+/// The last path segment must be the trait's original name, because the `Receiver`
+/// trait is found by appending "Receiver" to it.
+struct ServiceSpec {
+    path: syn::Path,
+    name: Option<syn::LitStr>,
+}
+
+impl ServiceSpec {
+    /// the service name that messages are dispatched by (the part of the method before '.')
+    fn name(&self) -> String {
+        match (&self.name, self.path.segments.last()) {
+            (Some(name), _) => name.value(),
+            (None, Some(seg)) => seg.ident.to_string(),
+            (None, None) => String::new(),
+        }
+    }
+
+    /// the generated `Receiver` trait, in the same module as the service trait
+    fn receiver(&self) -> syn::Path {
+        let mut path = self.path.clone();
+        if let Some(seg) = path.segments.last_mut() {
+            seg.ident = format_ident!("{}Receiver", seg.ident);
+        }
+        path
+    }
+}
+
+impl syn::parse::Parse for ServiceSpec {
+    fn parse(input: syn::parse::ParseStream) -> ParseResult<Self> {
+        let path: syn::Path = input.parse()?;
+        let name = if input.peek(Token![as]) {
+            input.parse::<Token![as]>()?;
+            Some(input.parse::<syn::LitStr>()?)
+        } else {
+            None
+        };
+        Ok(ServiceSpec { path, name })
+    }
+}
+
+/// extract services from attribute
+///  `#[services(Piano, brass::Tuba as "Tuba")]` returns services Piano and brass::Tuba
 ///
-fn attr_traits(attr: &Attribute, key: &str) -> Vec<syn::Path> {
-    let mut traits = Vec::new();
-    if attr.path.is_ident(key) {
-        if let Ok(Meta::List(ref ml)) = attr.parse_meta() {
+fn attr_traits(attr: &Attribute, key: &str) -> Vec<ServiceSpec> {
+    if !attr.path.is_ident(key) {
+        return Vec::new();
+    }
+    match attr.parse_args_with(Punctuated::<ServiceSpec, Token![,]>::parse_terminated) {
+        Ok(services) => services.into_iter().collect(),
+        Err(e) => abort!(
+            e.span(),
+            "{}", e;
+            help = "expected a list of service traits, as in `#[{}(Trait1, module::Trait2 as \"Name\")]`", key
+        ),
+    }
+}
+
+/// returns true if the attribute is `#[introspect]`
+fn attr_introspect(attr: &Attribute) -> bool {
+    if !attr.path.is_ident("introspect") {
+        return false;
+    }
+    if !attr.tokens.is_empty() {
+        abort!(
+            attr.tokens.span(),
+            "`#[introspect]` does not take arguments"
+        );
+    }
+    true
+}
+
+/// options of the `#[actor(...)]` attribute
+#[derive(Default)]
+struct ActorArgs {
+    init: Option<syn::Path>,
+    middleware: Option<syn::Path>,
+}
+
+/// extract the actor options from attribute
+///  `#[actor(init = "make_actor", middleware = "make_stack")]`
+///  The values may be paths, such as "config::make_actor"
+///
+fn attr_actor(attr: &Attribute, args: &mut ActorArgs) {
+    if !attr.path.is_ident("actor") {
+        return;
+    }
+    match attr.parse_meta() {
+        Ok(Meta::List(ref ml)) => {
             for n in ml.nested.iter() {
-                if let NestedMeta::Meta(Meta::Path(p)) = n {
-                    traits.push(p.clone())
+                match n {
+                    NestedMeta::Meta(Meta::NameValue(nv))
+                        if nv.path.is_ident("init") || nv.path.is_ident("middleware") =>
+                    {
+                        let path = match &nv.lit {
+                            syn::Lit::Str(s) => match s.parse::<syn::Path>() {
+                                Ok(path) => path,
+                                Err(_) => abort!(s.span(), "expected a function name"),
+                            },
+                            lit => abort!(
+                                lit.span(),
+                                "expected a string, as in `init = \"make_actor\"`"
+                            ),
+                        };
+                        if nv.path.is_ident("init") {
+                            args.init = Some(path);
+                        } else {
+                            args.middleware = Some(path);
+                        }
+                    }
+                    _ => abort!(
+                        n.span(),
+                        "expected `init = \"function_name\"` or `middleware = \"function_name\"`"
+                    ),
                 }
             }
         }
+        _ => abort!(attr.span(), "expected `#[actor(init = \"function_name\")]`"),
     }
-    traits
 }
 
 #[allow(dead_code)]
@@ -62,14 +167,47 @@ impl syn::parse::Parse for ReceiverDef {
     }
 }
 
+/// Derives the actor's message dispatch and `__guest_call` export.
+///
+/// The `Context` passed to handlers is decoded from the
+/// HostContext the host supplies with each message, if the host supports it.
+/// Records from the `log` crate are sent to the host with `__console_log`,
+/// and panics are reported to the host as the message's error.
+///
+/// The actor is constructed once, on the first message, and reused for every
+/// message after that, so it can hold state such as parsed configuration.
+/// It is constructed with `Default::default()`, or with the function named in
+/// `#[actor(init = "make_actor")]`, which has the signature `fn make_actor() -> MyActor`.
+/// With `#[actor(middleware = "make_stack")]`, where `fn make_stack() -> MiddlewareStack`,
+/// messages are dispatched to the actor through the stack's middleware.
+///
+/// Services in `#[services(..)]` may be qualified with their module path, as in `kv::KeyValue`,
+/// and messages are dispatched by the trait name ("KeyValue"). The service's `KeyValueReceiver`
+/// trait is taken from the same module, so the trait must be named by its original name:
+/// a module may be aliased (`use crate::kv as store;` and `store::KeyValue`), but not the trait.
+/// `kv::KeyValue as "Store"` dispatches messages for "Store.Method" instead, for example
+/// to list two services with the same trait name.
+///
+/// With `#[introspect]`, `MessageDispatch::services` (and messages with method `INTROSPECT_METHOD`)
+/// return the contract id and methods of each service, from the `service_descriptor()` that
+/// weld-codegen generates in each `Receiver` trait. Without it, services are described
+/// by name only, so interfaces generated before codegen added `service_descriptor()` can be used.
 #[proc_macro_error]
-#[proc_macro_derive(Actor, attributes(services))]
+#[proc_macro_derive(Actor, attributes(services, actor, introspect))]
 pub fn derive_actor(input: TokenStream) -> TokenStream {
     let actor_receiver = parse_macro_input!(input as ReceiverDef);
+    gen_actor(actor_receiver).into()
+}
 
+/// generates the actor's exports and message dispatch
+fn gen_actor(actor_receiver: ReceiverDef) -> TokenStream2 {
     let mut traits = Vec::new();
+    let mut args = ActorArgs::default();
+    let mut introspect = false;
     for attr in actor_receiver.attrs.iter() {
         traits.extend(attr_traits(attr, "services"));
+        attr_actor(attr, &mut args);
+        introspect |= attr_introspect(attr);
     }
     if traits.is_empty() {
         abort!(
@@ -78,7 +216,18 @@ pub fn derive_actor(input: TokenStream) -> TokenStream {
         );
     }
     let actor_ident = actor_receiver.ident;
-    let dispatch_impl = gen_dispatch(&traits, &actor_ident);
+    let dispatch_impl = gen_dispatch(&traits, &actor_ident, introspect);
+    let make_actor = match args.init {
+        Some(init) => quote!(#init()),
+        None => quote!(<#actor_ident as Default>::default()),
+    };
+    let (actor_type, make_actor) = match args.middleware {
+        Some(middleware) => (
+            quote!(wasmbus_rpc::middleware::Layered<#actor_ident>),
+            quote!(#middleware().wrap(#make_actor)),
+        ),
+        None => (quote!(#actor_ident), make_actor),
+    };
 
     let output = quote!(
 
@@ -94,10 +243,30 @@ pub fn derive_actor(input: TokenStream) -> TokenStream {
         wasmbus_rpc::WASMBUS_RPC_VERSION
     }
 
+    // called by hosts that support api version WASMBUS_CONTEXT_API_VERSION, before __guest_call,
+    // to get the address where the host writes the serialized HostContext for the next message
+    #[no_mangle]
+    pub extern "C" fn __guest_context(len: i32) -> i32 {
+        wasmbus_rpc::actor::alloc_host_context(len as _) as i32
+    }
+
+    // called by hosts that support api version WASMBUS_HOST_CALL_OPTS_API_VERSION, during __host_call,
+    // to get the serialized HostCallOpts for the message the actor is sending
+    #[no_mangle]
+    pub extern "C" fn __host_call_opts_len() -> i32 {
+        wasmbus_rpc::actor::host_call_opts().1 as i32
+    }
+
+    #[no_mangle]
+    pub extern "C" fn __host_call_opts() -> i32 {
+        wasmbus_rpc::actor::host_call_opts().0 as i32
+    }
+
     #[no_mangle]
     pub extern "C" fn __guest_call(op_len: i32, req_len: i32) -> i32 {
         use std::slice;
 
+        wasmbus_rpc::actor::init_guest();
         let buf: Vec<u8> = Vec::with_capacity(req_len as _);
         let req_ptr = buf.as_ptr();
 
@@ -112,11 +281,17 @@ pub fn derive_actor(input: TokenStream) -> TokenStream {
             )
         };
         let method = String::from_utf8_lossy(op);
-        let context = wasmbus_rpc::Context::default();
-        let actor = #actor_ident ::default();
+        let context = wasmbus_rpc::actor::take_host_context();
+
+        // the actor is constructed on the first call, and lives for the life of the instance.
+        // wasm actors are single-threaded, so a thread-local holds the only instance
+        thread_local! {
+            static ACTOR: &'static #actor_type = Box::leak(Box::new(#make_actor));
+        }
+        let actor: &'static #actor_type = ACTOR.with(|actor| *actor);
         let resp = futures::executor::block_on({
             MessageDispatch::dispatch(
-                &actor,
+                actor,
                 &context,
                 Message {
                     method: &method,
@@ -146,7 +321,7 @@ pub fn derive_actor(input: TokenStream) -> TokenStream {
     ); // end quote
 
     // struct #actor_ident { #fields }
-    output.into()
+    output
 }
 
 #[proc_macro_derive(ActorHealthResponder)]
@@ -172,22 +347,90 @@ pub fn derive_health_responder(input: TokenStream) -> TokenStream {
     output.into()
 }
 
-fn gen_dispatch(traits: &[syn::Path], ident: &Ident) -> TokenStream2 {
+/// generates MessageDispatch for the services. If introspect is true, the service descriptors
+/// come from each Receiver's `service_descriptor()`, otherwise they only have the service name.
+fn gen_dispatch(traits: &[ServiceSpec], ident: &Ident, introspect: bool) -> TokenStream2 {
     let mut methods = Vec::new();
     let mut trait_receiver_impl = Vec::new();
+    let mut names: HashMap<String, Span> = HashMap::new();
+    let mut descriptors = Vec::new();
+    let mut helpers = Vec::new();
 
-    for path in traits.iter() {
-        let path_str = path.segments.to_token_stream().to_string();
-        let id = format_ident!("{}Receiver", &path_str);
-        methods.push(quote!(
-            #path_str => #id::dispatch(self, ctx, &message).await
+    for service in traits.iter() {
+        let path = &service.path;
+        let name = service.name();
+        let name_span = match &service.name {
+            Some(lit) => lit.span(),
+            None => path.span(),
+        };
+        if let Some(first) = names.insert(name.clone(), name_span) {
+            abort!(
+                name_span,
+                "duplicate service name '{}'", name;
+                note = first => "first used here";
+                help = "rename one of the services, as in `module::{} as \"OtherName\"`", name
+            );
+        }
+        let id = service.receiver();
+        // receivers are reached through a helper trait, so that a missing service
+        // implementation is reported once, at the service name
+        let helper = Ident::new(
+            &format!(
+                "ServiceNotImplemented_{}",
+                name.replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+            ),
+            path.span(),
+        );
+        let service_path = quote!(#path).to_string().replace(' ', "");
+        let message = format!(
+            "`{{Self}}` does not implement the `{}` service",
+            service_path
+        );
+        let label = format!(
+            "`{}` is listed as a service, but not implemented",
+            service_path
+        );
+        let ident_at_path = Ident::new(&ident.to_string(), path.span());
+        methods.push(quote_spanned!(path.span()=>
+            #name => #id::dispatch(#helper::receiver(self), ctx, &message).await
         ));
-        trait_receiver_impl.push(quote!(
-            impl #id for #ident { }
+        descriptors.push(if introspect {
+            quote_spanned!(path.span()=> {
+                let mut service = #id::service_descriptor(#helper::receiver(self)).clone();
+                service.name = std::borrow::Cow::Borrowed(#name);
+                service
+            })
+        } else {
+            quote!(wasmbus_rpc::ServiceDescriptor {
+                name: std::borrow::Cow::Borrowed(#name),
+                contract_id: None,
+                methods: std::borrow::Cow::Borrowed(&[]),
+            })
+        });
+        helpers.push(quote_spanned!(path.span()=>
+            #[allow(non_camel_case_types)]
+            #[diagnostic::on_unimplemented(message = #message, label = #label)]
+            trait #helper {
+                type Receiver: #id + Sync;
+                fn receiver(&self) -> &Self::Receiver;
+            }
+            #[diagnostic::do_not_recommend]
+            impl<T: #id + Sync> #helper for T {
+                type Receiver = T;
+                fn receiver(&self) -> &T {
+                    self
+                }
+            }
+        ));
+        trait_receiver_impl.push(quote_spanned!(path.span()=>
+            impl #id for #ident_at_path where for<'a> #ident_at_path: #path { }
         ));
     }
 
     quote!(
+      const _: () = {
+        #( #helpers )*
+
         #[async_trait]
         impl MessageDispatch for #ident {
             async fn dispatch(
@@ -195,6 +438,12 @@ fn gen_dispatch(traits: &[syn::Path], ident: &Ident) -> TokenStream2 {
                 ctx: &Context,
                 message: Message<'_>,
             ) -> Result<Message<'_>, RpcError> {
+                if message.method == wasmbus_rpc::INTROSPECT_METHOD {
+                    return Ok(Message {
+                        method: wasmbus_rpc::INTROSPECT_METHOD,
+                        arg: std::borrow::Cow::Owned(wasmbus_rpc::serialize(&MessageDispatch::services(self))?),
+                    });
+                }
                 let (trait_name, trait_method) = message
                     .method
                     .rsplit_once('.')
@@ -209,40 +458,296 @@ fn gen_dispatch(traits: &[syn::Path], ident: &Ident) -> TokenStream2 {
                             format!("{}.{} - unknown method", trait_name,message.method)))
                 }
             }
+
+            fn services(&self) -> Vec<wasmbus_rpc::ServiceDescriptor> {
+                vec![ #( #descriptors ),* ]
+            }
         }
 
+      };
+
       #( #trait_receiver_impl )*
     )
 }
 
 // for providers that do not implement any Service Receivers
 // (for example, HttpServer that sends only)
-// implement MessageDispatch that always return error if we receive rpc
+// implement MessageDispatch that always return error if we receive rpc,
+// except for introspection, which returns an empty list
 fn gen_empty_dispatch(ident: &Ident) -> TokenStream2 {
     quote!(
         #[async_trait]
         impl MessageDispatch for #ident {
             async fn dispatch(&self,_ctx: &Context,message: Message<'_>)->Result<Message<'_>, RpcError> {
+                if message.method == wasmbus_rpc::INTROSPECT_METHOD {
+                    let services: Vec<wasmbus_rpc::ServiceDescriptor> = Vec::new();
+                    return Ok(Message {
+                        method: wasmbus_rpc::INTROSPECT_METHOD,
+                        arg: std::borrow::Cow::Owned(wasmbus_rpc::serialize(&services)?),
+                    });
+                }
                 Err(RpcError::MethodNotHandled(message.method.to_string()))
             }
         }
     )
 }
 
+/// Derives `MessageDispatch` for the provider's services, listed in `#[services(..)]`.
+/// Services are described as in `#[derive(Actor)]`, and `#[introspect]` has the same meaning.
 #[proc_macro_error]
-#[proc_macro_derive(Provider, attributes(services))]
+#[proc_macro_derive(Provider, attributes(services, introspect))]
 pub fn derive_provider(input: TokenStream) -> TokenStream {
     let provider_receiver = parse_macro_input!(input as ReceiverDef);
 
     let mut traits = Vec::new();
+    let mut introspect = false;
     for attr in provider_receiver.attrs.iter() {
         traits.extend(attr_traits(attr, "services"));
+        introspect |= attr_introspect(attr);
     }
     let ident = provider_receiver.ident;
     let output = if traits.is_empty() {
         gen_empty_dispatch(&ident)
     } else {
-        gen_dispatch(&traits, &ident)
+        gen_dispatch(&traits, &ident, introspect)
     };
     output.into()
 }
+
+/// options of the `#[provider(...)]` attribute
+struct ProviderArgs {
+    services: Vec<ServiceSpec>,
+    config: Option<syn::Path>,
+    logger: Option<syn::Path>,
+    middleware: Option<syn::Path>,
+    signals: bool,
+    host_watch: bool,
+    handler: bool,
+    introspect: bool,
+}
+
+/// parse a string literal containing a path, as in `config = "KvConfig"`
+fn lit_path(key: &Ident, lit: Option<syn::Lit>) -> ParseResult<syn::Path> {
+    match lit {
+        Some(syn::Lit::Str(s)) => s.parse::<syn::Path>(),
+        Some(lit) => Err(syn::Error::new(
+            lit.span(),
+            format!("{} must be a string, as in `{} = \"name\"`", key, key),
+        )),
+        None => Err(syn::Error::new(
+            key.span(),
+            format!("expected `{} = \"name\"`", key),
+        )),
+    }
+}
+
+/// parse a flag, either `host_watch` or `host_watch = true`
+fn lit_bool(key: &Ident, lit: Option<syn::Lit>) -> ParseResult<bool> {
+    match lit {
+        None => Ok(true),
+        Some(syn::Lit::Bool(b)) => Ok(b.value),
+        Some(lit) => Err(syn::Error::new(
+            lit.span(),
+            format!("{} must be `true` or `false`", key),
+        )),
+    }
+}
+
+impl syn::parse::Parse for ProviderArgs {
+    fn parse(input: syn::parse::ParseStream) -> ParseResult<Self> {
+        let mut pa = ProviderArgs {
+            services: Vec::new(),
+            config: None,
+            logger: None,
+            middleware: None,
+            signals: true,
+            host_watch: false,
+            handler: true,
+            introspect: false,
+        };
+        while !input.is_empty() {
+            let key: Ident = input.parse()?;
+            if key == "services" {
+                let content;
+                syn::parenthesized!(content in input);
+                pa.services
+                    .extend(content.parse_terminated::<_, Token![,]>(ServiceSpec::parse)?);
+            } else {
+                let value = if input.peek(Token![=]) {
+                    input.parse::<Token![=]>()?;
+                    Some(input.parse::<syn::Lit>()?)
+                } else {
+                    None
+                };
+                match key.to_string().as_str() {
+                    "config" => pa.config = Some(lit_path(&key, value)?),
+                    "logger" => pa.logger = Some(lit_path(&key, value)?),
+                    "middleware" => pa.middleware = Some(lit_path(&key, value)?),
+                    "signals" => pa.signals = lit_bool(&key, value)?,
+                    "host_watch" => pa.host_watch = lit_bool(&key, value)?,
+                    "handler" => pa.handler = lit_bool(&key, value)?,
+                    "introspect" => pa.introspect = lit_bool(&key, value)?,
+                    _ => return Err(syn::Error::new(
+                        key.span(),
+                        "unknown provider option. Options are services(..), config, logger, middleware, signals, host_watch, handler, and introspect",
+                    )),
+                }
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(pa)
+    }
+}
+
+/// Generates a capability provider's entry point and message dispatch.
+///
+/// ```ignore
+/// use wasmbus_rpc::provider::prelude::*;
+///
+/// #[provider(services(KeyValue), config = "KvConfig")]
+/// #[derive(Clone)]
+/// struct KvProvider { .. }
+/// ```
+///
+/// generates `MessageDispatch` for the listed services (as `#[derive(Provider)]` does),
+/// `ProviderDispatch`, an empty `ProviderHandler` impl, and a `main` function that calls
+/// `provider_main_with`. The module should import `wasmbus_rpc::provider::prelude::*`.
+///
+/// Options:
+/// - `services(Trait1, Trait2)`: service traits implemented by the provider. If there are none,
+///   all rpc messages return an error (for providers that only send). As in `#[services(..)]`,
+///   traits may be qualified with a module path, and dispatched by another name with `as "Name"`.
+/// - `config = "ConfigType"`: the provider is constructed with `From<ConfigType>`, from
+///   the configuration json in the host data. ConfigType implements `Deserialize` and `Default`
+///   (used if the host does not provide configuration).
+///   Without this option, the provider is constructed with `Default::default()`.
+/// - `logger = "init_fn"`: `init_fn()` is called to install a logger before the provider starts.
+///   Without this option, log messages are written to stderr.
+/// - `middleware = "make_stack"`: rpc messages are dispatched to the provider through the
+///   middleware in the `MiddlewareStack` returned by `fn make_stack() -> MiddlewareStack`.
+/// - `signals = false`: don't stop on SIGTERM or SIGINT (default true)
+/// - `host_watch`: stop when the host process exits (see `ShutdownTriggers::with_host_watch`)
+/// - `handler = false`: don't generate `ProviderHandler`, so the provider can implement
+///   `put_link`, `delete_link`, and the other handler methods.
+/// - `introspect`: describe the services' methods in `MessageDispatch::services`,
+///   as `#[introspect]` does for `#[derive(Provider)]`
+#[proc_macro_error]
+#[proc_macro_attribute]
+pub fn provider(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as ProviderArgs);
+    let item_tokens = TokenStream2::from(item.clone());
+    let provider_receiver = parse_macro_input!(item as ReceiverDef);
+    let ident = provider_receiver.ident;
+
+    let dispatch_impl = if args.services.is_empty() {
+        gen_empty_dispatch(&ident)
+    } else {
+        gen_dispatch(&args.services, &ident, args.introspect)
+    };
+    let handler_impl = if args.handler {
+        quote!(
+            #[async_trait]
+            impl ProviderHandler for #ident {}
+        )
+    } else {
+        quote!()
+    };
+    let make_provider = match args.config {
+        Some(config) => quote!(
+            |host_data: &wasmbus_rpc::core::HostData| -> RpcResult<#ident> {
+                let config = host_data.config::<#config>()?.unwrap_or_default();
+                Ok(<#ident as From<#config>>::from(config))
+            }
+        ),
+        None => quote!(
+            |_: &wasmbus_rpc::core::HostData| -> RpcResult<#ident> {
+                Ok(<#ident as Default>::default())
+            }
+        ),
+    };
+    let make_provider = match args.middleware {
+        Some(middleware) => quote!(
+            |host_data: &wasmbus_rpc::core::HostData| {
+                (#make_provider)(host_data).map(|provider| #middleware().wrap(provider))
+            }
+        ),
+        None => make_provider,
+    };
+    let init_logger = args.logger.map(|logger| quote!(#logger();));
+    let (signals, host_watch) = (args.signals, args.host_watch);
+
+    let output = quote!(
+        #item_tokens
+
+        #dispatch_impl
+
+        impl ProviderDispatch for #ident {}
+
+        #handler_impl
+
+        fn main() -> Result<(), Box<dyn std::error::Error>> {
+            #init_logger
+            provider_main_with(
+                #make_provider,
+                ShutdownTriggers {
+                    signals: #signals,
+                    stdin_eof: #host_watch,
+                    parent_exit: #host_watch,
+                    ..Default::default()
+                },
+            )
+        }
+    ); // end quote
+    output.into()
+}
+
+/// returns the functions exported by the actor derived from the struct
+#[cfg(test)]
+fn actor_exports(input: TokenStream2) -> Vec<syn::ItemFn> {
+    let actor_receiver = syn::parse2::<ReceiverDef>(input).unwrap();
+    let file = syn::parse2::<syn::File>(gen_actor(actor_receiver)).unwrap();
+    file.items
+        .into_iter()
+        .filter_map(|item| match item {
+            syn::Item::Fn(f) if f.attrs.iter().any(|a| a.path.is_ident("no_mangle")) => Some(f),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn actor_guest_call_inits_guest() {
+    let exports = actor_exports(quote!(
+        #[services(Echo)]
+        struct EchoActor {}
+    ));
+    let names = exports
+        .iter()
+        .map(|f| f.sig.ident.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            "__actor_api_version",
+            "__guest_context",
+            "__host_call_opts_len",
+            "__host_call_opts",
+            "__guest_call"
+        ]
+    );
+
+    // the logger and panic hook are installed before the message is received,
+    // so that panics while handling it are reported to the host
+    let stmts = exports[4]
+        .block
+        .stmts
+        .iter()
+        .map(|stmt| quote!(#stmt).to_string())
+        .collect::<Vec<_>>();
+    let init = quote!(wasmbus_rpc::actor::init_guest();).to_string();
+    let init_pos = stmts.iter().position(|s| *s == init);
+    let request_pos = stmts.iter().position(|s| s.contains("__guest_request"));
+    assert!(init_pos.is_some() && init_pos < request_pos, "{:#?}", stmts);
+}
@@ -3,6 +3,7 @@
 
 use crate::{Message, RpcError, RpcResult};
 use async_trait::async_trait;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 #[link(wasm_import_module = "wasmbus")]
 extern "C" {
@@ -22,7 +23,7 @@ extern "C" {
     pub fn __host_error_len() -> usize;
     pub fn __host_error(ptr: *const u8);
     //pub fn __guest_response(ptr: *const u8, len: usize);
-    //pub fn __guest_error(ptr: *const u8, len: usize);
+    pub fn __guest_error(ptr: *const u8, len: usize);
     //pub fn __guest_request(op_ptr: *const u8, ptr: *const u8);
 }
 
@@ -65,9 +66,21 @@ pub fn host_call(binding: &str, ns: &str, op: &str, msg: &[u8]) -> crate::RpcRes
     }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Debug, Default)]
 pub struct WasmHost {
     pub target: crate::core::WasmCloudEntity,
+    /// time limit in milliseconds for messages sent by this WasmHost.
+    /// If zero, the host's default is used
+    timeout_ms: AtomicU64,
+}
+
+impl Clone for WasmHost {
+    fn clone(&self) -> Self {
+        WasmHost {
+            target: self.target.clone(),
+            timeout_ms: AtomicU64::new(self.timeout_ms.load(Ordering::Relaxed)),
+        }
+    }
 }
 
 impl WasmHost {
@@ -78,13 +91,26 @@ impl WasmHost {
     ) -> RpcResult<Self> {
         Ok(WasmHost {
             target: crate::core::WasmCloudEntity::new_provider(contract_id, link_name)?,
+            ..Default::default()
         })
     }
 
-    /// constructs a WasmHost Transport for sending messages to another actor
+    /// constructs a WasmHost Transport for sending messages to another actor.
+    /// The id may be the actor's public key or its call alias.
     pub fn to_actor<T: ToString>(id: T) -> RpcResult<Self> {
+        let id = id.to_string();
+        if id.is_empty() {
+            return Err(RpcError::InvalidParameter(
+                "actor id may not be empty".to_string(),
+            ));
+        }
         Ok(WasmHost {
-            target: crate::core::WasmCloudEntity::new_actor(id.to_string())?,
+            target: crate::core::WasmCloudEntity {
+                public_key: id,
+                contract_id: String::new(),
+                link_name: String::new(),
+            },
+            ..Default::default()
         })
     }
 }
@@ -93,26 +119,42 @@ impl WasmHost {
 impl crate::Transport for WasmHost {
     async fn send(
         &self,
-        _ctx: &crate::Context,
+        ctx: &crate::Context,
         req: Message<'_>,
-        _opts: Option<crate::SendOpts>,
+        opts: Option<crate::SendOpts>,
     ) -> std::result::Result<Vec<u8>, RpcError> {
+        // hosts that support WASMBUS_HOST_CALL_OPTS_API_VERSION read these during the host call
+        let opts = opts.unwrap_or_default();
+        crate::actor::set_host_call_opts(Some(&crate::HostCallOpts {
+            timeout_ms: match self.timeout_ms.load(Ordering::Relaxed) {
+                0 => None,
+                ms => Some(ms),
+            },
+            idempotent: opts.idempotent,
+            read_only: opts.read_only,
+            trace_context: ctx.trace_context.clone(),
+        }));
         let res = if !self.target.public_key.is_empty() {
             // actor-to-actor calls use namespace for the actor target identifier
-            host_call("", &self.target.public_key, req.method, req.arg.as_ref())?
+            host_call("", &self.target.public_key, req.method, req.arg.as_ref())
         } else {
             host_call(
                 &self.target.link_name,
                 &self.target.contract_id,
                 req.method,
                 req.arg.as_ref(),
-            )?
+            )
         };
-        Ok(res)
+        crate::actor::set_host_call_opts(None);
+        res
     }
 
-    fn set_timeout(&self, _interval: std::time::Duration) {
-        // not applicable here - host sets timeout, so ignore this
+    /// Sets the time limit for messages sent by this WasmHost.
+    /// Intervals under a millisecond are rounded up to 1ms, since zero means "use the host's default".
+    /// Hosts that don't support WASMBUS_HOST_CALL_OPTS_API_VERSION use their own timeout.
+    fn set_timeout(&self, interval: std::time::Duration) {
+        self.timeout_ms
+            .store((interval.as_millis() as u64).max(1), Ordering::Relaxed);
     }
 }
 
@@ -123,3 +165,66 @@ pub fn console_log(s: &str) {
         __console_log(s.as_ptr(), s.len());
     }
 }
+
+/// Logger that forwards `log` records to the host with `__console_log`.
+/// Records are filtered by `log::max_level()`, which the host may set with each message
+/// (see [HostContext](crate::HostContext)).
+pub struct ConsoleLogger {}
+
+static LOGGER: ConsoleLogger = ConsoleLogger {};
+
+impl log::Log for ConsoleLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            console_log(&format!(
+                "{} {}: {}",
+                record.level(),
+                record.target(),
+                record.args()
+            ));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Installs the [ConsoleLogger] and the panic hook. Called by the actor derive macro
+/// on each message; only the first call has any effect.
+pub fn init_guest() {
+    static INIT: std::sync::Once = std::sync::Once::new();
+    INIT.call_once(|| {
+        if log::set_logger(&LOGGER).is_ok() {
+            log::set_max_level(log::LevelFilter::Info);
+        }
+        // reports the panic message and location to the host as the error of the current message.
+        // The actor traps after the hook returns.
+        std::panic::set_hook(Box::new(|info| {
+            let payload = info.payload();
+            let msg = match (
+                payload.downcast_ref::<&str>(),
+                payload.downcast_ref::<String>(),
+            ) {
+                (Some(s), _) => *s,
+                (None, Some(s)) => s.as_str(),
+                (None, None) => "Box<dyn Any>",
+            };
+            let errmsg = match info.location() {
+                Some(loc) => format!(
+                    "actor panicked at '{}', {}:{}:{}",
+                    msg,
+                    loc.file(),
+                    loc.line(),
+                    loc.column()
+                ),
+                None => format!("actor panicked at '{}'", msg),
+            };
+            unsafe {
+                __guest_error(errmsg.as_ptr(), errmsg.len());
+            }
+        }));
+    });
+}
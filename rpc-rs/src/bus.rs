@@ -0,0 +1,398 @@
+//! Message bus used by RpcClient and HostBridge
+//!
+//! [NatsBus] sends messages over a nats connection, and is used by default.
+//! [InProcessBus] delivers messages between clients in the same process,
+//! so that providers can be run and tested without a nats server.
+//!
+#![cfg(not(target_arch = "wasm32"))]
+
+use crate::{RpcError, RpcResult};
+use async_trait::async_trait;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex as StdMutex,
+    },
+    time::Duration,
+};
+use tokio::sync::{mpsc, Mutex};
+
+/// A message received on a subscription
+#[derive(Clone, Debug)]
+pub struct BusMessage {
+    /// subject the message was published to
+    pub subject: String,
+    /// subject for the response, if the sender expects one
+    pub reply: Option<String>,
+    /// message payload
+    pub data: Vec<u8>,
+}
+
+/// Publish-subscribe message transport
+#[async_trait]
+pub trait MessageBus: Send + Sync {
+    /// Publishes a message, without waiting for a response
+    async fn publish(&self, subject: &str, data: &[u8]) -> RpcResult<()>;
+
+    /// Publishes a message and waits for a response.
+    /// If timeout is None, waits indefinitely.
+    async fn request(
+        &self,
+        subject: &str,
+        data: &[u8],
+        timeout: Option<Duration>,
+    ) -> RpcResult<Vec<u8>>;
+
+    /// Subscribes to a subject. Subjects may contain nats wildcards ('*' and '>')
+    async fn subscribe(&self, subject: &str) -> RpcResult<Arc<dyn BusSubscription>>;
+
+    /// Subscribes to a subject as a member of a queue group.
+    /// Each message is delivered to only one member of the group.
+    async fn queue_subscribe(
+        &self,
+        subject: &str,
+        queue: &str,
+    ) -> RpcResult<Arc<dyn BusSubscription>>;
+
+    /// Returns the maximum message size, or None if there is no limit
+    async fn max_payload(&self) -> Option<usize> {
+        None
+    }
+
+    /// Returns the nats connection, if this bus uses nats
+    #[doc(hidden)]
+    fn nats_connection(&self) -> Option<crate::anats::Connection> {
+        None
+    }
+}
+
+/// A subscription created by a [MessageBus]
+#[async_trait]
+pub trait BusSubscription: Send + Sync {
+    /// Returns the next message, or None if the subscription is closed
+    async fn next(&self) -> Option<BusMessage>;
+
+    /// Closes the subscription
+    async fn close(&self) -> RpcResult<()>;
+}
+
+/// MessageBus using a nats connection
+#[derive(Clone)]
+pub struct NatsBus {
+    nc: crate::anats::Connection,
+}
+
+impl NatsBus {
+    /// Constructs a message bus for the nats connection
+    pub fn new(nc: crate::anats::Connection) -> Self {
+        NatsBus { nc }
+    }
+
+    /// Returns the nats connection
+    pub fn connection(&self) -> &crate::anats::Connection {
+        &self.nc
+    }
+}
+
+#[async_trait]
+impl MessageBus for NatsBus {
+    async fn publish(&self, subject: &str, data: &[u8]) -> RpcResult<()> {
+        self.nc
+            .publish(subject, data)
+            .await
+            .map_err(|e| RpcError::Nats(e.to_string()))
+    }
+
+    async fn request(
+        &self,
+        subject: &str,
+        data: &[u8],
+        timeout: Option<Duration>,
+    ) -> RpcResult<Vec<u8>> {
+        let resp = if let Some(timeout) = timeout {
+            self.nc.request_timeout(subject, data, timeout).await
+        } else {
+            self.nc.request(subject, data).await
+        }
+        .map_err(|e| RpcError::Nats(e.to_string()))?;
+        Ok(resp.data)
+    }
+
+    async fn subscribe(&self, subject: &str) -> RpcResult<Arc<dyn BusSubscription>> {
+        let sub = self
+            .nc
+            .subscribe(subject)
+            .await
+            .map_err(|e| RpcError::Nats(e.to_string()))?;
+        Ok(Arc::new(NatsSubscription(sub)))
+    }
+
+    async fn queue_subscribe(
+        &self,
+        subject: &str,
+        queue: &str,
+    ) -> RpcResult<Arc<dyn BusSubscription>> {
+        let sub = self
+            .nc
+            .queue_subscribe(subject, queue)
+            .await
+            .map_err(|e| RpcError::Nats(e.to_string()))?;
+        Ok(Arc::new(NatsSubscription(sub)))
+    }
+
+    async fn max_payload(&self) -> Option<usize> {
+        // server returns 0 if it didn't send a limit
+        match self.nc.max_payload().await {
+            0 => None,
+            n => Some(n),
+        }
+    }
+
+    fn nats_connection(&self) -> Option<crate::anats::Connection> {
+        Some(self.nc.clone())
+    }
+}
+
+struct NatsSubscription(crate::anats::Subscription);
+
+#[async_trait]
+impl BusSubscription for NatsSubscription {
+    async fn next(&self) -> Option<BusMessage> {
+        self.0.next().await.map(|msg| BusMessage {
+            subject: msg.subject,
+            reply: msg.reply,
+            data: msg.data,
+        })
+    }
+
+    async fn close(&self) -> RpcResult<()> {
+        self.0
+            .clone()
+            .close()
+            .await
+            .map_err(|e| RpcError::Nats(e.to_string()))
+    }
+}
+
+/// MessageBus that delivers messages to subscribers in the same process.
+/// Clones share the same set of subscriptions.
+///
+/// ```ignore
+/// let bus = Arc::new(InProcessBus::default());
+/// let bridge = HostBridge::new_with_bus(bus.clone(), &host_data)?;
+/// ```
+#[derive(Clone, Default)]
+pub struct InProcessBus {
+    inner: Arc<InProcessInner>,
+}
+
+#[derive(Default)]
+struct InProcessInner {
+    subs: StdMutex<Vec<InProcessSub>>,
+    /// round-robin counters for queue groups, keyed by (subject, queue)
+    queues: StdMutex<HashMap<(String, String), usize>>,
+    next_id: AtomicU64,
+    max_payload: Option<usize>,
+}
+
+struct InProcessSub {
+    id: u64,
+    subject: String,
+    queue: Option<String>,
+    tx: mpsc::UnboundedSender<BusMessage>,
+}
+
+impl InProcessBus {
+    /// Constructs a bus that rejects messages larger than max_payload
+    pub fn with_max_payload(max_payload: usize) -> Self {
+        InProcessBus {
+            inner: Arc::new(InProcessInner {
+                max_payload: Some(max_payload),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Delivers the message to matching subscribers.
+    /// Returns the number of subscribers the message was delivered to.
+    pub fn publish_with_reply(
+        &self,
+        subject: &str,
+        reply: Option<&str>,
+        data: &[u8],
+    ) -> RpcResult<usize> {
+        if let Some(allowed) = self.inner.max_payload {
+            if data.len() > allowed {
+                return Err(RpcError::PayloadTooLarge {
+                    actual: data.len(),
+                    allowed,
+                });
+            }
+        }
+        let msg = BusMessage {
+            subject: subject.to_string(),
+            reply: reply.map(|s| s.to_string()),
+            data: data.to_vec(),
+        };
+        let subs = self.inner.subs.lock().unwrap();
+        let mut delivered = 0;
+        // each queue group receives one copy, other subscribers receive their own
+        let mut groups: HashMap<(&str, &str), Vec<&InProcessSub>> = HashMap::new();
+        for sub in subs.iter().filter(|s| subject_matches(&s.subject, subject)) {
+            match sub.queue.as_ref() {
+                Some(queue) => groups
+                    .entry((sub.subject.as_str(), queue.as_str()))
+                    .or_default()
+                    .push(sub),
+                None => {
+                    if sub.tx.send(msg.clone()).is_ok() {
+                        delivered += 1;
+                    }
+                }
+            }
+        }
+        let mut counters = self.inner.queues.lock().unwrap();
+        for ((sub_subject, queue), members) in groups.into_iter() {
+            let counter = counters
+                .entry((sub_subject.to_string(), queue.to_string()))
+                .or_default();
+            let member = members[*counter % members.len()];
+            *counter = counter.wrapping_add(1);
+            if member.tx.send(msg.clone()).is_ok() {
+                delivered += 1;
+            }
+        }
+        Ok(delivered)
+    }
+
+    /// Returns true if any subscription matches the subject
+    pub fn has_subscribers(&self, subject: &str) -> bool {
+        self.inner
+            .subs
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|s| subject_matches(&s.subject, subject))
+    }
+
+    fn add_sub(&self, subject: &str, queue: Option<&str>) -> Arc<dyn BusSubscription> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        self.inner.subs.lock().unwrap().push(InProcessSub {
+            id,
+            subject: subject.to_string(),
+            queue: queue.map(|s| s.to_string()),
+            tx,
+        });
+        Arc::new(InProcessSubscription {
+            id,
+            rx: Mutex::new(rx),
+            bus: self.inner.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl MessageBus for InProcessBus {
+    async fn publish(&self, subject: &str, data: &[u8]) -> RpcResult<()> {
+        self.publish_with_reply(subject, None, data).map(|_| ())
+    }
+
+    async fn request(
+        &self,
+        subject: &str,
+        data: &[u8],
+        timeout: Option<Duration>,
+    ) -> RpcResult<Vec<u8>> {
+        let inbox = format!("_INBOX.{}", crate::rpc_client::make_uuid());
+        let sub = self.add_sub(&inbox, None);
+        let result = match self.publish_with_reply(subject, Some(&inbox), data) {
+            Ok(0) => Err(RpcError::Nats(format!("no responders for {}", subject))),
+            Ok(_) => {
+                let resp = match timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, sub.next())
+                        .await
+                        .map_err(|_| RpcError::Timeout(format!("request to {}", subject))),
+                    None => Ok(sub.next().await),
+                };
+                resp.and_then(|msg| {
+                    msg.map(|m| m.data)
+                        .ok_or_else(|| RpcError::Nats("subscription closed".to_string()))
+                })
+            }
+            Err(e) => Err(e),
+        };
+        let _ = sub.close().await;
+        result
+    }
+
+    async fn subscribe(&self, subject: &str) -> RpcResult<Arc<dyn BusSubscription>> {
+        Ok(self.add_sub(subject, None))
+    }
+
+    async fn queue_subscribe(
+        &self,
+        subject: &str,
+        queue: &str,
+    ) -> RpcResult<Arc<dyn BusSubscription>> {
+        Ok(self.add_sub(subject, Some(queue)))
+    }
+
+    async fn max_payload(&self) -> Option<usize> {
+        self.inner.max_payload
+    }
+}
+
+struct InProcessSubscription {
+    id: u64,
+    rx: Mutex<mpsc::UnboundedReceiver<BusMessage>>,
+    bus: Arc<InProcessInner>,
+}
+
+#[async_trait]
+impl BusSubscription for InProcessSubscription {
+    async fn next(&self) -> Option<BusMessage> {
+        self.rx.lock().await.recv().await
+    }
+
+    async fn close(&self) -> RpcResult<()> {
+        // dropping the sender ends the stream after any queued messages are received
+        self.bus.subs.lock().unwrap().retain(|s| s.id != self.id);
+        Ok(())
+    }
+}
+
+impl Drop for InProcessSubscription {
+    fn drop(&mut self) {
+        if let Ok(mut subs) = self.bus.subs.lock() {
+            subs.retain(|s| s.id != self.id);
+        }
+    }
+}
+
+/// Returns true if the subject matches the subscription pattern,
+/// which may contain nats wildcards: '*' matches one token, and '>' matches one or more tokens.
+pub fn subject_matches(pattern: &str, subject: &str) -> bool {
+    let mut subject_tokens = subject.split('.');
+    for pat in pattern.split('.') {
+        match (pat, subject_tokens.next()) {
+            (">", Some(_)) => return true,
+            ("*", Some(_)) => {}
+            (p, Some(s)) if p == s => {}
+            _ => return false,
+        }
+    }
+    subject_tokens.next().is_none()
+}
+
+#[test]
+fn subject_wildcards() {
+    assert!(subject_matches("a.b.c", "a.b.c"));
+    assert!(!subject_matches("a.b.c", "a.b"));
+    assert!(!subject_matches("a.b", "a.b.c"));
+    assert!(subject_matches("a.*.c", "a.b.c"));
+    assert!(!subject_matches("a.*", "a.b.c"));
+    assert!(subject_matches("a.>", "a.b.c"));
+    assert!(!subject_matches("a.>", "a"));
+}
@@ -0,0 +1,208 @@
+//! Per-target circuit breaker for RpcClient
+//!
+//! After `failure_threshold` consecutive timeouts or nats errors sending to a target,
+//! the circuit for that target opens, and messages to it fail immediately with
+//! `RpcError::CircuitOpen`. When the cool-down period has passed, the circuit
+//! is half-open: one message is allowed through. If it succeeds, the circuit closes;
+//! if it fails, the circuit opens for another cool-down period.
+//!
+//! Error responses from the target (`RpcError::Rpc`) show that the target is reachable,
+//! and are not counted as failures.
+//!
+#![cfg(not(target_arch = "wasm32"))]
+
+use crate::RpcError;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Settings for the RpcClient circuit breaker
+#[derive(Clone, Debug)]
+pub struct CircuitBreakerConfig {
+    /// number of consecutive failures that opens the circuit
+    pub failure_threshold: u32,
+    /// time the circuit stays open before a trial message is allowed
+    pub cool_down: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        CircuitBreakerConfig {
+            failure_threshold: 5,
+            cool_down: Duration::from_secs(30),
+        }
+    }
+}
+
+/// State of the circuit for a target
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// messages are sent normally
+    Closed,
+    /// messages fail immediately with RpcError::CircuitOpen
+    Open,
+    /// one trial message is allowed, to determine whether the target has recovered
+    HalfOpen,
+}
+
+impl fmt::Display for CircuitState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CircuitState::Closed => "closed",
+            CircuitState::Open => "open",
+            CircuitState::HalfOpen => "half-open",
+        })
+    }
+}
+
+/// A change in circuit state: (previous, new)
+pub(crate) type Transition = (CircuitState, CircuitState);
+
+pub(crate) struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    /// circuits that are open, half-open, or have recent failures, keyed by target url
+    circuits: Mutex<HashMap<String, Circuit>>,
+}
+
+struct Circuit {
+    state: CircuitState,
+    /// consecutive failures while closed
+    failures: u32,
+    /// time the circuit opened, or the trial message was sent
+    since: Instant,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(config: CircuitBreakerConfig) -> Self {
+        CircuitBreaker {
+            config,
+            circuits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the current state of the circuit for the target
+    pub(crate) fn state(&self, target_url: &str) -> CircuitState {
+        self.circuits
+            .lock()
+            .ok()
+            .and_then(|circuits| circuits.get(target_url).map(|c| c.state))
+            .unwrap_or(CircuitState::Closed)
+    }
+
+    /// Checks whether a message may be sent to the target.
+    /// Returns an error if the circuit is open, and the state change, if any.
+    pub(crate) fn allow(&self, target_url: &str) -> Result<Option<Transition>, RpcError> {
+        let mut circuits = match self.circuits.lock() {
+            Ok(circuits) => circuits,
+            Err(_) => return Ok(None),
+        };
+        let circuit = match circuits.get_mut(target_url) {
+            Some(circuit) => circuit,
+            None => return Ok(None),
+        };
+        match circuit.state {
+            CircuitState::Closed => Ok(None),
+            // allow one trial message after the cool-down. If a trial message
+            // never completes, allow another after a further cool-down
+            CircuitState::Open | CircuitState::HalfOpen
+                if circuit.since.elapsed() >= self.config.cool_down =>
+            {
+                let previous = circuit.state;
+                circuit.state = CircuitState::HalfOpen;
+                circuit.since = Instant::now();
+                Ok(if previous != CircuitState::HalfOpen {
+                    Some((previous, CircuitState::HalfOpen))
+                } else {
+                    None
+                })
+            }
+            _ => Err(RpcError::CircuitOpen(target_url.to_string())),
+        }
+    }
+
+    /// Records the result of sending to the target.
+    /// Returns the state change, if any.
+    pub(crate) fn record(&self, target_url: &str, success: bool) -> Option<Transition> {
+        let mut circuits = self.circuits.lock().ok()?;
+        if success {
+            // closed circuits without failures aren't stored
+            let circuit = circuits.remove(target_url)?;
+            return match circuit.state {
+                CircuitState::Closed => None,
+                previous => Some((previous, CircuitState::Closed)),
+            };
+        }
+        let circuit = circuits
+            .entry(target_url.to_string())
+            .or_insert_with(|| Circuit {
+                state: CircuitState::Closed,
+                failures: 0,
+                since: Instant::now(),
+            });
+        match circuit.state {
+            CircuitState::Closed => {
+                circuit.failures += 1;
+                if circuit.failures >= self.config.failure_threshold {
+                    circuit.state = CircuitState::Open;
+                    circuit.since = Instant::now();
+                    Some((CircuitState::Closed, CircuitState::Open))
+                } else {
+                    None
+                }
+            }
+            CircuitState::HalfOpen => {
+                circuit.state = CircuitState::Open;
+                circuit.since = Instant::now();
+                Some((CircuitState::HalfOpen, CircuitState::Open))
+            }
+            CircuitState::Open => None,
+        }
+    }
+}
+
+/// Returns true if the error indicates the target could not be reached
+pub(crate) fn is_failure(e: &RpcError) -> bool {
+    matches!(
+        e,
+        RpcError::Timeout(_) | RpcError::Nats(_) | RpcError::DeadlineExceeded(_)
+    )
+}
+
+#[test]
+fn circuit_transitions() {
+    let breaker = CircuitBreaker::new(CircuitBreakerConfig {
+        failure_threshold: 2,
+        cool_down: Duration::from_millis(0),
+    });
+    let url = "wasmbus://MTARGET";
+    assert_eq!(breaker.record(url, false), None);
+    assert_eq!(
+        breaker.record(url, false),
+        Some((CircuitState::Closed, CircuitState::Open))
+    );
+    // cool-down has passed, so one trial message is allowed
+    assert_eq!(
+        breaker.allow(url).unwrap(),
+        Some((CircuitState::Open, CircuitState::HalfOpen))
+    );
+    assert_eq!(
+        breaker.record(url, false),
+        Some((CircuitState::HalfOpen, CircuitState::Open))
+    );
+    assert!(breaker.allow(url).is_ok());
+    assert_eq!(
+        breaker.record(url, true),
+        Some((CircuitState::HalfOpen, CircuitState::Closed))
+    );
+    assert_eq!(breaker.state(url), CircuitState::Closed);
+
+    let slow = CircuitBreaker::new(CircuitBreakerConfig {
+        failure_threshold: 1,
+        cool_down: Duration::from_secs(60),
+    });
+    slow.record(url, false);
+    assert!(matches!(slow.allow(url), Err(RpcError::CircuitOpen(_))));
+}
@@ -14,6 +14,9 @@ pub struct Message<'m> {
 
 /// context data
 pub mod context {
+    use crate::core::WasmCloudEntity;
+    use serde::{Deserialize, Serialize};
+    use std::{cell::RefCell, collections::HashMap};
 
     /// Context - message passing metadata used by wasmhost Actors and Capability Providers
     #[derive(Default, Debug, Clone)]
@@ -23,6 +26,166 @@ pub mod context {
 
         /// Span name/context for tracing. This is a placeholder for now
         pub span: Option<String>,
+
+        /// Messages received by actors have the sending actor or provider,
+        /// if the host supplies it (see [HostContext])
+        pub origin: Option<WasmCloudEntity>,
+
+        /// id of the invocation that delivered the message, if the host supplies it
+        pub invocation_id: Option<String>,
+
+        /// trace propagation headers, for example "traceparent" and "tracestate"
+        pub trace_context: HashMap<String, String>,
+    }
+
+    /// Message metadata that a host sends to an actor with each message.
+    ///
+    /// Actors built with api version [WASMBUS_CONTEXT_API_VERSION](crate::WASMBUS_CONTEXT_API_VERSION)
+    /// or later (as reported by their `__actor_api_version` export) also export
+    /// `__guest_context(len: i32) -> i32`. Before calling `__guest_call`, the host may call
+    /// `__guest_context` with the length of the serialized HostContext, write the
+    /// serialized HostContext to the returned address, and the actor decodes it into
+    /// the [Context] for the next message. Hosts that don't call `__guest_context`,
+    /// and actors that don't export it, continue to use a default Context.
+    #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct HostContext {
+        /// the actor or provider that sent the message
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub origin: Option<WasmCloudEntity>,
+        /// invocation id
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub invocation_id: Option<String>,
+        /// trace propagation headers
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        pub trace_context: HashMap<String, String>,
+        /// maximum level of `log` records the actor sends to the host, such as "debug" or "off".
+        /// If None, the level is unchanged (initially "info").
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub log_level: Option<String>,
+    }
+
+    impl From<HostContext> for Context {
+        fn from(hc: HostContext) -> Context {
+            Context {
+                actor: hc
+                    .origin
+                    .as_ref()
+                    .filter(|o| o.is_actor())
+                    .map(|o| o.public_key.clone()),
+                span: None,
+                origin: hc.origin,
+                invocation_id: hc.invocation_id,
+                trace_context: hc.trace_context,
+            }
+        }
+    }
+
+    impl From<&Context> for HostContext {
+        fn from(ctx: &Context) -> HostContext {
+            HostContext {
+                origin: ctx.origin.clone().or_else(|| {
+                    ctx.actor.as_ref().map(|actor| WasmCloudEntity {
+                        public_key: actor.clone(),
+                        ..Default::default()
+                    })
+                }),
+                invocation_id: ctx.invocation_id.clone(),
+                trace_context: ctx.trace_context.clone(),
+                log_level: None,
+            }
+        }
+    }
+
+    thread_local! {
+        /// serialized HostContext written by the host for the next message
+        static HOST_CONTEXT: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Allocates space for the host to write the serialized HostContext,
+    /// and returns its address. Called from the actor's `__guest_context` export.
+    #[doc(hidden)]
+    pub fn alloc_host_context(len: usize) -> *mut u8 {
+        HOST_CONTEXT.with(|buf| {
+            let mut buf = buf.borrow_mut();
+            *buf = vec![0u8; len];
+            buf.as_mut_ptr()
+        })
+    }
+
+    /// Returns the Context for the current message, decoded from the HostContext
+    /// written by the host, or a default Context if there is none.
+    /// Applies the host's log level, if any. Called from the actor's `__guest_call` export.
+    #[doc(hidden)]
+    pub fn take_host_context() -> Context {
+        let buf = HOST_CONTEXT.with(|buf| std::mem::take(&mut *buf.borrow_mut()));
+        if buf.is_empty() {
+            return Context::default();
+        }
+        match crate::deserialize::<HostContext>(&buf) {
+            Ok(hc) => {
+                if let Some(level) = hc.log_level.as_deref().and_then(|l| l.parse().ok()) {
+                    log::set_max_level(level);
+                }
+                Context::from(hc)
+            }
+            Err(_) => Context::default(),
+        }
+    }
+
+    /// Options for a message an actor sends with `__host_call`.
+    ///
+    /// Actors built with api version
+    /// [WASMBUS_HOST_CALL_OPTS_API_VERSION](crate::WASMBUS_HOST_CALL_OPTS_API_VERSION) or later
+    /// export `__host_call_opts_len() -> i32` and `__host_call_opts() -> i32`, which return the length
+    /// and address of the serialized HostCallOpts for the host call in progress.
+    /// Hosts may call them while handling `__host_call`; hosts that don't use their own defaults.
+    #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct HostCallOpts {
+        /// time limit for the call, in milliseconds. If None, the host's default is used
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub timeout_ms: Option<u64>,
+        /// the message is idempotent, and may be retried
+        #[serde(default)]
+        pub idempotent: bool,
+        /// the message does not change the receiver's state, and may be retried
+        #[serde(default)]
+        pub read_only: bool,
+        /// trace propagation headers
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        pub trace_context: HashMap<String, String>,
+    }
+
+    impl HostCallOpts {
+        /// Returns the send options
+        pub fn send_opts(&self) -> super::SendOpts {
+            super::SendOpts::default()
+                .idempotent(self.idempotent)
+                .read_only(self.read_only)
+        }
+    }
+
+    thread_local! {
+        /// serialized HostCallOpts for the host call in progress
+        static HOST_CALL_OPTS: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Sets the options for the next host call, or clears them if None
+    #[doc(hidden)]
+    pub fn set_host_call_opts(opts: Option<&HostCallOpts>) {
+        let buf = opts
+            .and_then(|opts| crate::serialize(opts).ok())
+            .unwrap_or_default();
+        HOST_CALL_OPTS.with(|cell| *cell.borrow_mut() = buf);
+    }
+
+    /// Returns the address and length of the serialized options for the host call in progress.
+    /// Called from the actor's `__host_call_opts` and `__host_call_opts_len` exports.
+    #[doc(hidden)]
+    pub fn host_call_opts() -> (*const u8, usize) {
+        HOST_CALL_OPTS.with(|cell| {
+            let buf = cell.borrow();
+            (buf.as_ptr(), buf.len())
+        })
     }
 }
 
@@ -139,6 +302,14 @@ pub enum RpcError {
     #[error("timeout: {0}")]
     Timeout(String),
 
+    /// Message payload is larger than the server or receiver allows
+    #[error("payload too large: {actual} bytes exceeds limit of {allowed} bytes")]
+    PayloadTooLarge { actual: usize, allowed: usize },
+
+    /// The circuit breaker for the target is open, so the message was not sent
+    #[error("circuit open: {0}")]
+    CircuitOpen(String),
+
     //#[error("IO error")]
     //IO([from] std::io::Error)
     /// Anything else
@@ -183,4 +354,72 @@ pub trait MessageDispatch {
         ctx: &context::Context,
         message: Message<'_>,
     ) -> Result<Message<'_>, RpcError>;
+
+    /// Returns descriptions of the services this receiver handles.
+    /// Receivers generated by the `Actor` and `Provider` derive macros (or `#[provider]`)
+    /// return their services, and respond to [INTROSPECT_METHOD] messages with this list.
+    /// The services' methods are listed if the receiver is derived with `#[introspect]`.
+    fn services(&self) -> Vec<ServiceDescriptor> {
+        Vec::new()
+    }
+}
+
+/// Method of the message that asks a receiver for its [ServiceDescriptor]s.
+/// The message has no arg, and the response is the serialized `Vec<ServiceDescriptor>`.
+pub const INTROSPECT_METHOD: &str = "Wasmbus.Introspect";
+
+/// Description of a service's methods, generated by codegen for each service
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceDescriptor {
+    /// service name, which is the prefix of the method in messages, as in "KeyValue"
+    pub name: Cow<'static, str>,
+    /// capability contract id, for services implemented by capability providers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contract_id: Option<Cow<'static, str>>,
+    /// the service's methods
+    pub methods: Cow<'static, [MethodDescriptor]>,
+}
+
+impl ServiceDescriptor {
+    /// Returns true if the message method (in the form "Service.Method") is a method of this service
+    pub fn has_method(&self, method: &str) -> bool {
+        match method.rsplit_once('.') {
+            Some((service, method)) => {
+                service == self.name && self.methods.iter().any(|m| m.name == method)
+            }
+            None => false,
+        }
+    }
+}
+
+/// Description of a service method
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MethodDescriptor {
+    /// method name, without the service prefix, as in "Get"
+    pub name: Cow<'static, str>,
+    /// smithy shape id of the input type, or None if the method has no input
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<Cow<'static, str>>,
+    /// smithy shape id of the output type, or None if the method has no output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<Cow<'static, str>>,
+}
+
+/// Asks the actor or provider at the other end of the transport for the services
+/// and methods it handles
+pub async fn introspect<T: Transport + Sync + ?Sized>(
+    transport: &T,
+    ctx: &context::Context,
+) -> Result<Vec<ServiceDescriptor>, RpcError> {
+    let resp = transport
+        .send(
+            ctx,
+            Message {
+                method: INTROSPECT_METHOD,
+                arg: Cow::Borrowed(&[]),
+            },
+            Some(SendOpts::default().read_only(true).idempotent(true)),
+        )
+        .await?;
+    deserialize(&resp)
 }
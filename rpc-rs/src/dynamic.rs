@@ -0,0 +1,653 @@
+//! Schema-driven json client
+//!
+//! `RpcClient::send_json` converts json to msgpack without type information,
+//! so integer widths, blobs, and timestamps may not match the encoding
+//! expected by a receiver built with generated code.
+//! [SchemaCodec] uses the smithy model of the interface to convert
+//! json to the exact wire encoding of an operation's input,
+//! and to convert the operation's output back to json.
+//!
+//! Json representation of smithy types:
+//! - Blob: base64 string, or array of byte values
+//! - Timestamp: RFC3339 string, integer seconds since the epoch, or `{"sec":_,"nsec":_}`.
+//!   Output timestamps are RFC3339 strings.
+//! - Document: any json value
+//! - Structures: json objects whose keys are the serialized member names
+//!   (the declared name, unless overridden with `@serialization(name: ...)`)
+//!
+//! Validation errors name the path to the invalid value, for example `input.items[2].name`.
+//!
+//! ```ignore
+//! let codec = SchemaCodec::from_sources(&["./interface.smithy".parse()?], Path::new("."))?;
+//! let client = DynamicClient::new(rpc_client, codec);
+//! let resp = client
+//!     .send_json(origin, actor_id, "KeyValue.Get", json!({ "key": "abc" }))
+//!     .await?;
+//! ```
+#![cfg(not(target_arch = "wasm32"))]
+
+use crate::{core::WasmCloudEntity, Message, RpcClient, RpcError, RpcResult, Timestamp};
+use atelier_core::model::{
+    shapes::{HasTraits, MemberShape, Operation, ShapeKind, Simple},
+    values::Value as NodeValue,
+    HasIdentity, Model, ShapeID,
+};
+use chrono::{DateTime, Utc};
+use rmpv::Value as MsgValue;
+use serde_json::Value as JsonValue;
+use std::{borrow::Cow, convert::TryFrom, path::Path};
+pub use weld_codegen::config::ModelSource;
+use weld_codegen::is_optional_type;
+
+const PRELUDE_NAMESPACE: &str = "smithy.api";
+const WASMCLOUD_MODEL_NAMESPACE: &str = "org.wasmcloud.model";
+
+/// Converts json to and from the wire encoding of operations in a smithy model
+pub struct SchemaCodec {
+    model: Model,
+}
+
+impl SchemaCodec {
+    /// Loads the model from smithy sources (files, directories, or urls).
+    /// Relative paths are resolved from base_dir.
+    pub fn from_sources(sources: &[ModelSource], base_dir: &Path) -> RpcResult<Self> {
+        let model = weld_codegen::sources_to_model(sources, base_dir, 0)
+            .map_err(|e| RpcError::Other(format!("loading model: {}", e)))?;
+        Ok(Self::from_model(model))
+    }
+
+    /// Constructs a codec from a model that has already been loaded
+    pub fn from_model(model: Model) -> Self {
+        SchemaCodec { model }
+    }
+
+    /// Returns the model
+    pub fn model(&self) -> &Model {
+        &self.model
+    }
+
+    /// Converts json to the serialized input of the operation.
+    /// `method` is the dispatch name of the operation, in the form "Service.Operation".
+    /// If the operation has no input, the value is ignored and the result is empty.
+    pub fn encode_input(&self, method: &str, input: &JsonValue) -> RpcResult<Vec<u8>> {
+        match self.find_operation(method)?.input() {
+            Some(id) => {
+                let val = self.encode_shape(id, input, "input", false)?;
+                let mut buf = Vec::new();
+                rmpv::encode::write_value(&mut buf, &val)
+                    .map_err(|e| RpcError::Ser(format!("encoding {}: {}", method, e)))?;
+                Ok(buf)
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Converts the serialized output of the operation to json.
+    /// If the operation has no output, returns `JsonValue::Null`.
+    pub fn decode_output(&self, method: &str, buf: &[u8]) -> RpcResult<JsonValue> {
+        match self.find_operation(method)?.output() {
+            Some(id) => {
+                let val = rmpv::decode::read_value(&mut &buf[..])
+                    .map_err(|e| RpcError::Deser(format!("decoding {}: {}", method, e)))?;
+                self.decode_shape(id, &val, "output")
+            }
+            None => Ok(JsonValue::Null),
+        }
+    }
+
+    /// Finds the operation with the dispatch name "Service.Operation".
+    /// Names are compared without case, since dispatch names are PascalCase
+    /// and smithy operation names are usually camelCase.
+    fn find_operation(&self, method: &str) -> RpcResult<&Operation> {
+        let (service_name, op_name) = method
+            .split_once('.')
+            .ok_or_else(|| RpcError::MethodNotHandled(method.to_string()))?;
+        self.model
+            .shapes()
+            .filter(|s| {
+                s.id()
+                    .shape_name()
+                    .to_string()
+                    .eq_ignore_ascii_case(service_name)
+            })
+            .filter_map(|s| match s.body() {
+                ShapeKind::Service(service) => Some(service),
+                _ => None,
+            })
+            .flat_map(|service| service.operations())
+            .filter(|id| id.shape_name().to_string().eq_ignore_ascii_case(op_name))
+            .find_map(|id| match self.model.shape(id).map(|s| s.body()) {
+                Some(ShapeKind::Operation(op)) => Some(op),
+                _ => None,
+            })
+            .ok_or_else(|| RpcError::MethodNotHandled(method.to_string()))
+    }
+
+    /// Returns the scalar type of a prelude shape, a wasmcloud model integer,
+    /// or a simple shape declared in the model
+    fn scalar(&self, id: &ShapeID) -> Option<Scalar> {
+        let name = id.shape_name().to_string();
+        match id.namespace().to_string().as_str() {
+            PRELUDE_NAMESPACE => Scalar::from_prelude(name.trim_start_matches("Primitive")),
+            WASMCLOUD_MODEL_NAMESPACE => Scalar::from_wasmcloud_model(&name),
+            _ => None,
+        }
+        .or_else(|| match self.model.shape(id).map(|s| s.body()) {
+            Some(ShapeKind::Simple(simple)) => Some(Scalar::from(simple)),
+            _ => None,
+        })
+    }
+
+    /// Converts json to a msgpack value of the shape.
+    /// `as_bytes` is true for structure members that generated code serializes
+    /// with serde_bytes; other blobs are serialized as arrays of integers.
+    fn encode_shape(
+        &self,
+        id: &ShapeID,
+        val: &JsonValue,
+        path: &str,
+        as_bytes: bool,
+    ) -> RpcResult<MsgValue> {
+        if let Some(scalar) = self.scalar(id) {
+            return scalar.encode(val, path, as_bytes);
+        }
+        match self.model.shape(id).map(|s| s.body()) {
+            Some(ShapeKind::List(list)) | Some(ShapeKind::Set(list)) => {
+                let items = val
+                    .as_array()
+                    .ok_or_else(|| invalid(path, "expected array"))?;
+                let member = list.member().target();
+                Ok(MsgValue::Array(
+                    items
+                        .iter()
+                        .enumerate()
+                        .map(|(i, v)| {
+                            self.encode_shape(member, v, &format!("{}[{}]", path, i), false)
+                        })
+                        .collect::<RpcResult<Vec<_>>>()?,
+                ))
+            }
+            Some(ShapeKind::Map(map)) => {
+                let obj = val
+                    .as_object()
+                    .ok_or_else(|| invalid(path, "expected object"))?;
+                let mut entries = Vec::with_capacity(obj.len());
+                for (k, v) in obj.iter() {
+                    let item_path = format!("{}[{:?}]", path, k);
+                    entries.push((
+                        self.encode_shape(
+                            map.key().target(),
+                            &JsonValue::String(k.clone()),
+                            &item_path,
+                            false,
+                        )?,
+                        self.encode_shape(map.value().target(), v, &item_path, false)?,
+                    ));
+                }
+                Ok(MsgValue::Map(entries))
+            }
+            Some(ShapeKind::Structure(strukt)) => {
+                let obj = val
+                    .as_object()
+                    .ok_or_else(|| invalid(path, "expected object"))?;
+                let members = strukt.members().collect::<Vec<_>>();
+                if let Some(unknown) = obj.keys().find(|k| {
+                    !members
+                        .iter()
+                        .any(|m| &ser_name(m) == *k || &m.id().to_string() == *k)
+                }) {
+                    return Err(invalid(&format!("{}.{}", path, unknown), "unknown field"));
+                }
+                let mut fields = Vec::with_capacity(members.len());
+                for member in members.iter() {
+                    let name = ser_name(member);
+                    let field_path = format!("{}.{}", path, &name);
+                    let field = obj
+                        .get(&name)
+                        .or_else(|| obj.get(&member.id().to_string()))
+                        .filter(|v| !v.is_null());
+                    let encoded = match field {
+                        Some(v) => Some(self.encode_shape(
+                            member.target(),
+                            v,
+                            &field_path,
+                            is_blob(member.target()),
+                        )?),
+                        None if member.is_required() => {
+                            return Err(invalid(&field_path, "missing required field"))
+                        }
+                        // optional fields are skipped when serialized
+                        None if is_optional_type(member) => None,
+                        // non-optional number and boolean fields are serialized with their zero value
+                        None => Some(
+                            self.scalar(member.target())
+                                .and_then(|s| s.zero())
+                                .ok_or_else(|| invalid(&field_path, "missing field"))?,
+                        ),
+                    };
+                    if let Some(v) = encoded {
+                        fields.push((MsgValue::from(name), v));
+                    }
+                }
+                Ok(MsgValue::Map(fields))
+            }
+            Some(ShapeKind::Union(_)) => Err(invalid(path, "union types are not supported")),
+            _ => Err(invalid(
+                path,
+                &format!("unsupported or unknown shape {}", id),
+            )),
+        }
+    }
+
+    /// Converts a msgpack value of the shape to json
+    fn decode_shape(&self, id: &ShapeID, val: &MsgValue, path: &str) -> RpcResult<JsonValue> {
+        if let Some(scalar) = self.scalar(id) {
+            return scalar.decode(val, path);
+        }
+        match self.model.shape(id).map(|s| s.body()) {
+            Some(ShapeKind::List(list)) | Some(ShapeKind::Set(list)) => {
+                let items = val
+                    .as_array()
+                    .ok_or_else(|| mismatch(path, "expected array", val))?;
+                Ok(JsonValue::Array(
+                    items
+                        .iter()
+                        .enumerate()
+                        .map(|(i, v)| {
+                            self.decode_shape(
+                                list.member().target(),
+                                v,
+                                &format!("{}[{}]", path, i),
+                            )
+                        })
+                        .collect::<RpcResult<Vec<_>>>()?,
+                ))
+            }
+            Some(ShapeKind::Map(map)) => {
+                let entries = val
+                    .as_map()
+                    .ok_or_else(|| mismatch(path, "expected map", val))?;
+                let mut obj = serde_json::Map::with_capacity(entries.len());
+                for (k, v) in entries.iter() {
+                    let key = k
+                        .as_str()
+                        .ok_or_else(|| mismatch(path, "expected string key", k))?;
+                    let item_path = format!("{}[{:?}]", path, key);
+                    obj.insert(
+                        key.to_string(),
+                        self.decode_shape(map.value().target(), v, &item_path)?,
+                    );
+                }
+                Ok(JsonValue::Object(obj))
+            }
+            Some(ShapeKind::Structure(strukt)) => {
+                let entries = val
+                    .as_map()
+                    .ok_or_else(|| mismatch(path, "expected map", val))?;
+                let mut obj = serde_json::Map::with_capacity(entries.len());
+                for member in strukt.members() {
+                    let name = ser_name(member);
+                    let field_path = format!("{}.{}", path, &name);
+                    match entries.iter().find(|(k, _)| k.as_str() == Some(&name)) {
+                        Some((_, MsgValue::Nil)) | None if member.is_required() => {
+                            return Err(RpcError::Deser(format!(
+                                "{}: missing required field",
+                                field_path
+                            )));
+                        }
+                        Some((_, MsgValue::Nil)) | None => {}
+                        Some((_, v)) => {
+                            obj.insert(name, self.decode_shape(member.target(), v, &field_path)?);
+                        }
+                    }
+                }
+                Ok(JsonValue::Object(obj))
+            }
+            Some(ShapeKind::Union(_)) => Err(RpcError::Deser(format!(
+                "{}: union types are not supported",
+                path
+            ))),
+            _ => Err(RpcError::Deser(format!(
+                "{}: unsupported or unknown shape {}",
+                path, id
+            ))),
+        }
+    }
+}
+
+/// Sends json messages to actors and providers, using a [SchemaCodec] for the wire encoding
+pub struct DynamicClient {
+    client: RpcClient,
+    codec: SchemaCodec,
+}
+
+impl DynamicClient {
+    /// Constructs a dynamic client
+    pub fn new(client: RpcClient, codec: SchemaCodec) -> Self {
+        DynamicClient { client, codec }
+    }
+
+    /// Returns the codec
+    pub fn codec(&self) -> &SchemaCodec {
+        &self.codec
+    }
+
+    /// Returns the rpc client
+    pub fn client(&self) -> &RpcClient {
+        &self.client
+    }
+
+    /// Send a json message, encoded as the operation's input shape,
+    /// and return the response converted to json.
+    /// `method` is the dispatch name of the operation, in the form "Service.Operation".
+    pub async fn send_json<Target>(
+        &self,
+        origin: WasmCloudEntity,
+        target: Target,
+        method: &str,
+        data: JsonValue,
+    ) -> RpcResult<JsonValue>
+    where
+        Target: Into<WasmCloudEntity>,
+    {
+        let arg = self.codec.encode_input(method, &data)?;
+        let resp = self
+            .client
+            .send(
+                origin,
+                target,
+                Message {
+                    method,
+                    arg: Cow::Owned(arg),
+                },
+            )
+            .await?;
+        self.codec.decode_output(method, &resp)
+    }
+}
+
+/// Scalar (non-aggregate) types, and their msgpack encoding
+#[derive(Clone, Copy, Debug)]
+enum Scalar {
+    Blob,
+    Boolean,
+    String,
+    Timestamp,
+    Document,
+    Float,
+    Double,
+    Int { min: i128, max: i128 },
+    Unsupported(&'static str),
+}
+
+impl From<&Simple> for Scalar {
+    fn from(simple: &Simple) -> Scalar {
+        match simple {
+            Simple::Blob => Scalar::Blob,
+            Simple::Boolean => Scalar::Boolean,
+            Simple::String => Scalar::String,
+            Simple::Byte => Scalar::int(i8::MIN as i128, i8::MAX as i128),
+            Simple::Short => Scalar::int(i16::MIN as i128, i16::MAX as i128),
+            Simple::Integer => Scalar::int(i32::MIN as i128, i32::MAX as i128),
+            Simple::Long => Scalar::int(i64::MIN as i128, i64::MAX as i128),
+            Simple::Float => Scalar::Float,
+            Simple::Double => Scalar::Double,
+            Simple::Document => Scalar::Document,
+            Simple::Timestamp => Scalar::Timestamp,
+            Simple::BigInteger => Scalar::Unsupported("BigInteger"),
+            Simple::BigDecimal => Scalar::Unsupported("BigDecimal"),
+        }
+    }
+}
+
+impl Scalar {
+    fn int(min: i128, max: i128) -> Scalar {
+        Scalar::Int { min, max }
+    }
+
+    fn from_prelude(name: &str) -> Option<Scalar> {
+        let simple = match name {
+            "Blob" => Simple::Blob,
+            "Boolean" => Simple::Boolean,
+            "String" => Simple::String,
+            "Byte" => Simple::Byte,
+            "Short" => Simple::Short,
+            "Integer" => Simple::Integer,
+            "Long" => Simple::Long,
+            "Float" => Simple::Float,
+            "Double" => Simple::Double,
+            "Document" => Simple::Document,
+            "Timestamp" => Simple::Timestamp,
+            "BigInteger" => Simple::BigInteger,
+            "BigDecimal" => Simple::BigDecimal,
+            _ => return None,
+        };
+        Some(Scalar::from(&simple))
+    }
+
+    fn from_wasmcloud_model(name: &str) -> Option<Scalar> {
+        match name {
+            "U8" => Some(Scalar::int(0, u8::MAX as i128)),
+            "U16" => Some(Scalar::int(0, u16::MAX as i128)),
+            "U32" => Some(Scalar::int(0, u32::MAX as i128)),
+            "U64" => Some(Scalar::int(0, u64::MAX as i128)),
+            "I8" => Some(Scalar::int(i8::MIN as i128, i8::MAX as i128)),
+            "I16" => Some(Scalar::int(i16::MIN as i128, i16::MAX as i128)),
+            "I32" => Some(Scalar::int(i32::MIN as i128, i32::MAX as i128)),
+            "I64" => Some(Scalar::int(i64::MIN as i128, i64::MAX as i128)),
+            _ => None,
+        }
+    }
+
+    /// the value serialized for a missing non-optional field
+    fn zero(&self) -> Option<MsgValue> {
+        match self {
+            Scalar::Boolean => Some(MsgValue::Boolean(false)),
+            Scalar::Int { .. } => Some(MsgValue::from(0)),
+            Scalar::Float => Some(MsgValue::F32(0.0)),
+            Scalar::Double => Some(MsgValue::F64(0.0)),
+            _ => None,
+        }
+    }
+
+    fn encode(&self, val: &JsonValue, path: &str, as_bytes: bool) -> RpcResult<MsgValue> {
+        match self {
+            Scalar::Boolean => val
+                .as_bool()
+                .map(MsgValue::Boolean)
+                .ok_or_else(|| invalid(path, "expected boolean")),
+            Scalar::String => val
+                .as_str()
+                .map(MsgValue::from)
+                .ok_or_else(|| invalid(path, "expected string")),
+            Scalar::Int { min, max } => {
+                let n = val
+                    .as_i64()
+                    .map(|n| n as i128)
+                    .or_else(|| val.as_u64().map(|n| n as i128))
+                    .ok_or_else(|| invalid(path, "expected integer"))?;
+                if n < *min || n > *max {
+                    return Err(invalid(
+                        path,
+                        &format!("{} is out of range ({}..={})", n, min, max),
+                    ));
+                }
+                Ok(if n < 0 {
+                    MsgValue::from(n as i64)
+                } else {
+                    MsgValue::from(n as u64)
+                })
+            }
+            Scalar::Float => val
+                .as_f64()
+                .map(|f| MsgValue::F32(f as f32))
+                .ok_or_else(|| invalid(path, "expected number")),
+            Scalar::Double => val
+                .as_f64()
+                .map(MsgValue::F64)
+                .ok_or_else(|| invalid(path, "expected number")),
+            Scalar::Blob => {
+                let bytes = json_to_bytes(val, path)?;
+                Ok(if as_bytes {
+                    MsgValue::Binary(bytes)
+                } else {
+                    MsgValue::Array(bytes.into_iter().map(MsgValue::from).collect())
+                })
+            }
+            Scalar::Document => {
+                // generated code declares documents as Vec<u8>, which is serialized as an array
+                let bytes = serde_json::to_vec(val).map_err(|e| invalid(path, &e.to_string()))?;
+                Ok(MsgValue::Array(
+                    bytes.into_iter().map(MsgValue::from).collect(),
+                ))
+            }
+            Scalar::Timestamp => {
+                let ts = json_to_timestamp(val, path)?;
+                Ok(MsgValue::Map(vec![
+                    (MsgValue::from("sec"), MsgValue::from(ts.sec)),
+                    (MsgValue::from("nsec"), MsgValue::from(ts.nsec)),
+                ]))
+            }
+            Scalar::Unsupported(name) => Err(invalid(path, &format!("{} is not supported", name))),
+        }
+    }
+
+    fn decode(&self, val: &MsgValue, path: &str) -> RpcResult<JsonValue> {
+        match self {
+            Scalar::Boolean => val
+                .as_bool()
+                .map(JsonValue::Bool)
+                .ok_or_else(|| mismatch(path, "expected boolean", val)),
+            Scalar::String => val
+                .as_str()
+                .map(|s| JsonValue::String(s.to_string()))
+                .ok_or_else(|| mismatch(path, "expected string", val)),
+            Scalar::Int { .. } => val
+                .as_i64()
+                .map(JsonValue::from)
+                .or_else(|| val.as_u64().map(JsonValue::from))
+                .ok_or_else(|| mismatch(path, "expected integer", val)),
+            Scalar::Float | Scalar::Double => val
+                .as_f64()
+                .and_then(serde_json::Number::from_f64)
+                .map(JsonValue::Number)
+                .ok_or_else(|| mismatch(path, "expected number", val)),
+            Scalar::Blob => Ok(JsonValue::String(base64::encode(msg_to_bytes(val, path)?))),
+            Scalar::Document => serde_json::from_slice(&msg_to_bytes(val, path)?)
+                .map_err(|e| RpcError::Deser(format!("{}: invalid document: {}", path, e))),
+            Scalar::Timestamp => {
+                let field = |name: &str| {
+                    val.as_map()
+                        .and_then(|m| m.iter().find(|(k, _)| k.as_str() == Some(name)))
+                        .map(|(_, v)| v)
+                        .or_else(|| {
+                            // compact (unnamed) serialization of {sec,nsec}
+                            val.as_array()
+                                .and_then(|a| a.get(if name == "sec" { 0 } else { 1 }))
+                        })
+                };
+                let sec = field("sec").and_then(|v| v.as_i64());
+                let nsec = field("nsec").and_then(|v| v.as_u64());
+                match (sec, nsec) {
+                    (Some(sec), Some(nsec)) if nsec < 1_000_000_000 => {
+                        let dt = DateTime::<Utc>::try_from(Timestamp {
+                            sec,
+                            nsec: nsec as u32,
+                        })
+                        .map_err(|e| RpcError::Deser(format!("{}: {}", path, e)))?;
+                        Ok(JsonValue::String(dt.to_rfc3339()))
+                    }
+                    _ => Err(mismatch(path, "expected timestamp", val)),
+                }
+            }
+            Scalar::Unsupported(name) => Err(RpcError::Deser(format!(
+                "{}: {} is not supported",
+                path, name
+            ))),
+        }
+    }
+}
+
+/// Returns the name used to serialize a structure member
+fn ser_name(member: &MemberShape) -> String {
+    let trait_id = ShapeID::new_unchecked(WASMCLOUD_MODEL_NAMESPACE, "serialization", None);
+    match member.traits().get(&trait_id) {
+        Some(Some(NodeValue::Object(map))) => match map.get("name") {
+            Some(NodeValue::String(name)) => name.clone(),
+            _ => member.id().to_string(),
+        },
+        _ => member.id().to_string(),
+    }
+}
+
+/// Generated code serializes members that are exactly `smithy.api#Blob` with serde_bytes
+fn is_blob(id: &ShapeID) -> bool {
+    id == &ShapeID::new_unchecked(PRELUDE_NAMESPACE, "Blob", None)
+}
+
+fn json_to_bytes(val: &JsonValue, path: &str) -> RpcResult<Vec<u8>> {
+    match val {
+        JsonValue::String(s) => {
+            base64::decode(s).map_err(|e| invalid(path, &format!("invalid base64: {}", e)))
+        }
+        JsonValue::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                v.as_u64()
+                    .filter(|n| *n <= u8::MAX as u64)
+                    .map(|n| n as u8)
+                    .ok_or_else(|| invalid(&format!("{}[{}]", path, i), "expected byte value"))
+            })
+            .collect(),
+        _ => Err(invalid(path, "expected base64 string or array of bytes")),
+    }
+}
+
+fn msg_to_bytes(val: &MsgValue, path: &str) -> RpcResult<Vec<u8>> {
+    match val {
+        MsgValue::Binary(bytes) => Ok(bytes.clone()),
+        MsgValue::Array(items) => items
+            .iter()
+            .map(|v| {
+                v.as_u64()
+                    .filter(|n| *n <= u8::MAX as u64)
+                    .map(|n| n as u8)
+                    .ok_or_else(|| mismatch(path, "expected byte value", v))
+            })
+            .collect(),
+        _ => Err(mismatch(path, "expected bytes", val)),
+    }
+}
+
+fn json_to_timestamp(val: &JsonValue, path: &str) -> RpcResult<Timestamp> {
+    match val {
+        JsonValue::String(s) => DateTime::parse_from_rfc3339(s)
+            .map(|dt| Timestamp::from(dt.with_timezone(&Utc)))
+            .map_err(|e| invalid(path, &format!("invalid RFC3339 timestamp: {}", e))),
+        JsonValue::Number(n) => n
+            .as_i64()
+            .map(|sec| Timestamp { sec, nsec: 0 })
+            .ok_or_else(|| invalid(path, "expected integer seconds")),
+        JsonValue::Object(obj) => {
+            let sec = obj.get("sec").and_then(|v| v.as_i64());
+            let nsec = obj.get("nsec").map(|v| v.as_u64()).unwrap_or(Some(0));
+            match (sec, nsec) {
+                (Some(sec), Some(nsec)) if nsec < 1_000_000_000 => Ok(Timestamp {
+                    sec,
+                    nsec: nsec as u32,
+                }),
+                _ => Err(invalid(path, "expected {\"sec\":int, \"nsec\":int}")),
+            }
+        }
+        _ => Err(invalid(path, "expected timestamp")),
+    }
+}
+
+fn invalid(path: &str, msg: &str) -> RpcError {
+    RpcError::InvalidParameter(format!("{}: {}", path, msg))
+}
+
+fn mismatch(path: &str, msg: &str, val: &MsgValue) -> RpcError {
+    RpcError::Deser(format!("{}: {}, got {}", path, msg, val))
+}
@@ -0,0 +1,511 @@
+//! Building, signing, and verifying Invocations
+//!
+//! These are used by RpcClient and HostBridge, and may also be used by
+//! test tools, proxies, and alternative hosts that need to create or check
+//! invocations without a nats connection.
+//!
+#![cfg(not(target_arch = "wasm32"))]
+
+use crate::{
+    core::{validate_public_key, Invocation, KeyType, WasmCloudEntity},
+    rpc_client::{invocation_hash, make_uuid},
+    Message, RpcError, RpcResult,
+};
+use std::sync::Arc;
+use wascap::prelude::{Claims, KeyPair};
+
+/// Builds a signed Invocation
+///
+/// ```ignore
+/// let inv = InvocationBuilder::new(key, host_id)
+///     .origin(origin)
+///     .target(target)
+///     .message(Message { method: "Echo.echo", arg: Cow::Borrowed(b"hello") })
+///     .build()?;
+/// ```
+pub struct InvocationBuilder {
+    key: Arc<KeyPair>,
+    host_id: String,
+    origin: Option<WasmCloudEntity>,
+    target: Option<WasmCloudEntity>,
+    operation: String,
+    msg: Vec<u8>,
+    id: Option<String>,
+}
+
+impl InvocationBuilder {
+    /// Constructs a builder with the key used to sign invocations
+    /// and the host id included in the invocation
+    pub fn new<T: ToString>(key: Arc<KeyPair>, host_id: T) -> Self {
+        InvocationBuilder {
+            key,
+            host_id: host_id.to_string(),
+            origin: None,
+            target: None,
+            operation: String::new(),
+            msg: Vec::new(),
+            id: None,
+        }
+    }
+
+    /// Sets the sender of the invocation
+    #[must_use]
+    pub fn origin(mut self, origin: WasmCloudEntity) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    /// Sets the recipient of the invocation
+    #[must_use]
+    pub fn target(mut self, target: WasmCloudEntity) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    /// Sets the operation (method) and message args
+    #[must_use]
+    pub fn message(mut self, message: Message<'_>) -> Self {
+        self.operation = message.method.to_string();
+        self.msg = message.arg.into_owned();
+        self
+    }
+
+    /// Sets the invocation id. If not set, a new uuid is generated.
+    #[must_use]
+    pub fn id<T: ToString>(mut self, id: T) -> Self {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    /// Signs and returns the invocation.
+    /// Returns an error if the origin, target, or operation was not set
+    pub fn build(self) -> RpcResult<Invocation> {
+        let origin = self
+            .origin
+            .ok_or_else(|| RpcError::InvalidParameter("invocation origin not set".into()))?;
+        let target = self
+            .target
+            .ok_or_else(|| RpcError::InvalidParameter("invocation target not set".into()))?;
+        if self.operation.is_empty() {
+            return Err(RpcError::InvalidParameter(
+                "invocation operation not set".into(),
+            ));
+        }
+        let id = self.id.unwrap_or_else(make_uuid);
+        let origin_url = origin.url();
+        let target_url = format!("{}/{}", target.url(), &self.operation);
+        let claims = Claims::<wascap::prelude::Invocation>::new(
+            self.key.public_key(),
+            id.clone(),
+            &target_url,
+            &origin_url,
+            &invocation_hash(&target_url, &origin_url, &self.operation, &self.msg),
+        );
+        let encoded_claims = claims
+            .encode(&self.key)
+            .map_err(|e| RpcError::Ser(format!("signing invocation claims: {}", e)))?;
+        Ok(Invocation {
+            origin,
+            target,
+            operation: self.operation,
+            msg: self.msg,
+            id,
+            encoded_claims,
+            host_id: self.host_id,
+        })
+    }
+}
+
+/// Reason that an invocation failed verification
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InvocationError {
+    /// The claims token could not be decoded or validated
+    #[error("invalid invocation claims token: {0}")]
+    InvalidToken(String),
+
+    #[error("Invocation claims token expired")]
+    Expired,
+
+    #[error("Invocation claims signature invalid")]
+    InvalidSignature,
+
+    #[error("Attempt to use invocation before claims token allows")]
+    NotYetValid,
+
+    #[error("No wascap metadata found on claims")]
+    MissingMetadata,
+
+    /// The hash of the invocation does not match the hash in the signed claims
+    #[error("Invocation hash does not match signed claims hash ({claims} / {computed})")]
+    HashMismatch { claims: String, computed: String },
+
+    #[error("Invalid host ID on invocation: '{0}'")]
+    InvalidHostId(String),
+
+    /// The claims were signed by a key that is not a cluster issuer
+    #[error("Issuer of this invocation is not in list of cluster issuers: {0}")]
+    UnknownIssuer(String),
+
+    #[error("Invocation claims and invocation target URL do not match: {claims} != {invocation}")]
+    TargetUrlMismatch { claims: String, invocation: String },
+
+    #[error("Invocation claims and invocation origin URL do not match: {claims} != {invocation}")]
+    OriginUrlMismatch { claims: String, invocation: String },
+
+    /// The invocation is addressed to a different entity
+    #[error("target key mismatch: {target} != {expected}")]
+    TargetKeyMismatch { target: String, expected: String },
+
+    /// The sending actor is not linked to the provider
+    #[error("unlinked actor: {0}")]
+    UnlinkedActor(String),
+
+    /// The sending provider is not known to the receiving provider's NameResolver
+    #[error("unknown provider: {0}")]
+    UnknownProvider(String),
+}
+
+impl From<InvocationError> for RpcError {
+    fn from(e: InvocationError) -> RpcError {
+        RpcError::Rpc(e.to_string())
+    }
+}
+
+/// Verifies the signature, claims, and addressing of invocations
+#[derive(Clone, Debug, Default)]
+pub struct InvocationVerifier {
+    cluster_issuers: Vec<String>,
+    target_key: Option<String>,
+}
+
+impl InvocationVerifier {
+    /// Constructs a verifier that accepts invocations signed by any of the cluster issuers
+    pub fn new(cluster_issuers: Vec<String>) -> Self {
+        InvocationVerifier {
+            cluster_issuers,
+            target_key: None,
+        }
+    }
+
+    /// Requires that invocations are addressed to the entity with this public key
+    #[must_use]
+    pub fn target_key<T: ToString>(mut self, public_key: T) -> Self {
+        self.target_key = Some(public_key.to_string());
+        self
+    }
+
+    /// Checks the invocation, returning the reason if it is invalid
+    pub fn verify(&self, inv: &Invocation) -> Result<(), InvocationError> {
+        let vr = wascap::jwt::validate_token::<wascap::prelude::Invocation>(&inv.encoded_claims)
+            .map_err(|e| InvocationError::InvalidToken(e.to_string()))?;
+        if vr.expired {
+            return Err(InvocationError::Expired);
+        }
+        if !vr.signature_valid {
+            return Err(InvocationError::InvalidSignature);
+        }
+        if vr.cannot_use_yet {
+            return Err(InvocationError::NotYetValid);
+        }
+        let origin_url = inv.origin.url();
+        let target_url = format!("{}/{}", inv.target.url(), &inv.operation);
+        let hash = invocation_hash(&target_url, &origin_url, &inv.operation, &inv.msg);
+        let claims = Claims::<wascap::prelude::Invocation>::decode(&inv.encoded_claims)
+            .map_err(|e| InvocationError::InvalidToken(e.to_string()))?;
+        let inv_claims = claims
+            .metadata
+            .as_ref()
+            .ok_or(InvocationError::MissingMetadata)?;
+        if inv_claims.invocation_hash != hash {
+            return Err(InvocationError::HashMismatch {
+                claims: inv_claims.invocation_hash.clone(),
+                computed: hash,
+            });
+        }
+        if validate_public_key(&inv.host_id, KeyType::Host).is_err() {
+            return Err(InvocationError::InvalidHostId(inv.host_id.clone()));
+        }
+        if !self.cluster_issuers.contains(&claims.issuer) {
+            return Err(InvocationError::UnknownIssuer(claims.issuer.clone()));
+        }
+        if inv_claims.target_url != target_url {
+            return Err(InvocationError::TargetUrlMismatch {
+                claims: inv_claims.target_url.clone(),
+                invocation: target_url,
+            });
+        }
+        if inv_claims.origin_url != origin_url {
+            return Err(InvocationError::OriginUrlMismatch {
+                claims: inv_claims.origin_url.clone(),
+                invocation: origin_url,
+            });
+        }
+        if let Some(target_key) = self.target_key.as_ref() {
+            if &inv.target.public_key != target_key {
+                return Err(InvocationError::TargetKeyMismatch {
+                    target: inv.target.public_key.clone(),
+                    expected: target_key.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// signs test invocations with a cluster key
+#[cfg(test)]
+struct Signer {
+    cluster: Arc<KeyPair>,
+    host_id: String,
+    origin: WasmCloudEntity,
+    target: WasmCloudEntity,
+}
+
+#[cfg(test)]
+impl Signer {
+    fn new() -> Self {
+        Signer {
+            cluster: Arc::new(KeyPair::new_cluster()),
+            host_id: KeyPair::new_server().public_key(),
+            origin: WasmCloudEntity {
+                public_key: KeyPair::new_module().public_key(),
+                ..Default::default()
+            },
+            target: WasmCloudEntity {
+                public_key: KeyPair::new_service().public_key(),
+                contract_id: "wasmcloud:keyvalue".to_string(),
+                link_name: "default".to_string(),
+            },
+        }
+    }
+
+    fn verifier(&self) -> InvocationVerifier {
+        InvocationVerifier::new(vec![self.cluster.public_key()])
+    }
+
+    fn invocation(&self) -> Invocation {
+        InvocationBuilder::new(self.cluster.clone(), &self.host_id)
+            .origin(self.origin.clone())
+            .target(self.target.clone())
+            .message(Message {
+                method: "KeyValue.Get",
+                arg: std::borrow::Cow::Borrowed(b"key"),
+            })
+            .build()
+            .unwrap()
+    }
+
+    /// replaces the invocation's claims with claims for the urls and dates
+    fn resign(
+        &self,
+        inv: &mut Invocation,
+        target_url: &str,
+        origin_url: &str,
+        not_before: Option<u64>,
+        expires: Option<u64>,
+    ) {
+        let hash = invocation_hash(
+            &format!("{}/{}", inv.target.url(), &inv.operation),
+            &inv.origin.url(),
+            &inv.operation,
+            &inv.msg,
+        );
+        inv.encoded_claims = Claims::<wascap::prelude::Invocation>::with_dates(
+            self.cluster.public_key(),
+            inv.id.clone(),
+            not_before,
+            expires,
+            target_url,
+            origin_url,
+            &hash,
+        )
+        .encode(&self.cluster)
+        .unwrap();
+    }
+}
+
+#[cfg(test)]
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[test]
+fn build_and_verify() {
+    let signer = Signer::new();
+    let inv = signer.invocation();
+    assert_eq!(inv.operation, "KeyValue.Get");
+    assert_eq!(inv.msg, b"key");
+    assert_eq!(inv.host_id, signer.host_id);
+    signer.verifier().verify(&inv).unwrap();
+    signer
+        .verifier()
+        .target_key(&signer.target.public_key)
+        .verify(&inv)
+        .unwrap();
+
+    let inv = InvocationBuilder::new(signer.cluster.clone(), &signer.host_id)
+        .origin(signer.origin.clone())
+        .target(signer.target.clone())
+        .message(Message {
+            method: "KeyValue.Get",
+            arg: std::borrow::Cow::Borrowed(b"key"),
+        })
+        .id("invocation-1")
+        .build()
+        .unwrap();
+    assert_eq!(inv.id, "invocation-1");
+    signer.verifier().verify(&inv).unwrap();
+
+    let err = InvocationBuilder::new(signer.cluster.clone(), &signer.host_id)
+        .origin(signer.origin.clone())
+        .target(signer.target.clone())
+        .build()
+        .unwrap_err();
+    assert!(matches!(err, RpcError::InvalidParameter(_)), "{}", err);
+}
+
+#[test]
+fn tampered_invocation() {
+    let signer = Signer::new();
+    let verifier = signer.verifier();
+
+    let mut inv = signer.invocation();
+    inv.msg = b"other-key".to_vec();
+    assert!(matches!(
+        verifier.verify(&inv),
+        Err(InvocationError::HashMismatch { .. })
+    ));
+
+    let mut inv = signer.invocation();
+    inv.operation = "KeyValue.Del".to_string();
+    assert!(matches!(
+        verifier.verify(&inv),
+        Err(InvocationError::HashMismatch { .. })
+    ));
+
+    // claims signed by a key other than their issuer
+    let mut inv = signer.invocation();
+    let claims = Claims::<wascap::prelude::Invocation>::decode(&inv.encoded_claims).unwrap();
+    inv.encoded_claims = claims.encode(&KeyPair::new_cluster()).unwrap();
+    assert_eq!(
+        verifier.verify(&inv),
+        Err(InvocationError::InvalidSignature)
+    );
+
+    let mut inv = signer.invocation();
+    inv.encoded_claims = "not.a.token".to_string();
+    assert!(matches!(
+        verifier.verify(&inv),
+        Err(InvocationError::InvalidToken(_))
+    ));
+
+    let mut inv = signer.invocation();
+    inv.host_id = "NOTAHOST".to_string();
+    assert_eq!(
+        verifier.verify(&inv),
+        Err(InvocationError::InvalidHostId("NOTAHOST".to_string()))
+    );
+}
+
+#[test]
+fn claims_for_other_urls() {
+    let signer = Signer::new();
+    let verifier = signer.verifier();
+    let inv = signer.invocation();
+    let target_url = format!("{}/{}", inv.target.url(), &inv.operation);
+    let origin_url = inv.origin.url();
+
+    let mut other = inv.clone();
+    signer.resign(&mut other, "wasmbus://other/Get", &origin_url, None, None);
+    assert_eq!(
+        verifier.verify(&other),
+        Err(InvocationError::TargetUrlMismatch {
+            claims: "wasmbus://other/Get".to_string(),
+            invocation: target_url.clone(),
+        })
+    );
+
+    let mut other = inv.clone();
+    signer.resign(&mut other, &target_url, "wasmbus://other", None, None);
+    assert_eq!(
+        verifier.verify(&other),
+        Err(InvocationError::OriginUrlMismatch {
+            claims: "wasmbus://other".to_string(),
+            invocation: origin_url,
+        })
+    );
+}
+
+#[test]
+fn wrong_target() {
+    let signer = Signer::new();
+    let expected = KeyPair::new_service().public_key();
+    assert_eq!(
+        signer
+            .verifier()
+            .target_key(&expected)
+            .verify(&signer.invocation()),
+        Err(InvocationError::TargetKeyMismatch {
+            target: signer.target.public_key.clone(),
+            expected,
+        })
+    );
+}
+
+#[test]
+fn claims_dates() {
+    let signer = Signer::new();
+    let verifier = signer.verifier();
+    let inv = signer.invocation();
+    let target_url = format!("{}/{}", inv.target.url(), &inv.operation);
+    let origin_url = inv.origin.url();
+
+    let mut expired = inv.clone();
+    signer.resign(
+        &mut expired,
+        &target_url,
+        &origin_url,
+        None,
+        Some(now() - 60),
+    );
+    assert_eq!(verifier.verify(&expired), Err(InvocationError::Expired));
+
+    let mut early = inv.clone();
+    signer.resign(
+        &mut early,
+        &target_url,
+        &origin_url,
+        Some(now() + 600),
+        None,
+    );
+    assert_eq!(verifier.verify(&early), Err(InvocationError::NotYetValid));
+
+    let mut current = inv;
+    signer.resign(
+        &mut current,
+        &target_url,
+        &origin_url,
+        Some(now() - 60),
+        Some(now() + 600),
+    );
+    verifier.verify(&current).unwrap();
+}
+
+#[test]
+fn untrusted_issuer() {
+    let signer = Signer::new();
+    let inv = signer.invocation();
+    let verifier = InvocationVerifier::new(vec![KeyPair::new_cluster().public_key()]);
+    assert_eq!(
+        verifier.verify(&inv),
+        Err(InvocationError::UnknownIssuer(signer.cluster.public_key()))
+    );
+    assert!(matches!(
+        InvocationVerifier::default().verify(&inv),
+        Err(InvocationError::UnknownIssuer(_))
+    ));
+}
@@ -9,12 +9,16 @@ pub use timestamp::Timestamp;
 mod actor_wasm;
 mod common;
 pub use common::{
-    context::Context, deserialize, serialize, Message, MessageDispatch, RpcError, SendOpts,
-    Transport,
+    context::{Context, HostCallOpts, HostContext},
+    deserialize, introspect, serialize, Message, MessageDispatch, MethodDescriptor, RpcError,
+    SendOpts, ServiceDescriptor, Transport, INTROSPECT_METHOD,
 };
 pub mod channel_log;
+pub mod middleware;
 pub mod provider;
+pub mod router;
 pub(crate) mod provider_main;
+pub(crate) mod provider_stdio;
 mod wasmbus_model;
 pub mod model {
     // re-export model lib as "model"
@@ -24,16 +28,42 @@ pub mod model {
 // re-export
 pub use minicbor;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub mod bus;
+#[cfg(not(target_arch = "wasm32"))]
+mod invocation;
+#[cfg(not(target_arch = "wasm32"))]
+pub use invocation::{InvocationBuilder, InvocationError, InvocationVerifier};
 #[cfg(not(target_arch = "wasm32"))]
 pub(crate) mod rpc_client;
 #[cfg(not(target_arch = "wasm32"))]
-pub use rpc_client::{rpc_topic, RpcClient};
+pub use rpc_client::{rpc_topic, RetryPolicy, RpcClient, RpcClientBuilder, RpcHooks};
+#[cfg(not(target_arch = "wasm32"))]
+mod circuit_breaker;
+#[cfg(not(target_arch = "wasm32"))]
+pub use circuit_breaker::{CircuitBreakerConfig, CircuitState};
+#[cfg(not(target_arch = "wasm32"))]
+mod multi_lattice;
+#[cfg(not(target_arch = "wasm32"))]
+pub use multi_lattice::{LatticeTarget, MultiLatticeClient, MultiLatticeClientBuilder};
+#[cfg(not(target_arch = "wasm32"))]
+mod response_cache;
+#[cfg(not(target_arch = "wasm32"))]
+pub use response_cache::CacheConfig;
+#[cfg(all(feature = "dynamic_json", not(target_arch = "wasm32")))]
+pub mod dynamic;
 
 pub type RpcResult<T> = std::result::Result<T, RpcError>;
 
 /// Version number of this api
 #[doc(hidden)]
-pub const WASMBUS_RPC_VERSION: u32 = 0;
+pub const WASMBUS_RPC_VERSION: u32 = 2;
+
+/// First api version in which actors accept a [HostContext] with each message
+pub const WASMBUS_CONTEXT_API_VERSION: u32 = 1;
+
+/// First api version in which actors provide [HostCallOpts] for messages they send
+pub const WASMBUS_HOST_CALL_OPTS_API_VERSION: u32 = 2;
 
 /// import module for webassembly linking
 #[doc(hidden)]
@@ -67,6 +97,36 @@ pub mod core {
                     self.host_id == TEST_HARNESS
                 }
 
+                /// Returns host data for running a provider locally, outside a host.
+                /// The host id marks it as a test (`is_test()` is true), and the provider key,
+                /// invocation seed, and cluster issuer are generated. Lattice prefix and
+                /// link name are "default", and nats is expected at the default address.
+                pub fn for_test() -> HostData {
+                    let cluster = wascap::prelude::KeyPair::new_cluster();
+                    HostData {
+                        host_id: TEST_HARNESS.to_string(),
+                        lattice_rpc_prefix: "default".to_string(),
+                        link_name: "default".to_string(),
+                        lattice_rpc_url: DEFAULT_NATS_ADDR.to_string(),
+                        provider_key: wascap::prelude::KeyPair::new_service().public_key(),
+                        invocation_seed: cluster.seed().unwrap_or_default(),
+                        instance_id: crate::rpc_client::make_uuid(),
+                        cluster_issuers: vec![cluster.public_key()],
+                        ..Default::default()
+                    }
+                }
+
+                /// Returns the provider's configuration, parsed from `config_json`,
+                /// or None if the host did not provide any
+                pub fn config<T: serde::de::DeserializeOwned>(&self) -> RpcResult<Option<T>> {
+                    match self.config_json.as_deref().map(str::trim) {
+                        None | Some("") => Ok(None),
+                        Some(json) => serde_json::from_str(json).map(Some).map_err(|e| {
+                            RpcError::ProviderInit(format!("invalid provider configuration: {}", e))
+                        }),
+                    }
+                }
+
                 /// Connect to nats using options provided by host
                 pub async fn nats_connect(&self) -> RpcResult<crate::anats::Connection> {
                     use std::str::FromStr as _;
@@ -121,14 +181,11 @@ pub mod core {
     }
 
     impl WasmCloudEntity {
-        /// constructor for actor entity
+        /// constructor for actor entity.
+        /// Returns an error if the public key is not a valid actor key
         pub fn new_actor<T: ToString>(public_key: T) -> RpcResult<WasmCloudEntity> {
             let public_key = public_key.to_string();
-            if public_key.is_empty() {
-                return Err(RpcError::InvalidParameter(
-                    "public_key may not be empty".to_string(),
-                ));
-            }
+            validate_public_key(&public_key, KeyType::Actor)?;
             Ok(WasmCloudEntity {
                 public_key,
                 contract_id: String::new(),
@@ -174,8 +231,8 @@ pub mod core {
 
         /// Returns URL of the entity
         pub fn url(&self) -> String {
-            if self.public_key.to_uppercase().starts_with('M') {
-                format!("{}://{}", crate::core::URL_SCHEME, self.public_key)
+            if self.is_actor() {
+                format!("{}://{}", URL_SCHEME, self.public_key)
             } else {
                 format!(
                     "{}://{}/{}/{}",
@@ -206,23 +263,170 @@ pub mod core {
         }
     }
 
+    impl std::str::FromStr for WasmCloudEntity {
+        type Err = RpcError;
+
+        /// Parses an entity url, the inverse of [WasmCloudEntity::url].
+        /// Actor urls have the form `wasmbus://ACTOR_KEY`, and provider urls
+        /// have the form `wasmbus://contract/path/link_name/PROVIDER_KEY`.
+        /// The provider key may be empty, as it is for entities created with `new_provider`.
+        fn from_str(url: &str) -> Result<WasmCloudEntity, Self::Err> {
+            let path = url
+                .strip_prefix(URL_SCHEME)
+                .and_then(|rest| rest.strip_prefix("://"))
+                .ok_or_else(|| {
+                    RpcError::InvalidParameter(format!(
+                        "entity url '{}' does not begin with {}://",
+                        url, URL_SCHEME
+                    ))
+                })?;
+            let segments = path.split('/').collect::<Vec<&str>>();
+            match segments.as_slice() {
+                [public_key] => WasmCloudEntity::new_actor(public_key),
+                [contract @ .., link_name, public_key]
+                    if !contract.is_empty() && !link_name.is_empty() =>
+                {
+                    if !public_key.is_empty() {
+                        validate_public_key(public_key, KeyType::Provider)?;
+                    }
+                    Ok(WasmCloudEntity {
+                        public_key: public_key.to_string(),
+                        contract_id: contract.join(":"),
+                        link_name: link_name.to_string(),
+                    })
+                }
+                _ => Err(RpcError::InvalidParameter(format!(
+                    "invalid entity url '{}'",
+                    url
+                ))),
+            }
+        }
+    }
+
     impl TryFrom<&str> for WasmCloudEntity {
         type Error = RpcError;
 
-        /// converts string into actor entity
+        /// converts an entity url, or an actor public key, into an entity
         fn try_from(target: &str) -> Result<WasmCloudEntity, Self::Error> {
-            WasmCloudEntity::new_actor(target.to_string())
+            if target.starts_with(URL_SCHEME) {
+                target.parse()
+            } else {
+                WasmCloudEntity::new_actor(target)
+            }
         }
     }
 
     impl TryFrom<String> for WasmCloudEntity {
         type Error = RpcError;
 
-        /// converts string into actor entity
+        /// converts an entity url, or an actor public key, into an entity
         fn try_from(target: String) -> Result<WasmCloudEntity, Self::Error> {
-            WasmCloudEntity::new_actor(target)
+            WasmCloudEntity::try_from(target.as_str())
+        }
+    }
+
+    /// Type of entity identified by an nkey public key
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum KeyType {
+        /// Actor (module) key, beginning with 'M'
+        Actor,
+        /// Capability provider (service) key, beginning with 'V'
+        Provider,
+        /// Host (server) key, beginning with 'N'
+        Host,
+    }
+
+    impl KeyType {
+        /// Returns the first character of public keys of this type
+        pub fn prefix(&self) -> char {
+            match self {
+                KeyType::Actor => 'M',
+                KeyType::Provider => 'V',
+                KeyType::Host => 'N',
+            }
         }
     }
+
+    /// length of an encoded nkey public key
+    const PUBLIC_KEY_LEN: usize = 56;
+
+    /// Checks that the key is a valid nkey public key of the expected type:
+    /// it must have the correct length and prefix, be base32-encoded,
+    /// and have a valid checksum.
+    pub fn validate_public_key(key: &str, key_type: KeyType) -> RpcResult<()> {
+        let invalid = |reason: &str| {
+            RpcError::InvalidParameter(format!(
+                "invalid {:?} public key '{}': {}",
+                key_type, key, reason
+            ))
+        };
+        if key.len() != PUBLIC_KEY_LEN {
+            return Err(invalid("incorrect length"));
+        }
+        if !key.starts_with(key_type.prefix()) {
+            return Err(invalid(&format!("expected prefix '{}'", key_type.prefix())));
+        }
+        // decodes the key and verifies its checksum
+        wascap::prelude::KeyPair::from_public_key(key).map_err(|e| invalid(&e.to_string()))?;
+        Ok(())
+    }
+
+    #[test]
+    fn entity_url_roundtrip() {
+        let actor =
+            WasmCloudEntity::new_actor("MBCFOPM6JW2APJLXJD3Z5O4CN7CPYJ2B4FTKLJUR5YR5MITIU7HD3WD5")
+                .unwrap();
+        assert_eq!(actor.url().parse::<WasmCloudEntity>().unwrap(), actor);
+
+        let provider = WasmCloudEntity {
+            public_key: "VAG3QITQQ2ODAOWB5TTQSDJ53XK3SHBEIFNK4AYJ5RKAX2UNSCAPHA5M".to_string(),
+            contract_id: "wasmcloud:httpserver".to_string(),
+            link_name: "default".to_string(),
+        };
+        assert_eq!(
+            provider.url(),
+            "wasmbus://wasmcloud/httpserver/default/VAG3QITQQ2ODAOWB5TTQSDJ53XK3SHBEIFNK4AYJ5RKAX2UNSCAPHA5M"
+        );
+        assert_eq!(provider.url().parse::<WasmCloudEntity>().unwrap(), provider);
+
+        // provider entity without public key
+        let provider = WasmCloudEntity::new_provider("wasmcloud:keyvalue", "default").unwrap();
+        assert_eq!(provider.url().parse::<WasmCloudEntity>().unwrap(), provider);
+
+        assert!(
+            "http://MBCFOPM6JW2APJLXJD3Z5O4CN7CPYJ2B4FTKLJUR5YR5MITIU7HD3WD5"
+                .parse::<WasmCloudEntity>()
+                .is_err()
+        );
+        assert!(
+            "wasmbus://default/VAG3QITQQ2ODAOWB5TTQSDJ53XK3SHBEIFNK4AYJ5RKAX2UNSCAPHA5M"
+                .parse::<WasmCloudEntity>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn public_key_validation() {
+        let actor = "MBCFOPM6JW2APJLXJD3Z5O4CN7CPYJ2B4FTKLJUR5YR5MITIU7HD3WD5";
+        let provider = "VAG3QITQQ2ODAOWB5TTQSDJ53XK3SHBEIFNK4AYJ5RKAX2UNSCAPHA5M";
+        let host = "NAAACAQDAQCQMBYIBEFAWDANBYHRAEISCMKBKFQXDAMRUGY4DUPB6CRG";
+        assert!(validate_public_key(actor, KeyType::Actor).is_ok());
+        assert!(validate_public_key(provider, KeyType::Provider).is_ok());
+        assert!(validate_public_key(host, KeyType::Host).is_ok());
+
+        // wrong prefix
+        assert!(validate_public_key(actor, KeyType::Provider).is_err());
+        assert!(validate_public_key(host, KeyType::Actor).is_err());
+        // wrong length
+        assert!(validate_public_key(&actor[..55], KeyType::Actor).is_err());
+        assert!(validate_public_key("", KeyType::Actor).is_err());
+        // bad checksum
+        let corrupt = actor.replace("MBCF", "MBCG");
+        assert!(validate_public_key(&corrupt, KeyType::Actor).is_err());
+        // invalid characters
+        let lower = actor.to_lowercase();
+        assert!(validate_public_key(&lower, KeyType::Actor).is_err());
+    }
 }
 
 // re-export nats-aflowt
@@ -231,6 +435,13 @@ pub use nats_aflowt as anats;
 
 pub mod actor {
 
+    #[doc(hidden)]
+    pub use crate::common::context::{
+        alloc_host_context, host_call_opts, set_host_call_opts, take_host_context,
+    };
+    #[doc(hidden)]
+    pub use prelude::init_guest;
+
     pub mod prelude {
         pub use crate::{
             core::{Actor, ActorReceiver},
@@ -251,7 +462,7 @@ pub mod actor {
         cfg_if::cfg_if! {
 
             if #[cfg(target_arch = "wasm32")] {
-                pub use crate::actor_wasm::{console_log, WasmHost};
+                pub use crate::actor_wasm::{console_log, init_guest, ConsoleLogger, WasmHost};
             } else {
                 // this code is non-functional, since actors only run in wasm32,
                 // but it reduces compiler errors if you are building a cargo multi-project workspace for non-wasm32
@@ -270,6 +481,9 @@ pub mod actor {
                 }
 
                 pub fn console_log(_s: &str) {}
+
+                /// Installs the actor's logger and panic hook (wasm32 only)
+                pub fn init_guest() {}
             }
         }
     }
@@ -0,0 +1,485 @@
+//! Middleware for message dispatch and transports
+//!
+//! A [Middleware] sees each message on its way to a [MessageDispatch] (an actor or
+//! provider receiving messages), or to a [Transport] (a client sending them),
+//! and may inspect or change the message, return early, or handle the result.
+//! Middleware is added to a [MiddlewareStack], which wraps a dispatch or transport
+//! in a [Layered] that implements the same trait:
+//!
+//! ```ignore
+//! let stack = MiddlewareStack::new()
+//!     .layer(LoggingLayer::default())
+//!     .layer(TimeoutLayer::new(Duration::from_secs(5)));
+//! bridge.connect_with_middleware(provider, &stack, shutdown_tx).await?;
+//! ```
+//!
+//! The first layer added is the outermost: it sees the message first and the result last.
+//!
+
+use crate::{Context, Message, MessageDispatch, RpcResult, SendOpts, Transport};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// Processes messages dispatched to a receiver, or sent with a transport.
+/// The default implementations pass the message to the next layer unchanged,
+/// so a middleware only needs to implement the direction(s) it applies to.
+#[async_trait]
+pub trait Middleware: Send + Sync {
+    /// Handles a message received by a [MessageDispatch].
+    /// Call `next.run(ctx, message)` to pass it to the next layer.
+    async fn dispatch<'s>(
+        &self,
+        ctx: &Context,
+        message: Message<'_>,
+        next: DispatchNext<'s>,
+    ) -> RpcResult<Message<'s>> {
+        next.run(ctx, message).await
+    }
+
+    /// Handles a message sent with a [Transport].
+    /// Call `next.run(ctx, message, opts)` to pass it to the next layer.
+    async fn send<'s>(
+        &self,
+        ctx: &Context,
+        message: Message<'_>,
+        opts: Option<SendOpts>,
+        next: SendNext<'s>,
+    ) -> RpcResult<Vec<u8>> {
+        next.run(ctx, message, opts).await
+    }
+}
+
+/// The remaining layers, and the receiver, of a message being dispatched
+pub struct DispatchNext<'s> {
+    layers: &'s [Arc<dyn Middleware>],
+    inner: &'s (dyn MessageDispatch + Sync),
+}
+
+impl<'s> DispatchNext<'s> {
+    /// Passes the message to the next layer, or to the receiver if this is the last layer
+    pub async fn run(self, ctx: &Context, message: Message<'_>) -> RpcResult<Message<'s>> {
+        match self.layers.split_first() {
+            Some((layer, layers)) => {
+                let next = DispatchNext {
+                    layers,
+                    inner: self.inner,
+                };
+                layer.dispatch(ctx, message, next).await
+            }
+            None => self.inner.dispatch(ctx, message).await,
+        }
+    }
+}
+
+/// The remaining layers, and the transport, of a message being sent
+pub struct SendNext<'s> {
+    layers: &'s [Arc<dyn Middleware>],
+    inner: &'s (dyn Transport + Sync),
+}
+
+impl<'s> SendNext<'s> {
+    /// Passes the message to the next layer, or to the transport if this is the last layer
+    pub async fn run(
+        self,
+        ctx: &Context,
+        message: Message<'_>,
+        opts: Option<SendOpts>,
+    ) -> RpcResult<Vec<u8>> {
+        match self.layers.split_first() {
+            Some((layer, layers)) => {
+                let next = SendNext {
+                    layers,
+                    inner: self.inner,
+                };
+                layer.send(ctx, message, opts, next).await
+            }
+            None => self.inner.send(ctx, message, opts).await,
+        }
+    }
+}
+
+/// An ordered list of middleware
+#[derive(Clone, Default)]
+pub struct MiddlewareStack {
+    layers: Vec<Arc<dyn Middleware>>,
+}
+
+impl MiddlewareStack {
+    /// Constructs an empty stack
+    pub fn new() -> MiddlewareStack {
+        MiddlewareStack::default()
+    }
+
+    /// Adds a middleware inside the layers already in the stack
+    #[must_use]
+    pub fn layer<M: Middleware + 'static>(mut self, middleware: M) -> MiddlewareStack {
+        self.layers.push(Arc::new(middleware));
+        self
+    }
+
+    /// Adds a shared middleware, such as one whose state is also held by the caller
+    #[must_use]
+    pub fn layer_arc(mut self, middleware: Arc<dyn Middleware>) -> MiddlewareStack {
+        self.layers.push(middleware);
+        self
+    }
+
+    /// Returns the number of layers
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Returns true if the stack has no layers
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Wraps a [MessageDispatch] or [Transport] in the stack's layers
+    pub fn wrap<S>(&self, inner: S) -> Layered<S> {
+        Layered {
+            inner,
+            layers: self.layers.clone().into(),
+        }
+    }
+}
+
+/// A [MessageDispatch] or [Transport] wrapped in middleware.
+/// For providers, `Layered<P>` implements [ProviderDispatch](crate::provider::ProviderDispatch)
+/// if `P` does, with [ProviderHandler](crate::provider::ProviderHandler) calls passed to `P`.
+#[derive(Clone)]
+pub struct Layered<S> {
+    inner: S,
+    layers: Arc<[Arc<dyn Middleware>]>,
+}
+
+impl<S> Layered<S> {
+    /// Returns the wrapped receiver or transport
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+#[async_trait]
+impl<S: MessageDispatch + Sync> MessageDispatch for Layered<S> {
+    async fn dispatch(&self, ctx: &Context, message: Message<'_>) -> RpcResult<Message<'_>> {
+        let next = DispatchNext {
+            layers: &self.layers,
+            inner: &self.inner,
+        };
+        next.run(ctx, message).await
+    }
+
+    fn services(&self) -> Vec<crate::ServiceDescriptor> {
+        self.inner.services()
+    }
+}
+
+#[async_trait]
+impl<T: Transport + Sync> Transport for Layered<T> {
+    async fn send(
+        &self,
+        ctx: &Context,
+        req: Message<'_>,
+        opts: Option<SendOpts>,
+    ) -> RpcResult<Vec<u8>> {
+        let next = SendNext {
+            layers: &self.layers,
+            inner: &self.inner,
+        };
+        next.run(ctx, req, opts).await
+    }
+
+    fn set_timeout(&self, interval: std::time::Duration) {
+        self.inner.set_timeout(interval)
+    }
+}
+
+/// Middleware that logs each message and its result
+pub struct LoggingLayer {
+    level: log::Level,
+}
+
+impl LoggingLayer {
+    /// Logs messages at the level. Errors are logged at the level or `Warn`, whichever is higher.
+    pub fn new(level: log::Level) -> LoggingLayer {
+        LoggingLayer { level }
+    }
+
+    fn log_result<T>(&self, direction: &str, method: &str, result: &RpcResult<T>) {
+        match result {
+            Ok(_) => log::log!(self.level, "{} {}: ok", direction, method),
+            Err(e) => log::log!(
+                self.level.min(log::Level::Warn),
+                "{} {}: {}",
+                direction,
+                method,
+                e
+            ),
+        }
+    }
+}
+
+impl Default for LoggingLayer {
+    /// Logs messages at `Debug` level
+    fn default() -> Self {
+        LoggingLayer::new(log::Level::Debug)
+    }
+}
+
+#[async_trait]
+impl Middleware for LoggingLayer {
+    async fn dispatch<'s>(
+        &self,
+        ctx: &Context,
+        message: Message<'_>,
+        next: DispatchNext<'s>,
+    ) -> RpcResult<Message<'s>> {
+        let method = message.method;
+        log::log!(
+            self.level,
+            "dispatch {} from {}",
+            method,
+            ctx.actor.as_deref().unwrap_or("(unknown)")
+        );
+        let result = next.run(ctx, message).await;
+        self.log_result("dispatch", method, &result);
+        result
+    }
+
+    async fn send<'s>(
+        &self,
+        ctx: &Context,
+        message: Message<'_>,
+        opts: Option<SendOpts>,
+        next: SendNext<'s>,
+    ) -> RpcResult<Vec<u8>> {
+        let method = message.method;
+        log::log!(self.level, "send {}", method);
+        let result = next.run(ctx, message, opts).await;
+        self.log_result("send", method, &result);
+        result
+    }
+}
+
+/// Signature of the function that checks dispatched messages, for [AuthorizeLayer]
+pub type AuthorizeFn = dyn Fn(&Context, &str) -> RpcResult<()> + Send + Sync;
+
+/// Middleware that checks each dispatched message before it reaches the receiver.
+/// The check is given the message's context and method, and the error it returns
+/// is returned to the sender in place of the response.
+pub struct AuthorizeLayer {
+    check: Box<AuthorizeFn>,
+}
+
+impl AuthorizeLayer {
+    pub fn new<F>(check: F) -> AuthorizeLayer
+    where
+        F: Fn(&Context, &str) -> RpcResult<()> + Send + Sync + 'static,
+    {
+        AuthorizeLayer {
+            check: Box::new(check),
+        }
+    }
+}
+
+#[async_trait]
+impl Middleware for AuthorizeLayer {
+    async fn dispatch<'s>(
+        &self,
+        ctx: &Context,
+        message: Message<'_>,
+        next: DispatchNext<'s>,
+    ) -> RpcResult<Message<'s>> {
+        (self.check)(ctx, message.method)?;
+        next.run(ctx, message).await
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use native::{CatchPanicLayer, MethodMetrics, MetricsLayer, TimeoutLayer};
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use super::{DispatchNext, Layered, Middleware, SendNext};
+    use crate::{
+        core::{HealthCheckRequest, HealthCheckResponse, LinkDefinition},
+        provider::{ProviderDispatch, ProviderHandler},
+        Context, Message, RpcError, RpcResult, SendOpts,
+    };
+    use async_trait::async_trait;
+    use futures::FutureExt;
+    use std::{
+        collections::HashMap,
+        convert::Infallible,
+        panic::AssertUnwindSafe,
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    /// Middleware that fails messages that don't complete within the timeout
+    /// with `RpcError::Timeout`. Applies to messages dispatched and sent.
+    pub struct TimeoutLayer {
+        timeout: Duration,
+    }
+
+    impl TimeoutLayer {
+        pub fn new(timeout: Duration) -> TimeoutLayer {
+            TimeoutLayer { timeout }
+        }
+
+        fn error(&self, method: &str) -> RpcError {
+            RpcError::Timeout(format!(
+                "{} did not complete within {}ms",
+                method,
+                self.timeout.as_millis()
+            ))
+        }
+    }
+
+    #[async_trait]
+    impl Middleware for TimeoutLayer {
+        async fn dispatch<'s>(
+            &self,
+            ctx: &Context,
+            message: Message<'_>,
+            next: DispatchNext<'s>,
+        ) -> RpcResult<Message<'s>> {
+            let method = message.method;
+            tokio::time::timeout(self.timeout, next.run(ctx, message))
+                .await
+                .map_err(|_| self.error(method))?
+        }
+
+        async fn send<'s>(
+            &self,
+            ctx: &Context,
+            message: Message<'_>,
+            opts: Option<SendOpts>,
+            next: SendNext<'s>,
+        ) -> RpcResult<Vec<u8>> {
+            let method = message.method;
+            tokio::time::timeout(self.timeout, next.run(ctx, message, opts))
+                .await
+                .map_err(|_| self.error(method))?
+        }
+    }
+
+    /// Middleware that returns an error for dispatched messages whose handler panics,
+    /// instead of letting the panic end the task that received the message.
+    #[derive(Default)]
+    pub struct CatchPanicLayer {}
+
+    #[async_trait]
+    impl Middleware for CatchPanicLayer {
+        async fn dispatch<'s>(
+            &self,
+            ctx: &Context,
+            message: Message<'_>,
+            next: DispatchNext<'s>,
+        ) -> RpcResult<Message<'s>> {
+            let method = message.method;
+            match AssertUnwindSafe(next.run(ctx, message))
+                .catch_unwind()
+                .await
+            {
+                Ok(result) => result,
+                Err(panic) => {
+                    let msg = panic
+                        .downcast_ref::<&str>()
+                        .copied()
+                        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+                        .unwrap_or("unknown panic");
+                    Err(RpcError::Other(format!("{} panicked: {}", method, msg)))
+                }
+            }
+        }
+    }
+
+    /// Counts of messages for one method, collected by [MetricsLayer]
+    #[derive(Clone, Debug, Default, PartialEq, Eq)]
+    pub struct MethodMetrics {
+        /// number of messages
+        pub calls: u64,
+        /// number of messages that returned an error
+        pub errors: u64,
+        /// total time from the start of the message to its result
+        pub elapsed: Duration,
+    }
+
+    /// Middleware that counts messages, errors, and elapsed time for each method.
+    /// Clones share their counts, so a clone can be added to the stack,
+    /// and the original used to read the counts.
+    #[derive(Clone, Default)]
+    pub struct MetricsLayer {
+        methods: Arc<Mutex<HashMap<String, MethodMetrics>>>,
+    }
+
+    impl MetricsLayer {
+        /// Returns the counts for each method
+        pub fn snapshot(&self) -> HashMap<String, MethodMetrics> {
+            self.methods.lock().unwrap().clone()
+        }
+
+        fn record(&self, method: &str, start: Instant, failed: bool) {
+            let mut methods = self.methods.lock().unwrap();
+            let metrics = methods.entry(method.to_string()).or_default();
+            metrics.calls += 1;
+            metrics.errors += failed as u64;
+            metrics.elapsed += start.elapsed();
+        }
+    }
+
+    #[async_trait]
+    impl Middleware for MetricsLayer {
+        async fn dispatch<'s>(
+            &self,
+            ctx: &Context,
+            message: Message<'_>,
+            next: DispatchNext<'s>,
+        ) -> RpcResult<Message<'s>> {
+            let (method, start) = (message.method, Instant::now());
+            let result = next.run(ctx, message).await;
+            self.record(method, start, result.is_err());
+            result
+        }
+
+        async fn send<'s>(
+            &self,
+            ctx: &Context,
+            message: Message<'_>,
+            opts: Option<SendOpts>,
+            next: SendNext<'s>,
+        ) -> RpcResult<Vec<u8>> {
+            let (method, start) = (message.method, Instant::now());
+            let result = next.run(ctx, message, opts).await;
+            self.record(method, start, result.is_err());
+            result
+        }
+    }
+
+    // link, health, and shutdown messages from the host are not rpc messages,
+    // so they go directly to the provider, bypassing the middleware
+    #[async_trait]
+    impl<P: ProviderHandler> ProviderHandler for Layered<P> {
+        async fn put_link(&self, ld: &LinkDefinition) -> Result<bool, RpcError> {
+            self.inner.put_link(ld).await
+        }
+
+        async fn delete_link(&self, actor_id: &str) {
+            self.inner.delete_link(actor_id).await
+        }
+
+        async fn health_request(
+            &self,
+            arg: &HealthCheckRequest,
+        ) -> Result<HealthCheckResponse, RpcError> {
+            self.inner.health_request(arg).await
+        }
+
+        async fn shutdown(&self) -> Result<(), Infallible> {
+            self.inner.shutdown().await
+        }
+    }
+
+    impl<P: ProviderDispatch> ProviderDispatch for Layered<P> {}
+}
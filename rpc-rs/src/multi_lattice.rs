@@ -0,0 +1,263 @@
+//! Rpc client for sending to several lattices
+//!
+//! [MultiLatticeClient] holds an [RpcClient] for each lattice prefix, each with its own
+//! connection and credentials, and routes messages by the lattice named in the [LatticeTarget].
+//! Invocations may be signed with a key shared by all lattices, or with a key per lattice.
+//!
+//! ```ignore
+//! let client = MultiLatticeClient::builder()
+//!     .signing_seed(&cluster_seed)
+//!     .lattice("prod", RpcClient::builder().nats_url("nats://prod:4222").credentials_file("prod.creds"))
+//!     .lattice("edge", RpcClient::builder().nats_url("nats://edge:4222").signing_seed(&edge_seed))
+//!     .build()
+//!     .await?;
+//! let resp = client.send(origin, LatticeTarget::new("edge", target), message).await?;
+//! ```
+//!
+#![cfg(not(target_arch = "wasm32"))]
+
+use crate::{
+    core::WasmCloudEntity, Message, RpcClient, RpcClientBuilder, RpcError, RpcResult, SendOpts,
+};
+use std::{collections::HashMap, time::Duration};
+
+/// Target of a message sent with [MultiLatticeClient]: an actor or provider
+/// entity, qualified by the lattice prefix it is in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LatticeTarget {
+    /// lattice prefix. If None, the client's default lattice is used
+    pub lattice: Option<String>,
+    /// actor or provider
+    pub entity: WasmCloudEntity,
+}
+
+impl LatticeTarget {
+    /// Constructs a target in the lattice
+    pub fn new<T: ToString>(lattice: T, entity: WasmCloudEntity) -> Self {
+        LatticeTarget {
+            lattice: Some(lattice.to_string()),
+            entity,
+        }
+    }
+}
+
+impl From<WasmCloudEntity> for LatticeTarget {
+    /// target in the client's default lattice
+    fn from(entity: WasmCloudEntity) -> Self {
+        LatticeTarget {
+            lattice: None,
+            entity,
+        }
+    }
+}
+
+impl<T: ToString> From<(T, WasmCloudEntity)> for LatticeTarget {
+    fn from((lattice, entity): (T, WasmCloudEntity)) -> Self {
+        LatticeTarget::new(lattice, entity)
+    }
+}
+
+/// Rpc client that sends messages to actors and providers in several lattices.
+/// Clones share the underlying connections.
+#[derive(Clone)]
+pub struct MultiLatticeClient {
+    clients: HashMap<String, RpcClient>,
+    default_lattice: Option<String>,
+}
+
+impl MultiLatticeClient {
+    /// Returns a builder for configuring lattices
+    pub fn builder() -> MultiLatticeClientBuilder {
+        MultiLatticeClientBuilder::default()
+    }
+
+    /// Returns the client for the lattice
+    pub fn client(&self, lattice: &str) -> Option<&RpcClient> {
+        self.clients.get(lattice)
+    }
+
+    /// Returns the lattice prefixes managed by this client
+    pub fn lattices(&self) -> impl Iterator<Item = &str> {
+        self.clients.keys().map(|k| k.as_str())
+    }
+
+    /// Returns the lattice used for targets without a lattice qualifier
+    pub fn default_lattice(&self) -> Option<&str> {
+        self.default_lattice.as_deref()
+    }
+
+    /// Returns the client for the target's lattice
+    fn route(&self, target: &LatticeTarget) -> RpcResult<&RpcClient> {
+        let lattice = match (target.lattice.as_ref(), self.default_lattice.as_ref()) {
+            (Some(lattice), _) | (None, Some(lattice)) => lattice,
+            (None, None) => {
+                return Err(RpcError::InvalidParameter(format!(
+                    "no lattice for target {} and no default lattice",
+                    target.entity.url()
+                )))
+            }
+        };
+        self.clients
+            .get(lattice)
+            .ok_or_else(|| RpcError::InvalidParameter(format!("unknown lattice '{}'", lattice)))
+    }
+
+    /// Sends a message to the target's lattice, and waits for the response,
+    /// using that lattice client's default timeout. See [RpcClient::send]
+    pub async fn send<Target>(
+        &self,
+        origin: WasmCloudEntity,
+        target: Target,
+        message: Message<'_>,
+    ) -> RpcResult<Vec<u8>>
+    where
+        Target: Into<LatticeTarget>,
+    {
+        let target = target.into();
+        self.route(&target)?
+            .send(origin, target.entity, message)
+            .await
+    }
+
+    /// Sends a message to the target's lattice, with a timeout. See [RpcClient::send_timeout]
+    pub async fn send_timeout<Target>(
+        &self,
+        origin: WasmCloudEntity,
+        target: Target,
+        message: Message<'_>,
+        timeout: Duration,
+    ) -> RpcResult<Vec<u8>>
+    where
+        Target: Into<LatticeTarget>,
+    {
+        let target = target.into();
+        self.route(&target)?
+            .send_timeout(origin, target.entity, message, timeout)
+            .await
+    }
+
+    /// Sends a message to the target's lattice, with send options. See [RpcClient::send_with_opts]
+    pub async fn send_with_opts<Target>(
+        &self,
+        origin: WasmCloudEntity,
+        target: Target,
+        message: Message<'_>,
+        opts: &SendOpts,
+        timeout: Option<Duration>,
+    ) -> RpcResult<Vec<u8>>
+    where
+        Target: Into<LatticeTarget>,
+    {
+        let target = target.into();
+        self.route(&target)?
+            .send_with_opts(origin, target.entity, message, opts, timeout)
+            .await
+    }
+}
+
+/// Builder for [MultiLatticeClient]
+#[derive(Default)]
+pub struct MultiLatticeClientBuilder {
+    lattices: Vec<(String, RpcClientBuilder)>,
+    clients: Vec<(String, RpcClient)>,
+    seed: Option<String>,
+    key_error: Option<String>,
+    default_lattice: Option<String>,
+}
+
+impl MultiLatticeClientBuilder {
+    /// Adds a lattice. The builder configures the connection and credentials
+    /// for the lattice; its lattice prefix is set to `lattice`.
+    /// If the builder has no signing key, the shared key is used.
+    #[must_use]
+    pub fn lattice<T: ToString>(mut self, lattice: T, builder: RpcClientBuilder) -> Self {
+        self.lattices.push((lattice.to_string(), builder));
+        self
+    }
+
+    /// Adds a lattice with an existing client, which is used as-is.
+    /// The client's lattice prefix must be `lattice`.
+    #[must_use]
+    pub fn client<T: ToString>(mut self, lattice: T, client: RpcClient) -> Self {
+        self.clients.push((lattice.to_string(), client));
+        self
+    }
+
+    /// Sets the key for signing invocations in lattices that don't have their own key
+    #[must_use]
+    pub fn signing_key(mut self, key: wascap::prelude::KeyPair) -> Self {
+        match key.seed() {
+            Ok(seed) => self.seed = Some(seed),
+            Err(e) => self.key_error = Some(format!("invalid signing key: {}", e)),
+        }
+        self
+    }
+
+    /// Sets the seed of the key for signing invocations in lattices that don't have their own key.
+    /// If no shared key is set, each lattice without a key generates its own.
+    #[must_use]
+    pub fn signing_seed<T: ToString>(mut self, seed: T) -> Self {
+        self.seed = Some(seed.to_string());
+        self
+    }
+
+    /// Sets the lattice used for targets without a lattice qualifier.
+    /// If there is only one lattice, it is the default.
+    #[must_use]
+    pub fn default_lattice<T: ToString>(mut self, lattice: T) -> Self {
+        self.default_lattice = Some(lattice.to_string());
+        self
+    }
+
+    /// Connects to each lattice and returns the client
+    pub async fn build(self) -> RpcResult<MultiLatticeClient> {
+        if let Some(e) = self.key_error {
+            return Err(RpcError::InvalidParameter(e));
+        }
+        let mut clients = HashMap::new();
+        for (lattice, client) in self.clients.into_iter() {
+            if client.lattice_prefix() != lattice {
+                return Err(RpcError::InvalidParameter(format!(
+                    "client for lattice '{}' has lattice prefix '{}'",
+                    lattice,
+                    client.lattice_prefix()
+                )));
+            }
+            if clients.insert(lattice.clone(), client).is_some() {
+                return Err(RpcError::InvalidParameter(format!(
+                    "duplicate lattice '{}'",
+                    lattice
+                )));
+            }
+        }
+        for (lattice, mut builder) in self.lattices.into_iter() {
+            if clients.contains_key(&lattice) {
+                return Err(RpcError::InvalidParameter(format!(
+                    "duplicate lattice '{}'",
+                    lattice
+                )));
+            }
+            builder = builder.lattice_prefix(&lattice);
+            if let (false, Some(seed)) = (builder.has_signing_key(), self.seed.as_ref()) {
+                builder = builder.signing_seed(seed);
+            }
+            let client = builder.build().await?;
+            clients.insert(lattice, client);
+        }
+        let default_lattice = match self.default_lattice {
+            Some(lattice) if !clients.contains_key(&lattice) => {
+                return Err(RpcError::InvalidParameter(format!(
+                    "default lattice '{}' has not been added",
+                    lattice
+                )))
+            }
+            Some(lattice) => Some(lattice),
+            None if clients.len() == 1 => clients.keys().next().cloned(),
+            None => None,
+        };
+        Ok(MultiLatticeClient {
+            clients,
+            default_lattice,
+        })
+    }
+}
@@ -5,14 +5,17 @@
 
 pub use crate::rpc_client::make_uuid;
 use crate::{
+    bus::{BusMessage, BusSubscription, MessageBus, NatsBus},
     core::{
-        HealthCheckRequest, HealthCheckResponse, HostData, Invocation, InvocationResponse,
-        LinkDefinition,
+        validate_public_key, HealthCheckRequest, HealthCheckResponse, HostData, Invocation,
+        InvocationResponse, KeyType, LinkDefinition, WasmCloudEntity,
     },
-    Message, MessageDispatch, RpcClient, RpcError,
+    middleware::MiddlewareStack,
+    Context, InvocationError, InvocationVerifier, Message, MessageDispatch, RpcClient, RpcError,
+    RpcResult, Transport,
 };
 use async_trait::async_trait;
-use futures::future::JoinAll;
+use futures::{future::JoinAll, StreamExt};
 use log::{debug, error, info, trace, warn};
 use serde::de::DeserializeOwned;
 use std::{
@@ -20,14 +23,23 @@ use std::{
     collections::HashMap,
     convert::Infallible,
     ops::Deref,
-    sync::{Arc, Mutex as StdMutex},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex as StdMutex, RwLock as StdRwLock,
+    },
     time::Duration,
 };
-use tokio::sync::{oneshot, RwLock};
+use tokio::sync::{oneshot, Notify, RwLock};
 
 // name of nats queue group for rpc subscription
 const RPC_SUBSCRIPTION_QUEUE_GROUP: &str = "rpc";
 
+/// default number of actors that are sent to concurrently by HostBridge::broadcast
+const DEFAULT_BROADCAST_CONCURRENCY: usize = 16;
+
+/// time to wait for in-flight rpc messages to complete during shutdown
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
 pub type HostShutdownEvent = String;
 
 pub trait ProviderDispatch: MessageDispatch + ProviderHandler {}
@@ -38,14 +50,18 @@ pub mod prelude {
         core::LinkDefinition,
         provider::{HostBridge, ProviderDispatch, ProviderHandler},
         provider_main::{
-            get_host_bridge, load_host_data, provider_main, provider_run, provider_start,
+            get_host_bridge, load_host_data, load_host_data_file, load_host_data_from,
+            load_host_data_stdin, provider_main, provider_main_with, provider_run,
+            provider_run_with_bus, provider_run_with_triggers, provider_start,
+            provider_start_with_triggers, ShutdownTriggers,
         },
+        provider_stdio::{provider_run_stdio, provider_start_stdio},
         Context, Message, MessageDispatch, RpcError, RpcResult, SendOpts,
     };
 
     //pub use crate::Timestamp;
     pub use async_trait::async_trait;
-    pub use wasmbus_macros::Provider;
+    pub use wasmbus_macros::{provider, Provider};
 
     #[cfg(feature = "BigInteger")]
     pub use num_bigint::BigInt as BigInteger;
@@ -94,7 +110,7 @@ pub trait ProviderHandler: Sync {
 /// format of log message sent to main thread for output to logger
 pub type LogEntry = (log::Level, String);
 
-/// HostBridge manages the NATS connection to the host,
+/// HostBridge manages the connection to the host (over nats, or another [MessageBus]),
 /// and processes subscriptions for links, health-checks, and rpc messages.
 /// Callbacks from HostBridge are implemented by the provider in the [[ProviderHandler]] implementation.
 ///
@@ -109,17 +125,32 @@ impl HostBridge {
         nats: crate::anats::Connection,
         host_data: &HostData,
     ) -> Result<HostBridge, RpcError> {
-        let key = if host_data.is_test() {
+        Self::new_with_bus(Arc::new(NatsBus::new(nats)), host_data)
+    }
+
+    /// Constructs a HostBridge that receives host messages and sends rpc messages
+    /// on the message bus
+    pub fn new_with_bus(
+        bus: Arc<dyn MessageBus>,
+        host_data: &HostData,
+    ) -> Result<HostBridge, RpcError> {
+        let key = if host_data.is_test() && host_data.invocation_seed.is_empty() {
             wascap::prelude::KeyPair::new_user()
         } else {
             wascap::prelude::KeyPair::from_seed(&host_data.invocation_seed)
                 .map_err(|e| RpcError::NotInitialized(format!("key failure: {}", e)))?
         };
-        let rpc_client = crate::rpc_client::RpcClient::new(
-            nats,
+        // receivers reject invocations whose host id isn't a host public key,
+        // so test or default host data uses the public key of a generated server key
+        let host_id = match validate_public_key(&host_data.host_id, KeyType::Host) {
+            Ok(()) => host_data.host_id.clone(),
+            Err(_) => wascap::prelude::KeyPair::new_server().public_key(),
+        };
+        let rpc_client = crate::rpc_client::RpcClient::new_with_bus(
+            bus,
             &host_data.lattice_rpc_prefix,
             key,
-            host_data.host_id.clone(),
+            host_id,
             None,
         );
 
@@ -129,6 +160,12 @@ impl HostBridge {
                 links: RwLock::new(HashMap::new()),
                 rpc_client,
                 lattice_prefix: host_data.lattice_rpc_prefix.clone(),
+                max_inbound_payload: AtomicUsize::new(0),
+                broadcast_concurrency: AtomicUsize::new(DEFAULT_BROADCAST_CONCURRENCY),
+                resolver: StdRwLock::new(Arc::new(StaticResolver::default())),
+                shutting_down: AtomicBool::new(false),
+                in_flight: AtomicUsize::new(0),
+                idle: Notify::new(),
             }),
             host_data: host_data.clone(),
         })
@@ -148,6 +185,23 @@ impl HostBridge {
     pub fn link_name(&self) -> &str {
         self.host_data.link_name.as_str()
     }
+
+    /// Sets the maximum size of an incoming rpc message.
+    /// Larger messages are rejected with RpcError::PayloadTooLarge
+    /// before they are deserialized or dispatched to the provider.
+    /// If the parameter is None, incoming messages are not limited.
+    pub fn set_max_inbound_payload(&self, limit: Option<usize>) {
+        self.max_inbound_payload
+            .store(limit.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    /// Returns the maximum size of an incoming rpc message, or None if there is no limit
+    pub fn max_inbound_payload(&self) -> Option<usize> {
+        match self.max_inbound_payload.load(Ordering::Relaxed) {
+            0 => None,
+            limit => Some(limit),
+        }
+    }
 }
 
 impl Deref for HostBridge {
@@ -160,12 +214,51 @@ impl Deref for HostBridge {
 
 #[doc(hidden)]
 pub struct HostBridgeInner {
-    subs: RwLock<Vec<crate::anats::Subscription>>,
+    subs: RwLock<Vec<Arc<dyn BusSubscription>>>,
     /// Table of actors that are bound to this provider
     /// Key is actor_id / actor public key
     links: RwLock<HashMap<String, LinkDefinition>>,
     rpc_client: crate::rpc_client::RpcClient,
     lattice_prefix: String,
+    /// limit on incoming rpc message size. 0 means no limit
+    max_inbound_payload: AtomicUsize,
+    /// maximum number of concurrent sends in broadcast
+    broadcast_concurrency: AtomicUsize,
+    /// resolves actor aliases and provider names for ProviderTransport
+    resolver: StdRwLock<Arc<dyn NameResolver>>,
+    /// set when shutdown begins, so the provider's shutdown handler runs once
+    shutting_down: AtomicBool,
+    /// number of rpc messages being dispatched
+    in_flight: AtomicUsize,
+    /// notified when in_flight drops to zero
+    idle: Notify,
+}
+
+/// Counts an rpc message as in-flight until dropped
+struct InFlight(Arc<HostBridgeInner>);
+
+impl InFlight {
+    fn new(inner: Arc<HostBridgeInner>) -> Self {
+        inner.in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlight(inner)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if self.0.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+/// The result of sending a broadcast message to one linked actor
+#[derive(Debug)]
+pub struct BroadcastResult {
+    /// public key of the actor
+    pub actor_id: String,
+    /// the actor's response, or the error that occurred sending to it
+    pub result: RpcResult<Vec<u8>>,
 }
 
 impl HostBridge {
@@ -174,6 +267,56 @@ impl HostBridge {
         &self.rpc_client
     }
 
+    /// Stops the provider: runs the provider's shutdown handler, unsubscribes from
+    /// rpc and link topics, and waits up to `drain_timeout` for in-flight rpc messages
+    /// to complete. This is called when the host sends a shutdown message,
+    /// and by `provider_run` on a termination signal or host exit.
+    /// Returns false, without doing anything, if shutdown has already begun.
+    pub async fn shutdown_provider<P>(&self, provider: &P, drain_timeout: Duration) -> bool
+    where
+        P: ProviderHandler + ?Sized,
+    {
+        if self.shutting_down.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+        // Tell provider to shutdown - before we shut down nats subscriptions,
+        // in case it needs to do any message passing during shutdown
+        if let Err(e) = provider.shutdown().await {
+            error!("during provider shutdown processing, got error: {}", e);
+        }
+        // drain all subscriptions except shutdown
+        self.unsubscribe_all().await;
+        if tokio::time::timeout(drain_timeout, self.wait_idle())
+            .await
+            .is_err()
+        {
+            warn!(
+                "shutdown: {} rpc messages did not complete within {:?}",
+                self.in_flight.load(Ordering::SeqCst),
+                drain_timeout
+            );
+        }
+        true
+    }
+
+    /// Returns true if shutdown has begun
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+
+    /// Waits until no rpc messages are being dispatched
+    async fn wait_idle(&self) {
+        loop {
+            // the future is registered for notify_waiters when created,
+            // so it must be created before checking the count
+            let idle = self.idle.notified();
+            if self.in_flight.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+
     /// Clear out all subscriptions
     async fn unsubscribe_all(&self) {
         let mut copy = Vec::new();
@@ -191,7 +334,7 @@ impl HostBridge {
     }
 
     // add subscription so we can unsubscribe_all later
-    async fn add_subscription(&self, sub: crate::anats::Subscription) {
+    async fn add_subscription(&self, sub: Arc<dyn BusSubscription>) {
         let mut sub_lock = self.subs.write().await;
         sub_lock.push(sub);
     }
@@ -199,11 +342,7 @@ impl HostBridge {
     // parse incoming subscription message
     // if it fails deserialization, we can't really respond;
     // so log the error
-    fn parse_msg<T: DeserializeOwned>(
-        &self,
-        msg: &crate::anats::Message,
-        topic: &str,
-    ) -> Option<T> {
+    fn parse_msg<T: DeserializeOwned>(&self, msg: &BusMessage, topic: &str) -> Option<T> {
         match if self.host_data.is_test() {
             serde_json::from_slice(&msg.data).map_err(|e| RpcError::Deser(e.to_string()))
         } else {
@@ -241,6 +380,116 @@ impl HostBridge {
         read.get(actor_id).cloned()
     }
 
+    /// Replaces the resolver used to look up actor call aliases and provider keys.
+    /// The default resolver is an empty [StaticResolver]
+    pub fn set_resolver(&self, resolver: Arc<dyn NameResolver>) {
+        match self.resolver.write() {
+            Ok(mut write) => *write = resolver,
+            Err(_) => warn!("resolver lock error - unchanged"),
+        }
+    }
+
+    /// Returns the current name resolver
+    pub fn resolver(&self) -> Arc<dyn NameResolver> {
+        match self.resolver.read() {
+            Ok(read) => read.clone(),
+            Err(e) => e.into_inner().clone(),
+        }
+    }
+
+    /// Determines the entity that a ProviderTransport sends to
+    pub async fn resolve_target(
+        &self,
+        ld: &LinkDefinition,
+        target: &TransportTarget,
+    ) -> RpcResult<WasmCloudEntity> {
+        match target {
+            TransportTarget::LinkedActor => Ok(ld.actor_entity()),
+            TransportTarget::Actor(public_key) => WasmCloudEntity::new_actor(public_key),
+            TransportTarget::ActorAlias(alias) => {
+                let public_key = self.resolver().resolve_actor_alias(alias).await?;
+                WasmCloudEntity::new_actor(public_key)
+            }
+            TransportTarget::Provider {
+                contract_id,
+                link_name,
+            } => {
+                let public_key = self
+                    .resolver()
+                    .resolve_provider(contract_id, link_name)
+                    .await?;
+                let mut entity = WasmCloudEntity::new_provider(contract_id, link_name)?;
+                entity.public_key = public_key;
+                Ok(entity)
+            }
+        }
+    }
+
+    /// Sets the maximum number of actors that `broadcast` sends to concurrently.
+    /// The value must be at least 1.
+    pub fn set_broadcast_concurrency(&self, limit: usize) {
+        self.broadcast_concurrency
+            .store(limit.max(1), Ordering::Relaxed);
+    }
+
+    /// Sends the message to every linked actor.
+    /// See [broadcast_filtered](HostBridge::broadcast_filtered)
+    pub async fn broadcast(&self, ctx: &Context, message: Message<'_>) -> Vec<BroadcastResult> {
+        self.broadcast_filtered(ctx, message, |_| true).await
+    }
+
+    /// Sends the message to each linked actor for which `filter` returns true.
+    /// Messages are sent concurrently, up to the limit set by `set_broadcast_concurrency`,
+    /// and each send uses the default rpc timeout, so a slow or failed actor
+    /// does not prevent delivery to the others.
+    /// Returns one result per actor, in the order responses were received.
+    pub async fn broadcast_filtered<F>(
+        &self,
+        ctx: &Context,
+        message: Message<'_>,
+        filter: F,
+    ) -> Vec<BroadcastResult>
+    where
+        F: Fn(&LinkDefinition) -> bool,
+    {
+        let links = {
+            let read = self.links.read().await;
+            read.values()
+                .filter(|&ld| filter(ld))
+                .cloned()
+                .collect::<Vec<LinkDefinition>>()
+        };
+        let concurrency = self.broadcast_concurrency.load(Ordering::Relaxed).max(1);
+        let method = message.method;
+        let arg = message.arg.as_ref();
+        futures::stream::iter(links)
+            .map(|ld| async move {
+                let result = {
+                    let transport = ProviderTransport::new(&ld, Some(self));
+                    transport
+                        .send(
+                            ctx,
+                            Message {
+                                method,
+                                arg: Cow::Borrowed(arg),
+                            },
+                            None,
+                        )
+                        .await
+                };
+                if let Err(e) = &result {
+                    warn!("broadcast {} to {} failed: {}", method, &ld.actor_id, e);
+                }
+                BroadcastResult {
+                    actor_id: ld.actor_id,
+                    result,
+                }
+            })
+            .buffer_unordered(concurrency)
+            .collect()
+            .await
+    }
+
     /// Implement subscriber listener threads and provider callbacks
     pub async fn connect<P>(
         &'static self,
@@ -260,6 +509,21 @@ impl HostBridge {
         Ok(join)
     }
 
+    /// Implement subscriber listener threads and provider callbacks, with rpc messages
+    /// to the provider passed through the middleware. Link, health, and shutdown
+    /// messages from the host go directly to the provider.
+    pub async fn connect_with_middleware<P>(
+        &'static self,
+        provider: P,
+        middleware: &MiddlewareStack,
+        shutdown_tx: oneshot::Sender<HostShutdownEvent>,
+    ) -> Result<JoinAll<tokio::task::JoinHandle<Result<(), RpcError>>>, RpcError>
+    where
+        P: ProviderDispatch + Send + Sync + Clone + 'static,
+    {
+        self.connect(middleware.wrap(provider), shutdown_tx).await
+    }
+
     async fn subscribe_rpc<P>(&self, provider: P) -> Result<(), RpcError>
     where
         P: ProviderDispatch + Send + Sync + Clone + 'static,
@@ -272,21 +536,43 @@ impl HostBridge {
         debug!("subscribing for rpc : {}", &rpc_topic);
         let sub = self
             .rpc_client()
-            .get_async()
-            .unwrap() // we are only async
+            .bus()
             .queue_subscribe(&rpc_topic, RPC_SUBSCRIPTION_QUEUE_GROUP)
-            .await
-            .map_err(|e| RpcError::Nats(e.to_string()))?;
+            .await?;
         self.add_subscription(sub.clone()).await;
         let this = self.clone();
         tokio::spawn(async move {
             while let Some(msg) = sub.next().await {
+                if let Some(allowed) = this.max_inbound_payload() {
+                    if msg.data.len() > allowed {
+                        let e = RpcError::PayloadTooLarge {
+                            actual: msg.data.len(),
+                            allowed,
+                        };
+                        error!("Rejecting rpc message: {}", &e);
+                        if let Some(reply_to) = msg.reply {
+                            let _ = publish_invocation_response(
+                                this.rpc_client(),
+                                reply_to,
+                                InvocationResponse {
+                                    invocation_id: "invalid".to_string(),
+                                    error: Some(e.to_string()),
+                                    msg: Vec::new(),
+                                },
+                            )
+                            .await;
+                        }
+                        continue;
+                    }
+                }
                 match crate::deserialize::<Invocation>(&msg.data) {
                     Ok(inv) => match this.validate_invocation(&inv).await {
                         Ok(()) => {
                             let provider = provider.clone();
                             let rpc_client = this.rpc_client().clone();
+                            let in_flight = InFlight::new(this.inner.clone());
                             tokio::task::spawn(async move {
+                                let _in_flight = in_flight;
                                 trace!(
                                     "RPC Invocation: op:{} from:{}",
                                     &inv.operation,
@@ -345,7 +631,7 @@ impl HostBridge {
                                     reply_to,
                                     InvocationResponse {
                                         invocation_id: inv.id,
-                                        error: Some(s),
+                                        error: Some(s.to_string()),
                                         msg: Vec::new(),
                                     },
                                 )
@@ -377,66 +663,39 @@ impl HostBridge {
         Ok(())
     }
 
-    pub async fn validate_invocation(&self, inv: &Invocation) -> Result<(), String> {
-        let vr = wascap::jwt::validate_token::<wascap::prelude::Invocation>(&inv.encoded_claims)
-            .map_err(|e| format!("{}", e))?;
-        if vr.expired {
-            return Err("Invocation claims token expired".into());
-        }
-        if !vr.signature_valid {
-            return Err("Invocation claims signature invalid".into());
-        }
-        if vr.cannot_use_yet {
-            return Err("Attempt to use invocation before claims token allows".into());
-        }
-        let target_url = format!("{}/{}", inv.target.url(), &inv.operation);
-        let hash = crate::rpc_client::invocation_hash(
-            &target_url,
-            &inv.origin.url(),
-            &inv.operation,
-            &inv.msg,
-        );
-        let claims =
-            wascap::prelude::Claims::<wascap::prelude::Invocation>::decode(&inv.encoded_claims)
-                .map_err(|e| format!("{}", e))?;
-        let inv_claims = claims
-            .metadata
-            .ok_or_else(|| "No wascap metadata found on claims".to_string())?;
-        if inv_claims.invocation_hash != hash {
-            return Err(format!(
-                "Invocation hash does not match signed claims hash ({} / {})",
-                inv_claims.invocation_hash, hash
-            ));
-        }
-        if !inv.host_id.starts_with('N') && inv.host_id.len() != 56 {
-            return Err(format!("Invalid host ID on invocation: '{}'", inv.host_id));
-        }
-        if !self.host_data.cluster_issuers.contains(&claims.issuer) {
-            return Err("Issuer of this invocation is not in list of cluster issuers".into());
-        }
-        if inv_claims.target_url != target_url {
-            return Err(format!(
-                "Invocation claims and invocation target URL do not match: {} != {}",
-                &inv_claims.target_url, &target_url
-            ));
-        }
-        if inv_claims.origin_url != inv.origin.url() {
-            return Err("Invocation claims and invocation origin URL do not match".into());
-        }
-        // verify target public key is my key
-        if inv.target.public_key != self.host_data.provider_key {
-            return Err(format!(
-                "target key mismatch: {} != {}",
-                &inv.target.public_key, &self.host_data.host_id
-            ));
-        }
-        // verify that the sending actor is linked with this provider
-        if !self.is_linked(&inv.origin.public_key).await {
-            return Err(format!("unlinked actor: {}", &inv.origin.public_key));
+    /// Verifies the invocation signature and claims, that it is addressed
+    /// to this provider, and that the sender is a linked actor, or a provider
+    /// known to the resolver.
+    pub async fn validate_invocation(&self, inv: &Invocation) -> Result<(), InvocationError> {
+        InvocationVerifier::new(self.host_data.cluster_issuers.clone())
+            .target_key(&self.host_data.provider_key)
+            .verify(inv)?;
+        // verify that the sending actor is linked with this provider.
+        // Messages from other providers are accepted if the resolver
+        // resolves the provider's contract id and link name to its key.
+        if inv.origin.is_actor() {
+            if !self.is_linked(&inv.origin.public_key).await {
+                return Err(InvocationError::UnlinkedActor(
+                    inv.origin.public_key.clone(),
+                ));
+            }
+        } else if !self.is_known_provider(&inv.origin).await {
+            return Err(InvocationError::UnknownProvider(inv.origin.url()));
         }
         Ok(())
     }
 
+    /// Returns true if the resolver resolves the provider's contract id and link name
+    /// to its public key
+    async fn is_known_provider(&self, provider: &WasmCloudEntity) -> bool {
+        matches!(
+            self.resolver()
+                .resolve_provider(&provider.contract_id, &provider.link_name)
+                .await,
+            Ok(public_key) if public_key == provider.public_key
+        )
+    }
+
     async fn subscribe_shutdown<P>(
         &self,
         provider: P,
@@ -450,13 +709,7 @@ impl HostBridge {
             &self.lattice_prefix, &self.host_data.provider_key, self.host_data.link_name
         );
         debug!("subscribing for shutdown : {}", &shutdown_topic);
-        let sub = self
-            .rpc_client()
-            .get_async()
-            .unwrap() // we are only async
-            .subscribe(&shutdown_topic)
-            .await
-            .map_err(|e| RpcError::Nats(e.to_string()))?;
+        let sub = self.rpc_client().bus().subscribe(&shutdown_topic).await?;
         // TODO: there should be validation on this message, but it's not signed by host yet
         let msg = sub.next().await;
 
@@ -466,21 +719,15 @@ impl HostBridge {
         debug!("Received termination signal. Shutting down capability provider.");
         let (this, provider) = (self.clone(), provider.clone());
         if let Err(e) = tokio::spawn(async move {
-            // Tell provider to shutdown - before we shut down nats subscriptions,
-            // in case it needs to do any message passing during shutdown
-            if let Err(e) = provider.shutdown().await {
-                error!("during provider shutdown processing, got error: {}", e);
-            }
-
-            // drain all subscriptions except this one
-            this.unsubscribe_all().await;
+            this.shutdown_provider(&provider, DEFAULT_DRAIN_TIMEOUT)
+                .await;
         })
         .await
         {
             error!("joining thread shutdown/unsubscribe task: {}", e);
         }
         // send ack to host
-        if let Some(crate::anats::Message {
+        if let Some(BusMessage {
             reply: Some(reply_to),
             ..
         }) = msg.as_ref()
@@ -514,13 +761,7 @@ impl HostBridge {
         );
 
         debug!("subscribing for link put : {}", &ldput_topic);
-        let sub = self
-            .rpc_client()
-            .get_async()
-            .unwrap() // we are only async
-            .subscribe(&ldput_topic)
-            .await
-            .map_err(|e| RpcError::Nats(e.to_string()))?;
+        let sub = self.rpc_client().bus().subscribe(&ldput_topic).await?;
         self.add_subscription(sub.clone()).await;
         //let provider = provider.clone();
         let (this, provider) = (self.clone(), provider.clone());
@@ -564,13 +805,7 @@ impl HostBridge {
             &self.lattice_prefix, &self.host_data.provider_key, &self.host_data.link_name
         );
         debug!("subscribing for link del : {}", &link_del_topic);
-        let sub = self
-            .rpc_client()
-            .get_async()
-            .unwrap() // we are only async
-            .subscribe(&link_del_topic)
-            .await
-            .map_err(|e| RpcError::Nats(e.to_string()))?;
+        let sub = self.rpc_client().bus().subscribe(&link_del_topic).await?;
         self.add_subscription(sub.clone()).await;
         let (this, provider) = (self.clone(), provider.clone());
         tokio::spawn(async move {
@@ -594,13 +829,7 @@ impl HostBridge {
             &self.lattice_prefix, &self.host_data.provider_key, &self.host_data.link_name
         );
 
-        let sub = self
-            .rpc_client()
-            .get_async()
-            .unwrap() // we are only async
-            .subscribe(&topic)
-            .await
-            .map_err(|e| RpcError::Nats(e.to_string()))?;
+        let sub = self.rpc_client().bus().subscribe(&topic).await?;
         self.add_subscription(sub.clone()).await;
         let this = self.clone();
         tokio::spawn(async move {
@@ -664,10 +893,97 @@ async fn publish_invocation_response(
     Ok(())
 }
 
+/// Resolves names used by [TransportTarget] into entity public keys
+#[async_trait]
+pub trait NameResolver: Send + Sync {
+    /// Returns the public key of the actor with the call alias
+    async fn resolve_actor_alias(&self, alias: &str) -> RpcResult<String>;
+
+    /// Returns the public key of the capability provider with the contract id and link name
+    async fn resolve_provider(&self, contract_id: &str, link_name: &str) -> RpcResult<String>;
+}
+
+/// NameResolver that uses tables of aliases and providers registered by the provider,
+/// for example, from its configuration.
+#[derive(Default)]
+pub struct StaticResolver {
+    aliases: StdRwLock<HashMap<String, String>>,
+    providers: StdRwLock<HashMap<(String, String), String>>,
+}
+
+impl StaticResolver {
+    /// Adds (or replaces) an actor call alias
+    pub fn add_actor_alias<T1: ToString, T2: ToString>(&self, alias: T1, public_key: T2) {
+        if let Ok(mut write) = self.aliases.write() {
+            write.insert(alias.to_string(), public_key.to_string());
+        }
+    }
+
+    /// Adds (or replaces) the public key for a capability provider
+    pub fn add_provider<T1: ToString, T2: ToString, T3: ToString>(
+        &self,
+        contract_id: T1,
+        link_name: T2,
+        public_key: T3,
+    ) {
+        if let Ok(mut write) = self.providers.write() {
+            write.insert(
+                (contract_id.to_string(), link_name.to_string()),
+                public_key.to_string(),
+            );
+        }
+    }
+}
+
+#[async_trait]
+impl NameResolver for StaticResolver {
+    async fn resolve_actor_alias(&self, alias: &str) -> RpcResult<String> {
+        self.aliases
+            .read()
+            .ok()
+            .and_then(|read| read.get(alias).cloned())
+            .ok_or_else(|| RpcError::InvalidParameter(format!("unknown actor alias '{}'", alias)))
+    }
+
+    async fn resolve_provider(&self, contract_id: &str, link_name: &str) -> RpcResult<String> {
+        self.providers
+            .read()
+            .ok()
+            .and_then(|read| {
+                read.get(&(contract_id.to_string(), link_name.to_string()))
+                    .cloned()
+            })
+            .ok_or_else(|| {
+                RpcError::InvalidParameter(format!(
+                    "unknown provider contract_id '{}' link_name '{}'",
+                    contract_id, link_name
+                ))
+            })
+    }
+}
+
+/// Recipient of messages sent by a ProviderTransport
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum TransportTarget {
+    /// The actor on the other end of the link (this is the default)
+    #[default]
+    LinkedActor,
+    /// An actor, by public key
+    Actor(String),
+    /// An actor, by call alias
+    ActorAlias(String),
+    /// A capability provider, by contract id and link name
+    Provider {
+        contract_id: String,
+        link_name: String,
+    },
+}
+
 pub struct ProviderTransport<'send> {
     pub bridge: &'send HostBridge,
     pub ld: &'send LinkDefinition,
     timeout: StdMutex<std::time::Duration>,
+    target: TransportTarget,
 }
 
 impl<'send> ProviderTransport<'send> {
@@ -693,20 +1009,36 @@ impl<'send> ProviderTransport<'send> {
             timeout: StdMutex::new(
                 timeout.unwrap_or(crate::rpc_client::DEFAULT_RPC_TIMEOUT_MILLIS),
             ),
+            target: TransportTarget::default(),
         }
     }
+
+    /// constructs a ProviderTransport that sends to another actor or provider.
+    /// Messages are signed by this provider, using the link definition
+    /// as the origin. Actor aliases and provider names are looked up with
+    /// the bridge's [NameResolver].
+    /// If the bridge parameter is None, the current (static) bridge is used.
+    pub fn new_with_target(
+        ld: &'send LinkDefinition,
+        bridge: Option<&'send HostBridge>,
+        target: TransportTarget,
+    ) -> Self {
+        let mut transport = Self::new_with_timeout(ld, bridge, None);
+        transport.target = target;
+        transport
+    }
 }
 
 #[async_trait]
-impl<'send> crate::Transport for ProviderTransport<'send> {
+impl<'send> Transport for ProviderTransport<'send> {
     async fn send(
         &self,
         _ctx: &crate::Context,
         req: Message<'_>,
-        _opts: Option<crate::SendOpts>,
+        opts: Option<crate::SendOpts>,
     ) -> std::result::Result<Vec<u8>, RpcError> {
         let origin = self.ld.provider_entity();
-        let target = self.ld.actor_entity();
+        let target = self.bridge.resolve_target(self.ld, &self.target).await?;
         let timeout = {
             if let Ok(rd) = self.timeout.lock() {
                 *rd
@@ -718,7 +1050,13 @@ impl<'send> crate::Transport for ProviderTransport<'send> {
         };
         self.bridge
             .rpc_client()
-            .send_timeout(origin, target, req, timeout)
+            .send_with_opts(
+                origin,
+                target,
+                req,
+                &opts.unwrap_or_default(),
+                Some(timeout),
+            )
             .await
     }
 
@@ -1,11 +1,14 @@
 #![cfg(not(target_arch = "wasm32"))]
 
 use crate::{
+    bus::{MessageBus, NatsBus},
     core::HostData,
     provider::{HostBridge, ProviderDispatch},
-    RpcError,
+    RpcError, RpcResult,
 };
+use futures::future::BoxFuture;
 use once_cell::sync::OnceCell;
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 /// singleton host bridge for communicating with the host.
 static BRIDGE: OnceCell<HostBridge> = OnceCell::new();
@@ -22,14 +25,91 @@ pub fn get_host_bridge() -> &'static HostBridge {
     }
 }
 
+/// Returns the host bridge, if it has been initialized
+pub(crate) fn host_bridge() -> Option<&'static HostBridge> {
+    BRIDGE.get()
+}
+
 /// nats address to use if not included in initial HostData
 const DEFAULT_NATS_ADDR: &str = "nats://127.0.0.1:4222";
 
-/// Start provider services: tokio runtime, logger, nats, and rpc subscriptions
+/// Events, in addition to a shutdown message from the host, that stop the provider.
+/// Each triggers the same graceful shutdown: the provider's `shutdown` handler runs,
+/// subscriptions are closed, and in-flight rpc messages are given time to complete.
+#[derive(Clone, Debug)]
+pub struct ShutdownTriggers {
+    /// stop on SIGTERM or SIGINT (ctrl-c on windows). Default true
+    pub signals: bool,
+    /// stop when stdin reaches end-of-file, which happens when the host process exits.
+    /// Default false
+    pub stdin_eof: bool,
+    /// stop when the parent process id changes, which happens when the host process exits
+    /// and the provider is re-parented. Only supported on unix. Default false
+    pub parent_exit: bool,
+    /// how often to check the parent process id
+    pub poll_interval: Duration,
+    /// time to wait for in-flight rpc messages to complete
+    pub drain_timeout: Duration,
+}
+
+impl Default for ShutdownTriggers {
+    fn default() -> Self {
+        ShutdownTriggers {
+            signals: true,
+            stdin_eof: false,
+            parent_exit: false,
+            poll_interval: Duration::from_secs(1),
+            drain_timeout: crate::provider::DEFAULT_DRAIN_TIMEOUT,
+        }
+    }
+}
+
+impl ShutdownTriggers {
+    /// Only the host's shutdown message stops the provider
+    pub fn none() -> Self {
+        ShutdownTriggers {
+            signals: false,
+            ..Default::default()
+        }
+    }
+
+    /// Stop on signals, and when the host process exits (stdin eof or parent exit)
+    pub fn with_host_watch() -> Self {
+        ShutdownTriggers {
+            stdin_eof: true,
+            parent_exit: true,
+            ..Default::default()
+        }
+    }
+}
+
+/// Start provider services: tokio runtime, logger, nats, and rpc subscriptions.
+/// With the command-line flag `--stdio`, the provider runs without a lattice,
+/// processing json requests from stdin (see [provider_start_stdio](crate::provider_stdio::provider_start_stdio)).
 pub fn provider_main<P>(provider_dispatch: P) -> Result<(), Box<dyn std::error::Error>>
 where
     P: ProviderDispatch + Send + Sync + Clone + 'static,
 {
+    provider_main_with(|_| Ok(provider_dispatch), ShutdownTriggers::default())
+}
+
+/// Start provider services, constructing the provider from the host data
+/// (for example, from its `config_json`), and stopping on a host shutdown message
+/// or any of the triggers. This is the entry point generated by the `#[provider]` macro.
+/// With the command-line flag `--stdio`, the provider runs in stdio mode, as in [provider_main].
+pub fn provider_main_with<P, F>(
+    make_provider: F,
+    triggers: ShutdownTriggers,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    P: ProviderDispatch + Send + Sync + Clone + 'static,
+    F: FnOnce(&HostData) -> RpcResult<P>,
+{
+    if std::env::args().skip(1).any(|arg| arg == "--stdio") {
+        let host_data = crate::provider_stdio::stdio_host_data()?;
+        let provider_dispatch = make_provider(&host_data)?;
+        return crate::provider_stdio::start_stdio(provider_dispatch, host_data);
+    }
     // get lattice configuration from host
     let host_data = match load_host_data() {
         Ok(hd) => hd,
@@ -38,14 +118,28 @@ where
             return Err(Box::new(e));
         }
     };
-    provider_start(provider_dispatch, host_data)
+    let provider_dispatch = make_provider(&host_data)?;
+    provider_start_with_triggers(provider_dispatch, host_data, triggers)
 }
 
 /// Start provider services: tokio runtime, logger, nats, and rpc subscriptions,
+/// The provider stops when the host sends a shutdown message, or on SIGTERM or SIGINT.
 pub fn provider_start<P>(
     provider_dispatch: P,
     host_data: HostData,
 ) -> Result<(), Box<dyn std::error::Error>>
+where
+    P: ProviderDispatch + Send + Sync + Clone + 'static,
+{
+    provider_start_with_triggers(provider_dispatch, host_data, ShutdownTriggers::default())
+}
+
+/// Start provider services, stopping on a host shutdown message or any of the triggers
+pub fn provider_start_with_triggers<P>(
+    provider_dispatch: P,
+    host_data: HostData,
+    triggers: ShutdownTriggers,
+) -> Result<(), Box<dyn std::error::Error>>
 where
     P: ProviderDispatch + Send + Sync + Clone + 'static,
 {
@@ -54,14 +148,17 @@ where
         //.enable_io()
         .build()?;
 
-    runtime.block_on(async { provider_run(provider_dispatch, host_data).await })?;
+    runtime.block_on(async {
+        provider_run_with_triggers(provider_dispatch, host_data, triggers).await
+    })?;
     // in the unlikely case there are any stuck threads,
     // close them so the process has a clean exit
     runtime.shutdown_timeout(core::time::Duration::from_secs(10));
     Ok(())
 }
 
-/// Async provider initialization
+/// Async provider initialization.
+/// The provider stops when the host sends a shutdown message, or on SIGTERM or SIGINT.
 pub async fn provider_run<P>(
     provider_dispatch: P,
     host_data: HostData,
@@ -69,14 +166,20 @@ pub async fn provider_run<P>(
 where
     P: ProviderDispatch + Send + Sync + Clone + 'static,
 {
-    use std::str::FromStr as _;
+    provider_run_with_triggers(provider_dispatch, host_data, ShutdownTriggers::default()).await
+}
 
-    // initialize logger
-    let log_rx = crate::channel_log::init_logger()
-        .map_err(|_| RpcError::ProviderInit("log already initialized".to_string()))?;
-    crate::channel_log::init_receiver(log_rx);
+/// Async provider initialization, stopping on a host shutdown message or any of the triggers
+pub async fn provider_run_with_triggers<P>(
+    provider_dispatch: P,
+    host_data: HostData,
+    triggers: ShutdownTriggers,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    P: ProviderDispatch + Send + Sync + Clone + 'static,
+{
+    use std::str::FromStr as _;
 
-    let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
     eprintln!(
         "Starting capability provider {} instance {} with nats url {}",
         &host_data.provider_key, &host_data.instance_id, &host_data.lattice_rpc_url,
@@ -100,10 +203,73 @@ where
             RpcError::ProviderInit(format!("nats connection to {} failed: {}", nats_addr, e))
         })?;
 
+    run_with_bus(
+        provider_dispatch,
+        host_data,
+        Arc::new(NatsBus::new(nc)),
+        triggers,
+    )
+    .await
+}
+
+/// Async provider initialization, using the message bus for host messages and rpc.
+/// With an [InProcessBus](crate::bus::InProcessBus), a provider can be run
+/// inside a test, with the test acting as the host.
+///
+/// Only one provider may run in a process, unless the host data is for a test
+/// (host_id "_TEST_"). Under test, providers after the first run with their own
+/// HostBridge, but `get_host_bridge`, and `ProviderTransport`s constructed without a bridge,
+/// use the first provider's bridge. The test providers share the logger started by
+/// the first of them, which stops logging when that provider exits.
+///
+/// The provider stops only when the host sends a shutdown message.
+pub async fn provider_run_with_bus<P>(
+    provider_dispatch: P,
+    host_data: HostData,
+    bus: Arc<dyn MessageBus>,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    P: ProviderDispatch + Send + Sync + Clone + 'static,
+{
+    run_with_bus(provider_dispatch, host_data, bus, ShutdownTriggers::none()).await
+}
+
+pub(crate) async fn run_with_bus<P>(
+    provider_dispatch: P,
+    host_data: HostData,
+    bus: Arc<dyn MessageBus>,
+    triggers: ShutdownTriggers,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    P: ProviderDispatch + Send + Sync + Clone + 'static,
+{
     // initialize HostBridge
-    let bridge = HostBridge::new(nc, &host_data)?;
-    let _ = BRIDGE.set(bridge);
-    let bridge = get_host_bridge();
+    let bridge = HostBridge::new_with_bus(bus, &host_data)?;
+    let bridge: &'static HostBridge = match BRIDGE.set(bridge) {
+        Ok(()) => get_host_bridge(),
+        // under test, another provider in this process already set the singleton
+        Err(bridge) if host_data.is_test() => Box::leak(Box::new(bridge)),
+        Err(_) => {
+            return Err(Box::new(RpcError::ProviderInit(
+                "a provider is already running in this process".to_string(),
+            )))
+        }
+    };
+
+    // initialize logger, unless the provider installed its own before starting
+    // (or, under test, another provider in this process started it)
+    let log_started = match crate::channel_log::init_logger() {
+        Ok(log_rx) => {
+            crate::channel_log::init_receiver(log_rx);
+            true
+        }
+        Err(_) => {
+            log::debug!("logger already initialized - using the installed logger");
+            false
+        }
+    };
+
+    let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
 
     // pre-populate provider and bridge with initial set of link definitions
     // initialization of any link is fatal for provider startup
@@ -121,22 +287,299 @@ where
 
     // subscribe to nats topics
     let _join = bridge
-        .connect(provider_dispatch, shutdown_tx)
+        .connect(provider_dispatch.clone(), shutdown_tx)
         .await
         .map_err(|e| {
             RpcError::ProviderInit(format!("fatal error setting up subscriptions: {}", e))
         })?;
 
     // process subscription events and log messages, waiting for shutdown signal
-    let _ = shutdown_rx.await;
+    let termination = wait_for_termination(&triggers)?;
+    tokio::select! {
+        _ = shutdown_rx => {}
+        reason = termination => {
+            log::info!("{}: shutting down capability provider", reason);
+            bridge
+                .shutdown_provider(&provider_dispatch, triggers.drain_timeout)
+                .await;
+        }
+    }
     // stop the logger thread
     //let _ = stop_log_thread.send(());
-    crate::channel_log::stop_receiver();
+    if log_started {
+        crate::channel_log::stop_receiver();
+    }
 
     Ok(())
 }
 
+/// Returns a future that completes, with a description of the event,
+/// when any of the shutdown triggers occurs. If no triggers are enabled, it never completes.
+fn wait_for_termination(
+    triggers: &ShutdownTriggers,
+) -> Result<BoxFuture<'static, String>, RpcError> {
+    let mut events: Vec<BoxFuture<'static, String>> = Vec::new();
+    if triggers.signals {
+        events.push(Box::pin(async {
+            let _ = tokio::signal::ctrl_c().await;
+            "received SIGINT".to_string()
+        }));
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            let mut sigterm = signal(SignalKind::terminate()).map_err(|e| {
+                RpcError::ProviderInit(format!("failed to install SIGTERM handler: {}", e))
+            })?;
+            events.push(Box::pin(async move {
+                sigterm.recv().await;
+                "received SIGTERM".to_string()
+            }));
+        }
+    }
+    if triggers.stdin_eof {
+        // read on a plain thread, so a blocked read doesn't delay runtime shutdown
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        std::thread::spawn(move || {
+            use std::io::Read as _;
+            let mut buf = [0u8; 512];
+            let stdin = std::io::stdin();
+            let mut handle = stdin.lock();
+            loop {
+                match handle.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(_) => {}
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                    Err(_) => break,
+                }
+            }
+            let _ = tx.send(());
+        });
+        events.push(Box::pin(async {
+            let _ = rx.await;
+            "stdin closed, host has exited".to_string()
+        }));
+    }
+    #[cfg(unix)]
+    {
+        if triggers.parent_exit {
+            let parent = std::os::unix::process::parent_id();
+            let poll_interval = triggers.poll_interval;
+            events.push(Box::pin(async move {
+                loop {
+                    tokio::time::sleep(poll_interval).await;
+                    if std::os::unix::process::parent_id() != parent {
+                        return format!("parent process {} has exited", parent);
+                    }
+                }
+            }));
+        }
+    }
+    Ok(if events.is_empty() {
+        Box::pin(futures::future::pending())
+    } else {
+        Box::pin(async move { futures::future::select_all(events).await.0 })
+    })
+}
+
+/// Loads host data for the provider.
+///
+/// When a provider is started by the host, host data is read from stdin as one line of
+/// base64-encoded json. To make it easier to run a provider by hand, host data can also
+/// come from a file, `WASMCLOUD_*` environment variables, and command-line flags.
+/// These are used instead of stdin if any host data flag is present on the command line,
+/// or if either `WASMCLOUD_HOST_DATA` or `WASMCLOUD_PROVIDER_KEY` is set.
+///
+/// Sources are applied in order, with later sources overriding earlier ones:
+/// 1. a json or toml file (`--host-data PATH`, or `WASMCLOUD_HOST_DATA=PATH`),
+///    or generated test data (`--test`, see [HostData::for_test])
+/// 2. environment variables
+/// 3. command-line flags
+///
+/// | flag                 | environment variable         | field                  |
+/// |----------------------|------------------------------|------------------------|
+/// | `--host-id`          | `WASMCLOUD_HOST_ID`          | `host_id`              |
+/// | `--lattice-prefix`   | `WASMCLOUD_LATTICE_PREFIX`   | `lattice_rpc_prefix`   |
+/// | `--link-name`        | `WASMCLOUD_LINK_NAME`        | `link_name`            |
+/// | `--nats-url`         | `WASMCLOUD_NATS_URL`         | `lattice_rpc_url`      |
+/// | `--nats-jwt`         | `WASMCLOUD_NATS_JWT`         | `lattice_rpc_user_jwt` |
+/// | `--nats-seed`        | `WASMCLOUD_NATS_SEED`        | `lattice_rpc_user_seed`|
+/// | `--provider-key`     | `WASMCLOUD_PROVIDER_KEY`     | `provider_key`         |
+/// | `--invocation-seed`  | `WASMCLOUD_INVOCATION_SEED`  | `invocation_seed`      |
+/// | `--instance-id`      | `WASMCLOUD_INSTANCE_ID`      | `instance_id`          |
+/// | `--cluster-issuers`  | `WASMCLOUD_CLUSTER_ISSUERS`  | `cluster_issuers` (comma-separated) |
+/// | `--config-json`      | `WASMCLOUD_CONFIG_JSON`      | `config_json`          |
+/// | `--env KEY=VALUE`    |                              | `env_values` (repeatable) |
+///
+/// Flags may be written `--flag value` or `--flag=value`. Other arguments are ignored.
 pub fn load_host_data() -> Result<HostData, RpcError> {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    let vars = std::env::vars().collect::<HashMap<String, String>>();
+    match load_host_data_from(&args, &vars)? {
+        Some(host_data) => Ok(host_data),
+        None => load_host_data_stdin(),
+    }
+}
+
+/// environment variable with the path of a host data file
+const HOST_DATA_FILE_VAR: &str = "WASMCLOUD_HOST_DATA";
+
+type FieldSetter = fn(&mut HostData, &str);
+
+/// host data fields that can be set by flag or environment variable
+const HOST_DATA_FIELDS: &[(&str, &str, FieldSetter)] = &[
+    ("--host-id", "WASMCLOUD_HOST_ID", |hd, v| {
+        hd.host_id = v.to_string()
+    }),
+    ("--lattice-prefix", "WASMCLOUD_LATTICE_PREFIX", |hd, v| {
+        hd.lattice_rpc_prefix = v.to_string()
+    }),
+    ("--link-name", "WASMCLOUD_LINK_NAME", |hd, v| {
+        hd.link_name = v.to_string()
+    }),
+    ("--nats-url", "WASMCLOUD_NATS_URL", |hd, v| {
+        hd.lattice_rpc_url = v.to_string()
+    }),
+    ("--nats-jwt", "WASMCLOUD_NATS_JWT", |hd, v| {
+        hd.lattice_rpc_user_jwt = v.to_string()
+    }),
+    ("--nats-seed", "WASMCLOUD_NATS_SEED", |hd, v| {
+        hd.lattice_rpc_user_seed = v.to_string()
+    }),
+    ("--provider-key", "WASMCLOUD_PROVIDER_KEY", |hd, v| {
+        hd.provider_key = v.to_string()
+    }),
+    ("--invocation-seed", "WASMCLOUD_INVOCATION_SEED", |hd, v| {
+        hd.invocation_seed = v.to_string()
+    }),
+    ("--instance-id", "WASMCLOUD_INSTANCE_ID", |hd, v| {
+        hd.instance_id = v.to_string()
+    }),
+    ("--cluster-issuers", "WASMCLOUD_CLUSTER_ISSUERS", |hd, v| {
+        hd.cluster_issuers = v
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect()
+    }),
+    ("--config-json", "WASMCLOUD_CONFIG_JSON", |hd, v| {
+        hd.config_json = Some(v.to_string())
+    }),
+];
+
+/// Loads host data from command-line arguments (excluding the program name) and
+/// environment variables, as described in [load_host_data].
+/// Returns None if neither contains host data, in which case the host
+/// is expected to send host data on stdin.
+pub fn load_host_data_from(
+    args: &[String],
+    vars: &HashMap<String, String>,
+) -> Result<Option<HostData>, RpcError> {
+    let flags = parse_host_data_flags(args)?;
+    if flags.is_empty()
+        && !vars.contains_key(HOST_DATA_FILE_VAR)
+        && !vars.contains_key("WASMCLOUD_PROVIDER_KEY")
+    {
+        return Ok(None);
+    }
+    let flag = |name: &str| flags.iter().rev().find(|(f, _)| f == name).map(|(_, v)| v);
+
+    let mut host_data = if flags.iter().any(|(f, _)| f == "--test") {
+        HostData::for_test()
+    } else {
+        match flag("--host-data").or_else(|| vars.get(HOST_DATA_FILE_VAR)) {
+            Some(path) => load_host_data_file(std::path::Path::new(path))?,
+            None => HostData::default(),
+        }
+    };
+    for (_, var, set) in HOST_DATA_FIELDS.iter() {
+        if let Some(value) = vars.get(*var) {
+            set(&mut host_data, value);
+        }
+    }
+    for (name, value) in flags.iter() {
+        if let Some((_, _, set)) = HOST_DATA_FIELDS.iter().find(|(f, _, _)| f == name) {
+            set(&mut host_data, value);
+        } else if name == "--env" {
+            let (key, val) = value.split_once('=').ok_or_else(|| {
+                RpcError::InvalidParameter(format!("--env expects KEY=VALUE, got '{}'", value))
+            })?;
+            host_data
+                .env_values
+                .insert(key.to_string(), val.to_string());
+        }
+    }
+    if host_data.provider_key.is_empty() {
+        return Err(RpcError::InvalidParameter(
+            "host data is missing provider_key".to_string(),
+        ));
+    }
+    Ok(Some(host_data))
+}
+
+/// Returns the host data flags and their values, in the order they appear.
+/// `--test` has an empty value
+fn parse_host_data_flags(args: &[String]) -> Result<Vec<(String, String)>, RpcError> {
+    let mut flags = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+        if name == "--test" {
+            flags.push((name.to_string(), String::new()));
+            continue;
+        }
+        let known = name == "--host-data"
+            || name == "--env"
+            || HOST_DATA_FIELDS.iter().any(|(f, _, _)| *f == name);
+        if !known {
+            continue;
+        }
+        let value = match inline_value {
+            Some(value) => value,
+            None => iter
+                .next()
+                .cloned()
+                .ok_or_else(|| RpcError::InvalidParameter(format!("missing value for {}", name)))?,
+        };
+        flags.push((name.to_string(), value));
+    }
+    Ok(flags)
+}
+
+/// Loads host data from a file. Files ending in ".toml" are parsed as toml,
+/// others as json. Fields that are omitted have default (empty) values.
+pub fn load_host_data_file(path: &std::path::Path) -> Result<HostData, RpcError> {
+    let text = std::fs::read_to_string(path).map_err(|e| {
+        RpcError::InvalidParameter(format!("reading host data file {}: {}", path.display(), e))
+    })?;
+    let is_toml = path.extension().map(|ext| ext == "toml").unwrap_or(false);
+    let mut value: serde_json::Value = if is_toml {
+        toml::from_str(&text).map_err(|e| e.to_string())
+    } else {
+        serde_json::from_str(&text).map_err(|e| e.to_string())
+    }
+    .map_err(|e| {
+        RpcError::InvalidParameter(format!("parsing host data file {}: {}", path.display(), e))
+    })?;
+    // these fields are required in the serialized HostData from the host
+    if let Some(obj) = value.as_object_mut() {
+        for (field, empty) in [
+            ("env_values", serde_json::json!({})),
+            ("link_definitions", serde_json::json!([])),
+            ("cluster_issuers", serde_json::json!([])),
+        ] {
+            obj.entry(field).or_insert(empty);
+        }
+    }
+    serde_json::from_value(value).map_err(|e| {
+        RpcError::InvalidParameter(format!("parsing host data file {}: {}", path.display(), e))
+    })
+}
+
+/// Loads host data sent by the host on stdin, as one line of base64-encoded json
+pub fn load_host_data_stdin() -> Result<HostData, RpcError> {
     use std::io::BufRead;
 
     let mut buffer = String::new();
@@ -173,3 +616,50 @@ pub fn load_host_data() -> Result<HostData, RpcError> {
     })?;
     Ok(host_data)
 }
+
+#[test]
+fn host_data_precedence() {
+    let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<String>>();
+    let no_vars = HashMap::new();
+
+    // nothing set: read from stdin
+    assert_eq!(
+        load_host_data_from(&args(&["--verbose"]), &no_vars).unwrap(),
+        None
+    );
+
+    let file =
+        std::env::temp_dir().join(format!("host_data_{}.toml", crate::provider::make_uuid()));
+    std::fs::write(
+        &file,
+        "provider_key = \"VFILE\"\nlink_name = \"file\"\nlattice_rpc_prefix = \"file\"\n",
+    )
+    .unwrap();
+    let mut vars = HashMap::new();
+    vars.insert(HOST_DATA_FILE_VAR.to_string(), file.display().to_string());
+    vars.insert("WASMCLOUD_LINK_NAME".to_string(), "env".to_string());
+    vars.insert(
+        "WASMCLOUD_CLUSTER_ISSUERS".to_string(),
+        "C1, C2".to_string(),
+    );
+
+    // environment overrides file, flags override environment
+    let hd = load_host_data_from(&args(&["--link-name=cli", "--env", "A=1"]), &vars)
+        .unwrap()
+        .unwrap();
+    let _ = std::fs::remove_file(&file);
+    assert_eq!(hd.provider_key, "VFILE");
+    assert_eq!(hd.lattice_rpc_prefix, "file");
+    assert_eq!(hd.link_name, "cli");
+    assert_eq!(hd.cluster_issuers, vec!["C1".to_string(), "C2".to_string()]);
+    assert_eq!(hd.env_values.get("A").map(String::as_str), Some("1"));
+
+    let hd = load_host_data_from(&args(&["--test", "--link-name", "x"]), &no_vars)
+        .unwrap()
+        .unwrap();
+    assert!(hd.is_test());
+    assert_eq!(hd.link_name, "x");
+
+    assert!(load_host_data_from(&args(&["--link-name"]), &no_vars).is_err());
+    assert!(load_host_data_from(&args(&["--link-name", "x"]), &no_vars).is_err());
+}
@@ -0,0 +1,415 @@
+//! Run a provider without a lattice, using json lines on stdin and stdout
+//!
+//! Start the provider with `--stdio` (or call [provider_run_stdio]). Each input line
+//! is a json object, and for each one a json response line is written to the output.
+//! Blank lines and lines starting with '#' are ignored, so a script can be a plain file:
+//!
+//! ```text
+//! {"op":"link_put", "actor":"MBCFOPM6JW2APJLXJD3Z5O4CN7CPYJ2B4FTKLJUR5YR5MITIU7HD3WD5", "values":{"port":"8080"}}
+//! {"op":"health"}
+//! {"id":1, "actor":"MBCFOPM6JW2APJLXJD3Z5O4CN7CPYJ2B4FTKLJUR5YR5MITIU7HD3WD5", "method":"KeyValue.Get", "args":"key1"}
+//! {"op":"link_del", "actor":"MBCFOPM6JW2APJLXJD3Z5O4CN7CPYJ2B4FTKLJUR5YR5MITIU7HD3WD5"}
+//! ```
+//!
+//! Operations (`op`, default "rpc"):
+//! - `rpc`: sends `method` to the provider from `actor`. `args` is json, converted to the
+//!   provider's wire format, or `args_base64` is the already-encoded message.
+//!   The response is in `result`, or in `result_base64` if it can't be represented as json.
+//!   The actor must have been linked with `link_put`.
+//! - `link_put`: links `actor` with the provider, with optional `contract_id` and `values`
+//! - `link_del`: removes the link for `actor`
+//! - `health`: returns the provider's health check response
+//! - `shutdown`: shuts down the provider. Reaching end of input also shuts down the provider.
+//!
+//! Each response includes the `id` of the request, if it had one, and either `result` or `error`.
+//!
+//! The provider runs with test host data (see [HostData::for_test]), and sends and receives
+//! messages on an [InProcessBus]. Messages the provider sends to actors fail with "no responders".
+//!
+#![cfg(not(target_arch = "wasm32"))]
+
+use crate::{
+    bus::{InProcessBus, MessageBus},
+    core::{HealthCheckResponse, HostData, LinkDefinition, LinkSettings, WasmCloudEntity},
+    provider::ProviderDispatch,
+    provider_main::{host_bridge, run_with_bus, ShutdownTriggers},
+    Message, RpcClient, RpcError, RpcResult,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::{borrow::Cow, sync::Arc, time::Duration};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+
+/// time to wait for the provider to respond to a message
+const STDIO_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// time to wait for the provider to subscribe, or to process a link change
+const STDIO_WAIT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct StdioRequest {
+    id: Option<JsonValue>,
+    op: Option<String>,
+    actor: Option<String>,
+    method: Option<String>,
+    args: Option<JsonValue>,
+    args_base64: Option<String>,
+    contract_id: Option<String>,
+    values: Option<LinkSettings>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct StdioResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<JsonValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<JsonValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result_base64: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Runs the provider in stdio mode, with host data from command-line flags or
+/// environment variables (see [load_host_data](crate::provider_main::load_host_data)),
+/// or generated test host data if there are none.
+/// This is called by `provider_main` when the command line contains `--stdio`.
+pub fn provider_start_stdio<P>(provider_dispatch: P) -> Result<(), Box<dyn std::error::Error>>
+where
+    P: ProviderDispatch + Send + Sync + Clone + 'static,
+{
+    let host_data = stdio_host_data()?;
+    start_stdio(provider_dispatch, host_data)
+}
+
+/// Returns host data for stdio mode, from command-line flags or environment variables,
+/// or generated test host data if there are none
+pub(crate) fn stdio_host_data() -> RpcResult<HostData> {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    let vars = std::env::vars().collect();
+    Ok(crate::provider_main::load_host_data_from(&args, &vars)?.unwrap_or_else(HostData::for_test))
+}
+
+/// Runs the provider in stdio mode, on a new runtime, reading from stdin and writing to stdout
+pub(crate) fn start_stdio<P>(
+    provider_dispatch: P,
+    host_data: HostData,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    P: ProviderDispatch + Send + Sync + Clone + 'static,
+{
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(async {
+        provider_run_stdio(
+            provider_dispatch,
+            host_data,
+            tokio::io::BufReader::new(tokio::io::stdin()),
+            tokio::io::stdout(),
+        )
+        .await
+    })?;
+    // stdin may still be blocked in a read
+    runtime.shutdown_timeout(Duration::from_secs(1));
+    Ok(())
+}
+
+/// Runs the provider, reading requests from `input` and writing responses to `output`,
+/// until a shutdown request or the end of input.
+///
+/// The host id is replaced with the test host id, so the provider accepts json
+/// link definitions and health checks, and a generated key for signing
+/// invocations is added to the cluster issuers.
+pub async fn provider_run_stdio<P, R, W>(
+    provider_dispatch: P,
+    mut host_data: HostData,
+    input: R,
+    mut output: W,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    P: ProviderDispatch + Send + Sync + Clone + 'static,
+    R: AsyncBufRead + Unpin + Send,
+    W: AsyncWrite + Unpin + Send,
+{
+    let test_data = HostData::for_test();
+    host_data.host_id = test_data.host_id;
+    if host_data.provider_key.is_empty() {
+        host_data.provider_key = test_data.provider_key;
+    }
+    if host_data.link_name.is_empty() {
+        host_data.link_name = test_data.link_name;
+    }
+    if host_data.lattice_rpc_prefix.is_empty() {
+        host_data.lattice_rpc_prefix = test_data.lattice_rpc_prefix;
+    }
+    let issuer = wascap::prelude::KeyPair::new_cluster();
+    host_data.cluster_issuers.push(issuer.public_key());
+
+    let bus = InProcessBus::default();
+    let client = RpcClient::new_with_bus(
+        Arc::new(bus.clone()),
+        &host_data.lattice_rpc_prefix,
+        issuer,
+        wascap::prelude::KeyPair::new_server().public_key(),
+        Some(STDIO_TIMEOUT),
+    );
+    let session = StdioSession {
+        bus: bus.clone(),
+        client,
+        host_data: host_data.clone(),
+    };
+
+    let provider = run_with_bus(
+        provider_dispatch,
+        host_data,
+        Arc::new(bus),
+        ShutdownTriggers::none(),
+    );
+    tokio::pin!(provider);
+    // the provider can exit early if initialization fails
+    tokio::select! {
+        res = &mut provider => return res,
+        ready = session.wait_for_provider() => ready?,
+    }
+
+    let mut lines = input.lines();
+    let mut shutdown_sent = false;
+    loop {
+        let line = match lines.next_line().await? {
+            Some(line) => line,
+            None => break,
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (resp, is_shutdown) = session.handle_line(line).await;
+        let mut buf = serde_json::to_vec(&resp)?;
+        buf.push(b'\n');
+        output.write_all(&buf).await?;
+        output.flush().await?;
+        if is_shutdown {
+            shutdown_sent = resp.error.is_none();
+            break;
+        }
+    }
+    if !shutdown_sent {
+        // end of input
+        let _ = session.shutdown().await;
+    }
+    provider.await
+}
+
+struct StdioSession {
+    bus: InProcessBus,
+    client: RpcClient,
+    host_data: HostData,
+}
+
+impl StdioSession {
+    fn topic(&self, suffix: &str) -> String {
+        format!(
+            "wasmbus.rpc.{}.{}.{}.{}",
+            &self.host_data.lattice_rpc_prefix,
+            &self.host_data.provider_key,
+            &self.host_data.link_name,
+            suffix
+        )
+    }
+
+    /// waits until the provider has subscribed to its shutdown and rpc topics
+    async fn wait_for_provider(&self) -> RpcResult<()> {
+        let rpc_topic =
+            crate::rpc_topic(&self.provider_entity(), &self.host_data.lattice_rpc_prefix);
+        let topics = [
+            rpc_topic,
+            self.topic("health"),
+            self.topic("linkdefs.put"),
+            self.topic("linkdefs.del"),
+            self.topic("shutdown"),
+        ];
+        let started = tokio::time::Instant::now();
+        while !topics.iter().all(|t| self.bus.has_subscribers(t)) {
+            if started.elapsed() > STDIO_WAIT {
+                return Err(RpcError::ProviderInit(
+                    "provider did not subscribe to host topics".to_string(),
+                ));
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        Ok(())
+    }
+
+    fn provider_entity(&self) -> WasmCloudEntity {
+        WasmCloudEntity {
+            public_key: self.host_data.provider_key.clone(),
+            link_name: self.host_data.link_name.clone(),
+            contract_id: String::new(),
+        }
+    }
+
+    /// Processes one request line. Returns the response, and whether the provider was shut down
+    async fn handle_line(&self, line: &str) -> (StdioResponse, bool) {
+        let req = match serde_json::from_str::<StdioRequest>(line) {
+            Ok(req) => req,
+            Err(e) => {
+                return (
+                    StdioResponse {
+                        error: Some(format!("invalid request: {}", e)),
+                        ..Default::default()
+                    },
+                    false,
+                )
+            }
+        };
+        let id = req.id.clone();
+        let op = req.op.clone().unwrap_or_else(|| "rpc".to_string());
+        let result = match op.as_str() {
+            "rpc" => self.rpc(req).await,
+            "link_put" => self.link_put(req).await.map(|_| JsonValue::Null.into()),
+            "link_del" => self.link_del(req).await.map(|_| JsonValue::Null.into()),
+            "health" => self.health().await.map(StdioResponse::from),
+            "shutdown" => self.shutdown().await.map(|s| JsonValue::String(s).into()),
+            _ => Err(RpcError::InvalidParameter(format!("unknown op '{}'", op))),
+        };
+        let mut resp = result.unwrap_or_else(|e| StdioResponse {
+            error: Some(e.to_string()),
+            ..Default::default()
+        });
+        resp.id = id;
+        (resp, op == "shutdown")
+    }
+
+    fn actor(req: &StdioRequest) -> RpcResult<String> {
+        let actor = req
+            .actor
+            .as_ref()
+            .ok_or_else(|| RpcError::InvalidParameter("missing 'actor'".to_string()))?;
+        crate::core::validate_public_key(actor, crate::core::KeyType::Actor)?;
+        Ok(actor.clone())
+    }
+
+    async fn rpc(&self, req: StdioRequest) -> RpcResult<StdioResponse> {
+        let actor = Self::actor(&req)?;
+        let method = req
+            .method
+            .as_ref()
+            .ok_or_else(|| RpcError::InvalidParameter("missing 'method'".to_string()))?;
+        let arg = match (&req.args_base64, &req.args) {
+            (Some(b64), _) => base64::decode(b64)
+                .map_err(|e| RpcError::InvalidParameter(format!("invalid args_base64: {}", e)))?,
+            (None, Some(args)) => crate::serialize(args)?,
+            (None, None) => Vec::new(),
+        };
+        let mut target = self.provider_entity();
+        if let Some(bridge) = host_bridge() {
+            if let Some(ld) = bridge.get_link(&actor).await {
+                target.contract_id = ld.contract_id;
+            }
+        }
+        let resp = self
+            .client
+            .send(
+                WasmCloudEntity::new_actor(&actor)?,
+                target,
+                Message {
+                    method,
+                    arg: Cow::Owned(arg),
+                },
+            )
+            .await?;
+        if resp.is_empty() {
+            return Ok(JsonValue::Null.into());
+        }
+        Ok(match crate::deserialize::<JsonValue>(&resp) {
+            Ok(value) => value.into(),
+            // binary data has no json representation
+            Err(_) => StdioResponse {
+                result_base64: Some(base64::encode(&resp)),
+                ..Default::default()
+            },
+        })
+    }
+
+    async fn link_put(&self, req: StdioRequest) -> RpcResult<()> {
+        let actor = Self::actor(&req)?;
+        let ld = LinkDefinition {
+            actor_id: actor.clone(),
+            provider_id: self.host_data.provider_key.clone(),
+            link_name: self.host_data.link_name.clone(),
+            contract_id: req.contract_id.unwrap_or_default(),
+            values: req.values.unwrap_or_default(),
+        };
+        self.bus
+            .publish(&self.topic("linkdefs.put"), &to_json(&ld)?)
+            .await?;
+        self.wait_for_link(&actor, true).await
+    }
+
+    async fn link_del(&self, req: StdioRequest) -> RpcResult<()> {
+        let actor = Self::actor(&req)?;
+        let ld = LinkDefinition {
+            actor_id: actor.clone(),
+            provider_id: self.host_data.provider_key.clone(),
+            link_name: self.host_data.link_name.clone(),
+            ..Default::default()
+        };
+        self.bus
+            .publish(&self.topic("linkdefs.del"), &to_json(&ld)?)
+            .await?;
+        self.wait_for_link(&actor, false).await
+    }
+
+    /// link changes are processed asynchronously, so wait until the bridge reflects the change
+    async fn wait_for_link(&self, actor: &str, linked: bool) -> RpcResult<()> {
+        let bridge = match host_bridge() {
+            Some(bridge) => bridge,
+            None => return Ok(()),
+        };
+        let started = tokio::time::Instant::now();
+        while bridge.is_linked(actor).await != linked {
+            if started.elapsed() > STDIO_WAIT {
+                return Err(RpcError::Other(if linked {
+                    format!("link to {} was not accepted by the provider", actor)
+                } else {
+                    format!("link to {} was not removed", actor)
+                }));
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        Ok(())
+    }
+
+    async fn health(&self) -> RpcResult<JsonValue> {
+        let resp = self
+            .bus
+            .request(&self.topic("health"), b"", Some(STDIO_TIMEOUT))
+            .await?;
+        let health: HealthCheckResponse =
+            serde_json::from_slice(&resp).map_err(|e| RpcError::Deser(e.to_string()))?;
+        serde_json::to_value(&health).map_err(|e| RpcError::Ser(e.to_string()))
+    }
+
+    async fn shutdown(&self) -> RpcResult<String> {
+        let resp = self
+            .bus
+            .request(&self.topic("shutdown"), b"", Some(STDIO_TIMEOUT))
+            .await?;
+        Ok(String::from_utf8_lossy(&resp).to_string())
+    }
+}
+
+impl From<JsonValue> for StdioResponse {
+    fn from(result: JsonValue) -> Self {
+        StdioResponse {
+            result: Some(result),
+            ..Default::default()
+        }
+    }
+}
+
+fn to_json<T: Serialize>(data: &T) -> RpcResult<Vec<u8>> {
+    serde_json::to_vec(data).map_err(|e| RpcError::Ser(e.to_string()))
+}
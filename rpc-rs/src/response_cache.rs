@@ -0,0 +1,199 @@
+//! Client-side cache of responses to read-only rpc messages
+//!
+//! RpcClient uses the cache, if configured, for messages sent with
+//! `SendOpts::read_only(true)`. Entries are keyed by origin, target, method,
+//! and a hash of the message args, so a response is only returned to the origin
+//! (actor or provider) that requested it. Entries expire after a fixed time-to-live,
+//! and the least-recently-used entries are evicted when the cache is full.
+//!
+#![cfg(not(target_arch = "wasm32"))]
+
+use crate::rpc_client::invocation_hash;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Settings for the RpcClient response cache
+#[derive(Clone, Debug)]
+pub struct CacheConfig {
+    /// maximum number of cached responses
+    pub max_entries: usize,
+    /// time a response remains valid after it is received
+    pub ttl: Duration,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            max_entries: 1000,
+            ttl: Duration::from_secs(5),
+        }
+    }
+}
+
+pub(crate) struct ResponseCache {
+    config: CacheConfig,
+    inner: Mutex<CacheInner>,
+}
+
+#[derive(Default)]
+struct CacheInner {
+    entries: HashMap<String, CacheEntry>,
+    /// keys ordered by last use. The first entry is the least recently used
+    lru: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+struct CacheEntry {
+    response: Vec<u8>,
+    /// target url, including method, used for invalidation
+    target_url: String,
+    expires: Instant,
+    tick: u64,
+}
+
+impl CacheInner {
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.lru.remove(&entry.tick);
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+impl ResponseCache {
+    pub(crate) fn new(config: CacheConfig) -> Self {
+        ResponseCache {
+            config,
+            inner: Mutex::new(CacheInner::default()),
+        }
+    }
+
+    /// Returns the cache key for a message. `target_url` includes the method
+    pub(crate) fn key(target_url: &str, origin_url: &str, method: &str, arg: &[u8]) -> String {
+        invocation_hash(target_url, origin_url, method, arg)
+    }
+
+    /// Returns the cached response, if present and not expired
+    pub(crate) fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let mut inner = self.inner.lock().ok()?;
+        let expires = inner.entries.get(key)?.expires;
+        if expires <= Instant::now() {
+            inner.remove(key);
+            return None;
+        }
+        let tick = inner.next_tick();
+        let entry = inner.entries.get_mut(key)?;
+        let old_tick = std::mem::replace(&mut entry.tick, tick);
+        let response = entry.response.clone();
+        inner.lru.remove(&old_tick);
+        inner.lru.insert(tick, key.to_string());
+        Some(response)
+    }
+
+    /// Adds a response, evicting the least recently used entries if the cache is full
+    pub(crate) fn insert(&self, key: String, target_url: &str, response: Vec<u8>) {
+        if self.config.max_entries == 0 {
+            return;
+        }
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.remove(&key);
+            while inner.entries.len() >= self.config.max_entries {
+                match inner.lru.keys().next().copied() {
+                    Some(oldest) => {
+                        if let Some(old_key) = inner.lru.remove(&oldest) {
+                            inner.entries.remove(&old_key);
+                        }
+                    }
+                    None => break,
+                }
+            }
+            let tick = inner.next_tick();
+            inner.lru.insert(tick, key.clone());
+            inner.entries.insert(
+                key,
+                CacheEntry {
+                    response,
+                    target_url: target_url.to_string(),
+                    expires: Instant::now() + self.config.ttl,
+                    tick,
+                },
+            );
+        }
+    }
+
+    /// Removes entries whose target url (including method) starts with the prefix
+    pub(crate) fn invalidate(&self, target_prefix: &str) {
+        if let Ok(mut inner) = self.inner.lock() {
+            let keys = inner
+                .entries
+                .iter()
+                .filter(|(_, e)| e.target_url.starts_with(target_prefix))
+                .map(|(k, _)| k.clone())
+                .collect::<Vec<String>>();
+            for key in keys.iter() {
+                inner.remove(key);
+            }
+        }
+    }
+
+    /// Removes all entries
+    pub(crate) fn clear(&self) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.entries.clear();
+            inner.lru.clear();
+        }
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+}
+
+#[test]
+fn cache_lru_and_ttl() {
+    let cache = ResponseCache::new(CacheConfig {
+        max_entries: 2,
+        ttl: Duration::from_secs(60),
+    });
+    let (a, b, c) = ("wasmbus://A/Get", "wasmbus://B/Get", "wasmbus://C/Get");
+    let o = "wasmbus://O";
+    cache.insert(ResponseCache::key(a, o, "Get", b"1"), a, b"a".to_vec());
+    cache.insert(ResponseCache::key(b, o, "Get", b"1"), b, b"b".to_vec());
+    // use a, so b is least recently used
+    assert_eq!(
+        cache.get(&ResponseCache::key(a, o, "Get", b"1")),
+        Some(b"a".to_vec())
+    );
+    cache.insert(ResponseCache::key(c, o, "Get", b"1"), c, b"c".to_vec());
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get(&ResponseCache::key(b, o, "Get", b"1")).is_none());
+    // args and origin are part of the key
+    assert!(cache.get(&ResponseCache::key(a, o, "Get", b"2")).is_none());
+    assert!(cache
+        .get(&ResponseCache::key(a, "wasmbus://P", "Get", b"1"))
+        .is_none());
+
+    cache.invalidate("wasmbus://A/");
+    assert!(cache.get(&ResponseCache::key(a, o, "Get", b"1")).is_none());
+    assert_eq!(
+        cache.get(&ResponseCache::key(c, o, "Get", b"1")),
+        Some(b"c".to_vec())
+    );
+
+    let expiring = ResponseCache::new(CacheConfig {
+        max_entries: 2,
+        ttl: Duration::from_millis(0),
+    });
+    expiring.insert(ResponseCache::key(a, o, "Get", b"1"), a, b"a".to_vec());
+    assert!(expiring
+        .get(&ResponseCache::key(a, o, "Get", b"1"))
+        .is_none());
+}
@@ -0,0 +1,177 @@
+//! Message dispatch to handlers registered at runtime
+//!
+//! A [Router] is a [MessageDispatch] that looks up the handler for each message
+//! by its full method name ("Service.Method"). Unlike receivers derived with
+//! `#[services(..)]`, the set of methods doesn't have to be known at compile time,
+//! so a gateway-style provider can expose methods loaded from its configuration.
+//!
+//! ```ignore
+//! let router = Router::new()
+//!     .contract_id("wasmcloud:example:gateway")
+//!     .route("Gateway.Lookup", |_ctx: Context, key: String| async move {
+//!         Ok(format!("value of {}", key))
+//!     })
+//!     .route_raw("Gateway.Forward", move |ctx: Context, arg: Vec<u8>| {
+//!         let client = client.clone();
+//!         async move { client.send(&ctx, Message { method: "Upstream.Forward", arg: arg.into() }, None).await }
+//!     });
+//! ```
+//!
+//! Methods are grouped into services by the prefix before the last '.',
+//! and the router answers [INTROSPECT_METHOD](crate::INTROSPECT_METHOD) messages
+//! with a [ServiceDescriptor] for each service.
+//!
+
+use crate::{
+    deserialize, serialize, Context, Message, MessageDispatch, MethodDescriptor, RpcError,
+    RpcResult, ServiceDescriptor, INTROSPECT_METHOD,
+};
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+};
+
+/// Future returned by a [RawHandler]
+pub type HandlerFuture = Pin<Box<dyn Future<Output = RpcResult<Vec<u8>>> + Send>>;
+
+/// A handler of serialized messages, as stored in a [Router].
+/// The handler receives a copy of the message context and the message arg,
+/// and returns the serialized response.
+pub type RawHandler = dyn Fn(Context, Vec<u8>) -> HandlerFuture + Send + Sync;
+
+/// Dispatches messages to handlers registered by method name.
+/// Messages for methods without a handler return `RpcError::MethodNotHandled`.
+#[derive(Clone, Default)]
+pub struct Router {
+    routes: HashMap<String, Arc<RawHandler>>,
+    contract_id: Option<String>,
+}
+
+impl Router {
+    /// Constructs a router with no routes
+    pub fn new() -> Router {
+        Router::default()
+    }
+
+    /// Sets the capability contract id reported in the router's service descriptors
+    #[must_use]
+    pub fn contract_id<T: ToString>(mut self, contract_id: T) -> Router {
+        self.contract_id = Some(contract_id.to_string());
+        self
+    }
+
+    /// Adds a handler for the method ("Service.Method"). The message arg is deserialized
+    /// into the handler's input type, and the handler's output is serialized as the response.
+    /// Replaces any previous handler for the method.
+    #[must_use]
+    pub fn route<I, O, F, Fut>(self, method: &str, handler: F) -> Router
+    where
+        I: DeserializeOwned + Send + 'static,
+        O: Serialize + Send + 'static,
+        F: Fn(Context, I) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = RpcResult<O>> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        self.route_raw(method, move |ctx: Context, arg: Vec<u8>| {
+            let handler = handler.clone();
+            async move {
+                let input = deserialize::<I>(&arg)?;
+                let output = handler(ctx, input).await?;
+                serialize(&output)
+            }
+        })
+    }
+
+    /// Adds a handler for the method ("Service.Method") that receives the serialized
+    /// message arg and returns the serialized response. Use this to forward messages
+    /// without decoding them, or for methods that have no input.
+    /// Replaces any previous handler for the method.
+    #[must_use]
+    pub fn route_raw<F, Fut>(mut self, method: &str, handler: F) -> Router
+    where
+        F: Fn(Context, Vec<u8>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = RpcResult<Vec<u8>>> + Send + 'static,
+    {
+        self.routes.insert(
+            method.to_string(),
+            Arc::new(move |ctx: Context, arg: Vec<u8>| -> HandlerFuture {
+                Box::pin(handler(ctx, arg))
+            }),
+        );
+        self
+    }
+
+    /// Removes the handler for the method. Returns true if there was one.
+    pub fn remove(&mut self, method: &str) -> bool {
+        self.routes.remove(method).is_some()
+    }
+
+    /// Returns true if the router has a handler for the method
+    pub fn has_route(&self, method: &str) -> bool {
+        self.routes.contains_key(method)
+    }
+
+    /// Returns the methods with handlers, in no particular order
+    pub fn methods(&self) -> impl Iterator<Item = &str> {
+        self.routes.keys().map(String::as_str)
+    }
+}
+
+#[async_trait]
+impl MessageDispatch for Router {
+    async fn dispatch(&self, ctx: &Context, message: Message<'_>) -> RpcResult<Message<'_>> {
+        if message.method == INTROSPECT_METHOD {
+            return Ok(Message {
+                method: INTROSPECT_METHOD,
+                arg: Cow::Owned(serialize(&self.services())?),
+            });
+        }
+        let (method, handler) = self
+            .routes
+            .get_key_value(message.method)
+            .ok_or_else(|| RpcError::MethodNotHandled(message.method.to_string()))?;
+        let arg = handler(ctx.clone(), message.arg.into_owned()).await?;
+        Ok(Message {
+            method,
+            arg: Cow::Owned(arg),
+        })
+    }
+
+    /// Returns a descriptor for each service, with its methods in sorted order.
+    /// Methods that are not of the form "Service.Method" are not listed.
+    fn services(&self) -> Vec<ServiceDescriptor> {
+        let mut services = BTreeMap::<&str, Vec<MethodDescriptor>>::new();
+        for (service, method) in self.routes.keys().filter_map(|m| m.rsplit_once('.')) {
+            services.entry(service).or_default().push(MethodDescriptor {
+                name: Cow::Owned(method.to_string()),
+                input: None,
+                output: None,
+            });
+        }
+        services
+            .into_iter()
+            .map(|(service, mut methods)| {
+                methods.sort_by(|a, b| a.name.cmp(&b.name));
+                ServiceDescriptor {
+                    name: Cow::Owned(service.to_string()),
+                    contract_id: self.contract_id.clone().map(Cow::Owned),
+                    methods: Cow::Owned(methods),
+                }
+            })
+            .collect()
+    }
+}
+
+// a Router can be used as a provider: messages from the host other than rpc
+// use the default ProviderHandler implementation
+#[cfg(not(target_arch = "wasm32"))]
+#[async_trait]
+impl crate::provider::ProviderHandler for Router {}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl crate::provider::ProviderDispatch for Router {}
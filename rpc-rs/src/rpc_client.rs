@@ -1,21 +1,34 @@
 #![cfg(not(target_arch = "wasm32"))]
 use crate::{
-    core::{Invocation, InvocationResponse, WasmCloudEntity},
-    Message, RpcError,
+    bus::{MessageBus, NatsBus},
+    circuit_breaker::{is_failure, CircuitBreaker, CircuitBreakerConfig, CircuitState, Transition},
+    core::{InvocationResponse, WasmCloudEntity},
+    response_cache::{CacheConfig, ResponseCache},
+    InvocationBuilder, Message, RpcError, RpcResult, SendOpts,
 };
 #[allow(unused_imports)]
-use log::{debug, error, trace};
+use log::{debug, error, info, trace, warn};
 use ring::digest::{Context, SHA256};
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Value as JsonValue;
 use std::{
     convert::{TryFrom, TryInto},
-    sync::Arc,
-    time::Duration,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 
 pub(crate) const DEFAULT_RPC_TIMEOUT_MILLIS: Duration = Duration::from_millis(2000);
 
+/// nats address used by RpcClientBuilder if no url is provided
+const DEFAULT_NATS_ADDR: &str = "nats://127.0.0.1:4222";
+
+/// lattice prefix used by RpcClientBuilder if no prefix is provided
+const DEFAULT_LATTICE_PREFIX: &str = "default";
+
 /// Send wasmbus rpc messages
 ///
 /// The primary use of RpcClient is providers sending to actors,
@@ -28,12 +41,12 @@ pub(crate) const DEFAULT_RPC_TIMEOUT_MILLIS: Duration = Duration::from_millis(20
 /// the message needs to be signed by a valid cluster key.
 ///
 /// This RpcClient does not subscribe to rpc topics.
-/// To subscribe, use the nats client api directly.
+/// To subscribe, use the message bus directly.
 ///
 #[derive(Clone)]
 pub struct RpcClient {
-    /// sync or async nats client
-    client: NatsClientType,
+    /// message bus (nats, or in-process for tests)
+    bus: Arc<dyn MessageBus>,
     /// lattice rpc prefix
     lattice_prefix: String,
     /// secrets for signing invocations
@@ -42,11 +55,59 @@ pub struct RpcClient {
     host_id: String,
     /// timeout for rpc messages
     timeout: Option<Duration>,
+    /// configured limit on the size of an outgoing invocation, shared by clones. 0 means no limit
+    max_payload: Arc<AtomicUsize>,
+    /// retries for idempotent and read-only messages
+    retry_policy: Option<RetryPolicy>,
+    /// callbacks for monitoring rpc messages
+    hooks: Vec<Arc<dyn RpcHooks>>,
+    /// cache of responses to read-only messages, shared by clones of this client
+    cache: Option<Arc<ResponseCache>>,
+    /// per-target circuit breaker, shared by clones of this client
+    breaker: Option<Arc<CircuitBreaker>>,
 }
 
-#[derive(Clone)]
-pub(crate) enum NatsClientType {
-    Async(crate::anats::Connection),
+/// Policy for retrying idempotent and read-only messages
+/// after a timeout or nats error.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Maximum number of retries after the first attempt
+    pub max_retries: u32,
+    /// Delay before the first retry. The delay doubles for each subsequent retry.
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 2,
+            backoff: Duration::from_millis(100),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the delay before retry number `attempt` (starting at 0)
+    fn delay(&self, attempt: u32) -> Duration {
+        self.backoff * 2u32.saturating_pow(attempt.min(16))
+    }
+}
+
+/// Callbacks invoked by RpcClient for each rpc message, for example,
+/// to collect metrics. All methods have default implementations that do nothing.
+pub trait RpcHooks: Send + Sync {
+    /// Called before an invocation is sent. `payload_len` is the size of the serialized invocation
+    #[allow(unused_variables)]
+    fn before_send(&self, target_url: &str, payload_len: usize) {}
+
+    /// Called after a response (or error) is received, or after a message is posted
+    #[allow(unused_variables)]
+    fn after_send(&self, target_url: &str, elapsed: Duration, error: Option<&RpcError>) {}
+
+    /// Called when the circuit breaker state for a target changes.
+    /// `target_url` is the url of the target entity, without the method.
+    #[allow(unused_variables)]
+    fn circuit_state_changed(&self, target_url: &str, from: CircuitState, to: CircuitState) {}
 }
 
 /// Returns the rpc topic (subject) name for sending to an actor or provider.
@@ -76,23 +137,59 @@ impl RpcClient {
         key: wascap::prelude::KeyPair,
         host_id: String,
         timeout: Option<Duration>,
+    ) -> Self {
+        Self::new_with_bus(
+            Arc::new(NatsBus::new(nats)),
+            lattice_prefix,
+            key,
+            host_id,
+            timeout,
+        )
+    }
+
+    /// Constructs a new RpcClient that sends messages on the message bus.
+    /// parameters: message bus, lattice rpc prefix (usually "default"),
+    /// secret key for signing messages, and host_id
+    pub fn new_with_bus(
+        bus: Arc<dyn MessageBus>,
+        lattice_prefix: &str,
+        key: wascap::prelude::KeyPair,
+        host_id: String,
+        timeout: Option<Duration>,
     ) -> Self {
         RpcClient {
-            client: NatsClientType::Async(nats),
+            bus,
             lattice_prefix: lattice_prefix.to_string(),
             key: Arc::new(key),
             host_id,
             timeout,
+            max_payload: Arc::new(AtomicUsize::new(0)),
+            retry_policy: None,
+            hooks: Vec::new(),
+            cache: None,
+            breaker: None,
         }
     }
 
+    /// Returns a builder for constructing an RpcClient and its nats connection
+    pub fn builder() -> RpcClientBuilder {
+        RpcClientBuilder::default()
+    }
+
     /// convenience method for returning async client
-    /// If the client is not the correct type, returns None
+    /// If the client is not using nats, returns None
     pub fn get_async(&self) -> Option<crate::anats::Connection> {
-        use std::borrow::Borrow;
-        match self.client.borrow() {
-            NatsClientType::Async(nats) => Some(nats.clone()),
-        }
+        self.bus.nats_connection()
+    }
+
+    /// Returns the message bus used by this client
+    pub fn bus(&self) -> Arc<dyn MessageBus> {
+        self.bus.clone()
+    }
+
+    /// Returns the lattice rpc prefix used by this client
+    pub fn lattice_prefix(&self) -> &str {
+        &self.lattice_prefix
     }
 
     /// Replace the default timeout with the specified value.
@@ -101,6 +198,84 @@ impl RpcClient {
         self.timeout = timeout;
     }
 
+    /// Sets a limit on the size of serialized invocations sent by this client.
+    /// The effective limit is the smaller of this value and the max_payload
+    /// of the message bus (for nats, the limit advertised by the server). If the parameter is None, only the
+    /// server limit applies. The limit is shared by clones of this client.
+    pub fn set_max_payload(&self, max_payload: Option<usize>) {
+        self.max_payload
+            .store(max_payload.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    /// Enables caching of responses to messages sent with `SendOpts::read_only(true)`,
+    /// or, if the parameter is None, disables the cache.
+    /// Responses are cached per origin, so a client sending for several actors
+    /// doesn't return one actor's response to another.
+    /// The cache is shared by clones of this client made after this call.
+    pub fn set_response_cache(&mut self, config: Option<CacheConfig>) {
+        self.cache = config.map(|c| Arc::new(ResponseCache::new(c)));
+    }
+
+    /// Removes cached responses from the target. If method is provided,
+    /// only responses to that method are removed.
+    pub fn invalidate_cached(&self, target: &WasmCloudEntity, method: Option<&str>) {
+        if let Some(cache) = self.cache.as_ref() {
+            match method {
+                // the trailing '/' keeps "Get" from matching "GetAll"
+                Some(method) => cache.invalidate(&format!("{}/{}/", target.url(), method)),
+                None => cache.invalidate(&format!("{}/", target.url())),
+            }
+        }
+    }
+
+    /// Removes all cached responses
+    pub fn clear_cache(&self) {
+        if let Some(cache) = self.cache.as_ref() {
+            cache.clear();
+        }
+    }
+
+    /// Enables a circuit breaker for each target, or, if the parameter is None,
+    /// disables circuit breaking. See [CircuitBreakerConfig]
+    pub fn set_circuit_breaker(&mut self, config: Option<CircuitBreakerConfig>) {
+        self.breaker = config.map(|c| Arc::new(CircuitBreaker::new(c)));
+    }
+
+    /// Returns the circuit breaker state for the target.
+    /// If the circuit breaker is not enabled, returns Closed.
+    pub fn circuit_state(&self, target: &WasmCloudEntity) -> CircuitState {
+        match self.breaker.as_ref() {
+            Some(breaker) => breaker.state(&target.url()),
+            None => CircuitState::Closed,
+        }
+    }
+
+    /// logs a circuit state change and notifies hooks
+    fn circuit_changed(&self, target_url: &str, transition: Option<Transition>) {
+        if let Some((from, to)) = transition {
+            match to {
+                CircuitState::Open => warn!("circuit to {} is open (was {})", target_url, from),
+                _ => info!("circuit to {} is {} (was {})", target_url, to, from),
+            }
+            for hook in self.hooks.iter() {
+                hook.circuit_state_changed(target_url, from, to);
+            }
+        }
+    }
+
+    /// Returns the maximum size of a serialized invocation that may be sent,
+    /// or None if there is no limit.
+    pub async fn max_payload(&self) -> Option<usize> {
+        let configured = match self.max_payload.load(Ordering::Relaxed) {
+            0 => None,
+            limit => Some(limit),
+        };
+        match (configured, self.bus.max_payload().await) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
     /// Send an rpc message using json-encoded data
     pub async fn send_json<Target, Arg, Resp>(
         &self,
@@ -135,7 +310,7 @@ impl RpcClient {
     where
         Target: Into<WasmCloudEntity>,
     {
-        self.inner_rpc(origin, target, message, true, self.timeout)
+        self.inner_rpc(origin, target, message, true, self.timeout, None)
             .await
     }
 
@@ -155,10 +330,36 @@ impl RpcClient {
     where
         Target: Into<WasmCloudEntity>,
     {
-        self.inner_rpc(origin, target, message, true, Some(timeout))
+        self.inner_rpc(origin, target, message, true, Some(timeout), None)
             .await
     }
 
+    /// Send a wasmbus rpc message with send options.
+    /// If the message is idempotent or read-only, and the client has a retry policy,
+    /// the message may be retried after a timeout or nats error.
+    /// If timeout is None, the client's default timeout is used.
+    pub async fn send_with_opts<Target>(
+        &self,
+        origin: WasmCloudEntity,
+        target: Target,
+        message: Message<'_>,
+        opts: &SendOpts,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<u8>, RpcError>
+    where
+        Target: Into<WasmCloudEntity>,
+    {
+        self.inner_rpc(
+            origin,
+            target,
+            message,
+            true,
+            timeout.or(self.timeout),
+            Some(opts),
+        )
+        .await
+    }
+
     /// Send a wasmbus rpc message without waiting for response.
     /// This has somewhat limited utility and is only useful if
     /// the message is declared to return no args, or if the caller
@@ -175,7 +376,9 @@ impl RpcClient {
     where
         Target: Into<WasmCloudEntity>,
     {
-        let _ = self.inner_rpc(origin, target, message, false, None).await?;
+        let _ = self
+            .inner_rpc(origin, target, message, false, None, None)
+            .await?;
         Ok(())
     }
 
@@ -187,80 +390,155 @@ impl RpcClient {
         message: Message<'_>,
         expect_response: bool,
         timeout: Option<Duration>,
+        opts: Option<&SendOpts>,
     ) -> Result<Vec<u8>, RpcError>
     where
         Target: Into<WasmCloudEntity>,
     {
         let target = target.into();
-        let origin_url = origin.url();
-        let subject = make_uuid();
-        let issuer = &self.key.public_key();
         let target_url = format!("{}/{}", target.url(), &message.method);
         debug!("rpc_client sending to {}", &target_url);
-        let claims = wascap::prelude::Claims::<wascap::prelude::Invocation>::new(
-            issuer.clone(),
-            subject.clone(),
-            &target_url,
-            &origin_url,
-            &invocation_hash(&target_url, &origin_url, message.method, &message.arg),
-        );
-
-        let topic = rpc_topic(&target, &self.lattice_prefix);
-        let method = message.method.to_string();
-        let invocation = Invocation {
-            origin,
-            target,
-            operation: method.clone(),
-            msg: message.arg.into_owned(),
-            id: subject,
-            encoded_claims: claims.encode(&self.key).unwrap(),
-            host_id: self.host_id.clone(),
+        let cache_key = match (self.cache.as_ref(), opts) {
+            (Some(cache), Some(opts)) if opts.read_only && expect_response => {
+                let key =
+                    ResponseCache::key(&target_url, &origin.url(), message.method, &message.arg);
+                if let Some(response) = cache.get(&key) {
+                    trace!("rpc cached response from {}", &target_url);
+                    return Ok(response);
+                }
+                Some(key)
+            }
+            _ => None,
         };
+        let entity_url = target.url();
+        if let Some(breaker) = self.breaker.as_ref() {
+            match breaker.allow(&entity_url) {
+                Ok(transition) => self.circuit_changed(&entity_url, transition),
+                Err(e) => {
+                    debug!("not sending to {}: {}", &target_url, &e);
+                    return Err(e);
+                }
+            }
+        }
+        let topic = rpc_topic(&target, &self.lattice_prefix);
+        let invocation = InvocationBuilder::new(self.key.clone(), &self.host_id)
+            .origin(origin)
+            .target(target)
+            .message(message)
+            .build()?;
         trace!("rpc send {}", &target_url);
 
         let nats_body = crate::serialize(&invocation)?;
-        if expect_response {
-            let payload = if let Some(timeout) = timeout {
-                match tokio::time::timeout(timeout, self.request(&topic, &nats_body)).await {
+        if let Some(allowed) = self.max_payload().await {
+            if nats_body.len() > allowed {
+                error!(
+                    "rpc message to {} is too large: {} bytes (limit {})",
+                    &target_url,
+                    nats_body.len(),
+                    allowed
+                );
+                return Err(RpcError::PayloadTooLarge {
+                    actual: nats_body.len(),
+                    allowed,
+                });
+            }
+        }
+        for hook in self.hooks.iter() {
+            hook.before_send(&target_url, nats_body.len());
+        }
+        let start = Instant::now();
+        let result = if expect_response {
+            self.request_invocation(&topic, &nats_body, &target_url, timeout, opts)
+                .await
+        } else {
+            self.publish(&topic, &nats_body)
+                .await
+                .map(|_| Vec::new())
+                .map_err(|e| RpcError::Nats(format!("rpc send error: {}: {}", target_url, e)))
+        };
+        for hook in self.hooks.iter() {
+            hook.after_send(&target_url, start.elapsed(), result.as_ref().err());
+        }
+        if let Some(breaker) = self.breaker.as_ref() {
+            let success = !matches!(&result, Err(e) if is_failure(e));
+            self.circuit_changed(&entity_url, breaker.record(&entity_url, success));
+        }
+        if let (Some(cache), Some(key), Ok(response)) =
+            (self.cache.as_ref(), cache_key, result.as_ref())
+        {
+            cache.insert(key, &format!("{}/", &target_url), response.clone());
+        }
+        result
+    }
+
+    /// send the serialized invocation and wait for the response,
+    /// retrying if the retry policy allows
+    async fn request_invocation(
+        &self,
+        topic: &str,
+        nats_body: &[u8],
+        target_url: &str,
+        timeout: Option<Duration>,
+        opts: Option<&SendOpts>,
+    ) -> Result<Vec<u8>, RpcError> {
+        // only idempotent and read-only messages may be retried
+        let retry = match (&self.retry_policy, opts) {
+            (Some(policy), Some(opts)) if opts.idempotent || opts.read_only => Some(policy),
+            _ => None,
+        };
+        let mut attempt = 0u32;
+        let payload = loop {
+            let result = if let Some(timeout) = timeout {
+                match tokio::time::timeout(timeout, self.request(topic, nats_body)).await {
                     Ok(Ok(result)) => Ok(result),
                     Ok(Err(rpc_err)) => Err(RpcError::Nats(format!(
                         "rpc send error: {}: {}",
                         target_url, rpc_err
                     ))),
                     Err(timeout_err) => {
-                        error!("rpc timeout: sending to {}: {}", &target_url, timeout_err);
+                        error!("rpc timeout: sending to {}: {}", target_url, timeout_err);
                         Err(RpcError::Timeout(format!(
                             "sending to {}: {}",
-                            &target_url, timeout_err
+                            target_url, timeout_err
                         )))
                     }
                 }
             } else {
                 // no timeout, wait indefinitely or until host times out
-                self.request(&topic, &nats_body)
+                self.request(topic, nats_body)
                     .await
                     .map_err(|e| RpcError::Nats(format!("rpc send error: {}: {}", target_url, e)))
-            }?;
-
-            let inv_response = crate::deserialize::<InvocationResponse>(&payload).map_err(|e| {
-                RpcError::Deser(format!("response to {}: {}", &method, &e.to_string()))
-            })?;
-            match inv_response.error {
-                None => {
-                    trace!("rpc ok response from {}", &target_url);
-                    Ok(inv_response.msg)
-                }
-                Some(err) => {
-                    // if error is Some(_), we must ignore the msg field
-                    error!("rpc error response from {}: {}", &target_url, &err);
-                    Err(RpcError::Rpc(err))
+            };
+            match (result, retry) {
+                (Ok(payload), _) => break payload,
+                (Err(RpcError::Timeout(_) | RpcError::Nats(_)), Some(policy))
+                    if attempt < policy.max_retries =>
+                {
+                    let delay = policy.delay(attempt);
+                    attempt += 1;
+                    debug!(
+                        "retrying rpc to {} in {:?} (attempt {})",
+                        target_url, &delay, attempt
+                    );
+                    tokio::time::sleep(delay).await;
                 }
+                (Err(e), _) => return Err(e),
+            }
+        };
+
+        let inv_response = crate::deserialize::<InvocationResponse>(&payload).map_err(|e| {
+            RpcError::Deser(format!("response to {}: {}", target_url, &e.to_string()))
+        })?;
+        match inv_response.error {
+            None => {
+                trace!("rpc ok response from {}", target_url);
+                Ok(inv_response.msg)
+            }
+            Some(err) => {
+                // if error is Some(_), we must ignore the msg field
+                error!("rpc error response from {}: {}", target_url, &err);
+                Err(response_error(err))
             }
-        } else {
-            self.publish(&topic, &nats_body)
-                .await
-                .map_err(|e| RpcError::Nats(format!("rpc send error: {}: {}", target_url, e)))?;
-            Ok(Vec::new())
         }
     }
 
@@ -269,37 +547,408 @@ impl RpcClient {
     /// If this client has a default timeout, and a response is not received within
     /// the appropriate time, an error will be returned.
     pub async fn request(&self, subject: &str, data: &[u8]) -> Result<Vec<u8>, RpcError> {
-        use std::borrow::Borrow as _;
-
-        let bytes = match self.client.borrow() {
-            NatsClientType::Async(ref nats) => {
-                let resp = if let Some(timeout) = self.timeout {
-                    nats.request_timeout(subject, data, timeout)
-                        .await
-                        .map_err(|e| RpcError::Nats(e.to_string()))?
-                } else {
-                    nats.request(subject, data)
-                        .await
-                        .map_err(|e| RpcError::Nats(e.to_string()))?
-                };
-                resp.data
-            }
-        };
-        Ok(bytes)
+        self.bus.request(subject, data, self.timeout).await
     }
 
     /// Send a nats message with no reply-to. Do not wait for a response.
     /// This can be used for general nats messages, not just wasmbus actor/provider messages.
     pub async fn publish(&self, subject: &str, data: &[u8]) -> Result<(), RpcError> {
-        use std::borrow::Borrow as _;
+        self.bus.publish(subject, data).await
+    }
+}
 
-        match self.client.borrow() {
-            NatsClientType::Async(nats) => nats
-                .publish(subject, data)
-                .await
-                .map_err(|e| RpcError::Nats(e.to_string()))?,
+/// Converts the error in an InvocationResponse to an RpcError.
+/// A receiver that rejects a message as too large responds with the text of
+/// RpcError::PayloadTooLarge, which is returned as that variant.
+fn response_error(err: String) -> RpcError {
+    let sizes = err
+        .strip_prefix("payload too large: ")
+        .and_then(|s| s.strip_suffix(" bytes"))
+        .and_then(|s| s.split_once(" bytes exceeds limit of "))
+        .and_then(|(actual, allowed)| Some((actual.parse().ok()?, allowed.parse().ok()?)));
+    match sizes {
+        Some((actual, allowed)) => RpcError::PayloadTooLarge { actual, allowed },
+        None => RpcError::Rpc(err),
+    }
+}
+
+/// Nats authentication options for RpcClientBuilder
+#[derive(Clone)]
+enum NatsAuth {
+    None,
+    CredsFile(PathBuf),
+    Jwt { jwt: String, seed: String },
+    UserPassword { user: String, password: String },
+    Token(String),
+}
+
+/// Builder for RpcClient
+///
+/// The builder creates the nats connection (unless an existing connection
+/// is provided with `connection`, or a message bus with `bus`),
+/// and the key used for signing invocations.
+///
+/// ```ignore
+/// let client = RpcClient::builder()
+///     .nats_url("nats://127.0.0.1:4222")
+///     .lattice_prefix("default")
+///     .timeout(Some(Duration::from_secs(5)))
+///     .build()
+///     .await?;
+/// ```
+pub struct RpcClientBuilder {
+    nats_urls: Vec<String>,
+    connection: Option<crate::anats::Connection>,
+    bus: Option<Arc<dyn MessageBus>>,
+    auth: NatsAuth,
+    tls_required: bool,
+    tls_root_certificates: Vec<PathBuf>,
+    tls_client_cert: Option<(PathBuf, PathBuf)>,
+    max_reconnects: Option<usize>,
+    reconnect_buffer_size: Option<usize>,
+    connection_name: Option<String>,
+    key: Option<wascap::prelude::KeyPair>,
+    seed: Option<String>,
+    host_id: Option<String>,
+    lattice_prefix: String,
+    timeout: Option<Duration>,
+    max_payload: Option<usize>,
+    retry_policy: Option<RetryPolicy>,
+    hooks: Vec<Arc<dyn RpcHooks>>,
+    cache: Option<CacheConfig>,
+    circuit_breaker: Option<CircuitBreakerConfig>,
+}
+
+impl Default for RpcClientBuilder {
+    fn default() -> Self {
+        RpcClientBuilder {
+            nats_urls: Vec::new(),
+            connection: None,
+            bus: None,
+            auth: NatsAuth::None,
+            tls_required: false,
+            tls_root_certificates: Vec::new(),
+            tls_client_cert: None,
+            max_reconnects: None,
+            reconnect_buffer_size: None,
+            connection_name: None,
+            key: None,
+            seed: None,
+            host_id: None,
+            lattice_prefix: DEFAULT_LATTICE_PREFIX.to_string(),
+            timeout: Some(DEFAULT_RPC_TIMEOUT_MILLIS),
+            max_payload: None,
+            retry_policy: None,
+            hooks: Vec::new(),
+            cache: None,
+            circuit_breaker: None,
         }
-        Ok(())
+    }
+}
+
+impl RpcClientBuilder {
+    /// Adds a nats server url. May be called more than once to add several servers.
+    /// If no urls are provided, the default is "nats://127.0.0.1:4222"
+    #[must_use]
+    pub fn nats_url<T: ToString>(mut self, url: T) -> Self {
+        self.nats_urls.push(url.to_string());
+        self
+    }
+
+    /// Uses an existing nats connection. Connection options
+    /// (urls, credentials, tls, and reconnect policy) are ignored.
+    #[must_use]
+    pub fn connection(mut self, nc: crate::anats::Connection) -> Self {
+        self.connection = Some(nc);
+        self
+    }
+
+    /// Uses a message bus instead of a nats connection, for example, an
+    /// [InProcessBus](crate::bus::InProcessBus) for tests.
+    /// Connection options are ignored.
+    #[must_use]
+    pub fn bus(mut self, bus: Arc<dyn MessageBus>) -> Self {
+        self.bus = Some(bus);
+        self
+    }
+
+    /// Authenticates to nats with a credentials (.creds) file
+    #[must_use]
+    pub fn credentials_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.auth = NatsAuth::CredsFile(path.into());
+        self
+    }
+
+    /// Authenticates to nats with a user jwt and the seed used to sign the server nonce
+    #[must_use]
+    pub fn user_jwt<T1: ToString, T2: ToString>(mut self, jwt: T1, seed: T2) -> Self {
+        self.auth = NatsAuth::Jwt {
+            jwt: jwt.to_string(),
+            seed: seed.to_string(),
+        };
+        self
+    }
+
+    /// Authenticates to nats with user name and password
+    #[must_use]
+    pub fn user_password<T1: ToString, T2: ToString>(mut self, user: T1, password: T2) -> Self {
+        self.auth = NatsAuth::UserPassword {
+            user: user.to_string(),
+            password: password.to_string(),
+        };
+        self
+    }
+
+    /// Authenticates to nats with a token
+    #[must_use]
+    pub fn token<T: ToString>(mut self, token: T) -> Self {
+        self.auth = NatsAuth::Token(token.to_string());
+        self
+    }
+
+    /// Requires a tls connection to the nats server
+    #[must_use]
+    pub fn tls_required(mut self, required: bool) -> Self {
+        self.tls_required = required;
+        self
+    }
+
+    /// Adds a root certificate (PEM file) for verifying the nats server
+    #[must_use]
+    pub fn tls_root_certificate<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.tls_root_certificates.push(path.into());
+        self
+    }
+
+    /// Sets the client certificate and key (PEM files) for tls client authentication
+    #[must_use]
+    pub fn tls_client_cert<P1: Into<PathBuf>, P2: Into<PathBuf>>(
+        mut self,
+        cert: P1,
+        key: P2,
+    ) -> Self {
+        self.tls_client_cert = Some((cert.into(), key.into()));
+        self
+    }
+
+    /// Sets the maximum number of reconnect attempts. None (the default) retries forever.
+    #[must_use]
+    pub fn max_reconnects(mut self, max_reconnects: Option<usize>) -> Self {
+        self.max_reconnects = max_reconnects;
+        self
+    }
+
+    /// Sets the size of the buffer used for messages published while reconnecting
+    #[must_use]
+    pub fn reconnect_buffer_size(mut self, size: usize) -> Self {
+        self.reconnect_buffer_size = Some(size);
+        self
+    }
+
+    /// Sets the connection name reported to the nats server
+    #[must_use]
+    pub fn connection_name<T: ToString>(mut self, name: T) -> Self {
+        self.connection_name = Some(name.to_string());
+        self
+    }
+
+    /// Sets the key for signing invocations. Either a key or a seed may be set, but not both.
+    #[must_use]
+    pub fn signing_key(mut self, key: wascap::prelude::KeyPair) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    /// Sets the seed of the key for signing invocations.
+    /// If neither a key nor a seed is provided, a new cluster key is generated.
+    #[must_use]
+    pub fn signing_seed<T: ToString>(mut self, seed: T) -> Self {
+        self.seed = Some(seed.to_string());
+        self
+    }
+
+    /// Returns true if a signing key or seed has been set
+    pub(crate) fn has_signing_key(&self) -> bool {
+        self.key.is_some() || self.seed.is_some()
+    }
+
+    /// Sets the host id included in invocations.
+    /// If not provided, the public key of a generated server key is used.
+    #[must_use]
+    pub fn host_id<T: ToString>(mut self, host_id: T) -> Self {
+        self.host_id = Some(host_id.to_string());
+        self
+    }
+
+    /// Sets the lattice rpc prefix. The default is "default"
+    #[must_use]
+    pub fn lattice_prefix<T: ToString>(mut self, prefix: T) -> Self {
+        self.lattice_prefix = prefix.to_string();
+        self
+    }
+
+    /// Sets the default timeout for rpc messages. None waits indefinitely.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets a limit on the size of outgoing invocations. See [RpcClient::set_max_payload]
+    #[must_use]
+    pub fn max_payload(mut self, max_payload: Option<usize>) -> Self {
+        self.max_payload = max_payload;
+        self
+    }
+
+    /// Sets the retry policy for idempotent and read-only messages
+    #[must_use]
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Adds callbacks that are invoked for each rpc message
+    #[must_use]
+    pub fn hook(mut self, hook: Arc<dyn RpcHooks>) -> Self {
+        self.hooks.push(hook);
+        self
+    }
+
+    /// Enables caching of responses to read-only messages.
+    /// See [RpcClient::set_response_cache]
+    #[must_use]
+    pub fn response_cache(mut self, config: CacheConfig) -> Self {
+        self.cache = Some(config);
+        self
+    }
+
+    /// Enables a per-target circuit breaker. See [RpcClient::set_circuit_breaker]
+    #[must_use]
+    pub fn circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.circuit_breaker = Some(config);
+        self
+    }
+
+    /// Connects to nats (if needed) and returns the RpcClient
+    pub async fn build(self) -> RpcResult<RpcClient> {
+        let key = match (self.key, self.seed.as_ref()) {
+            (Some(_), Some(_)) => {
+                return Err(RpcError::InvalidParameter(
+                    "signing key and signing seed may not both be set".to_string(),
+                ))
+            }
+            (Some(key), None) => key,
+            (None, Some(seed)) => wascap::prelude::KeyPair::from_seed(seed)
+                .map_err(|e| RpcError::InvalidParameter(format!("invalid signing seed: {}", e)))?,
+            (None, None) => wascap::prelude::KeyPair::new_cluster(),
+        };
+        let host_id = self
+            .host_id
+            .unwrap_or_else(|| wascap::prelude::KeyPair::new_server().public_key());
+        let bus: Arc<dyn MessageBus> = match (self.bus, self.connection) {
+            (Some(bus), _) => bus,
+            (None, Some(nc)) => Arc::new(NatsBus::new(nc)),
+            (None, None) => Arc::new(NatsBus::new(
+                Self::connect(
+                    &self.nats_urls,
+                    self.auth,
+                    self.tls_required,
+                    &self.tls_root_certificates,
+                    self.tls_client_cert,
+                    self.max_reconnects,
+                    self.reconnect_buffer_size,
+                    self.connection_name,
+                )
+                .await?,
+            )),
+        };
+        let mut client =
+            RpcClient::new_with_bus(bus, &self.lattice_prefix, key, host_id, self.timeout);
+        client.set_max_payload(self.max_payload);
+        client.retry_policy = self.retry_policy;
+        client.hooks = self.hooks;
+        client.set_response_cache(self.cache);
+        client.set_circuit_breaker(self.circuit_breaker);
+        Ok(client)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn connect(
+        urls: &[String],
+        auth: NatsAuth,
+        tls_required: bool,
+        root_certificates: &[PathBuf],
+        client_cert: Option<(PathBuf, PathBuf)>,
+        max_reconnects: Option<usize>,
+        reconnect_buffer_size: Option<usize>,
+        connection_name: Option<String>,
+    ) -> RpcResult<crate::anats::Connection> {
+        use crate::anats::Options;
+
+        let mut opts = match auth {
+            NatsAuth::None => Options::new(),
+            NatsAuth::CredsFile(path) => Options::with_credentials(path),
+            NatsAuth::Jwt { jwt, seed } => {
+                wascap::prelude::KeyPair::from_seed(&seed).map_err(|e| {
+                    RpcError::InvalidParameter(format!("invalid nats user seed: {}", e))
+                })?;
+                // with credentials in the .creds format, the nats client signs the
+                // server nonce and returns any signing error from connect
+                let creds = format!(
+                    "-----BEGIN NATS USER JWT-----\n{}\n------END NATS USER JWT------\n\n\
+                     -----BEGIN USER NKEY SEED-----\n{}\n------END USER NKEY SEED------\n",
+                    jwt, seed
+                );
+                Options::with_static_credentials(&creds).map_err(|e| {
+                    RpcError::InvalidParameter(format!("invalid nats user credentials: {}", e))
+                })?
+            }
+            NatsAuth::UserPassword { user, password } => Options::with_user_pass(&user, &password),
+            NatsAuth::Token(token) => Options::with_token(&token),
+        };
+        opts = opts.max_reconnects(max_reconnects);
+        if let Some(size) = reconnect_buffer_size {
+            opts = opts.reconnect_buffer_size(size);
+        }
+        if let Some(name) = connection_name {
+            opts = opts.with_name(&name);
+        }
+        if tls_required {
+            opts = opts.tls_required(true);
+        }
+        for cert in root_certificates.iter() {
+            opts = opts.add_root_certificate(cert);
+        }
+        if let Some((cert, key)) = client_cert {
+            opts = opts.client_cert(cert, key);
+        }
+
+        let servers = Self::server_addresses(urls)?;
+        let addrs = servers
+            .iter()
+            .map(|s| format!("{}:{}", s.host(), s.port()))
+            .collect::<Vec<_>>()
+            .join(",");
+        opts.connect(servers)
+            .await
+            .map_err(|e| RpcError::Nats(format!("nats connection to {} failed: {}", addrs, e)))
+    }
+
+    /// Parses the nats server urls. If there are none, returns the default server address.
+    fn server_addresses(urls: &[String]) -> RpcResult<Vec<crate::anats::ServerAddress>> {
+        use crate::anats::ServerAddress;
+        use std::str::FromStr as _;
+
+        let default = [DEFAULT_NATS_ADDR.to_string()];
+        let urls = if urls.is_empty() { &default[..] } else { urls };
+        urls.iter()
+            .map(|url| {
+                ServerAddress::from_str(url).map_err(|e| {
+                    RpcError::InvalidParameter(format!("Invalid nats server url '{}': {}", url, e))
+                })
+            })
+            .collect()
     }
 }
 
@@ -370,3 +1019,247 @@ where
     serde_json::to_value(crate::deserialize::<T>(msg)?)
         .map_err(|e| RpcError::Ser(format!("response serialization : {}.", e)))
 }
+
+#[test]
+fn retry_delay() {
+    let policy = RetryPolicy {
+        max_retries: 20,
+        backoff: Duration::from_millis(10),
+    };
+    assert_eq!(policy.delay(0), Duration::from_millis(10));
+    assert_eq!(policy.delay(1), Duration::from_millis(20));
+    assert_eq!(policy.delay(3), Duration::from_millis(80));
+    // the doubling stops after 16 retries
+    assert_eq!(policy.delay(16), policy.delay(19));
+}
+
+#[tokio::test]
+async fn builder_validation() {
+    let invalid = |r: RpcResult<RpcClient>| match r {
+        Err(RpcError::InvalidParameter(msg)) => msg,
+        Err(e) => panic!("expected InvalidParameter, got {}", e),
+        Ok(_) => panic!("expected InvalidParameter"),
+    };
+    let bus: Arc<dyn MessageBus> = Arc::new(crate::bus::InProcessBus::default());
+    let seed = wascap::prelude::KeyPair::new_cluster().seed().unwrap();
+
+    let msg = invalid(
+        RpcClient::builder()
+            .bus(bus.clone())
+            .signing_key(wascap::prelude::KeyPair::new_cluster())
+            .signing_seed(&seed)
+            .build()
+            .await,
+    );
+    assert!(msg.contains("both"), "{}", msg);
+    let msg = invalid(
+        RpcClient::builder()
+            .bus(bus.clone())
+            .signing_seed("SCNOTASEED")
+            .build()
+            .await,
+    );
+    assert!(msg.contains("signing seed"), "{}", msg);
+    let client = RpcClient::builder()
+        .bus(bus)
+        .signing_seed(&seed)
+        .build()
+        .await
+        .unwrap();
+    assert_eq!(
+        client.key.public_key(),
+        wascap::prelude::KeyPair::from_seed(&seed)
+            .unwrap()
+            .public_key()
+    );
+
+    // urls and credentials are checked before connecting
+    let msg = invalid(
+        RpcClient::builder()
+            .nats_url("http://127.0.0.1:4222")
+            .build()
+            .await,
+    );
+    assert!(msg.contains("server url"), "{}", msg);
+    let msg = invalid(
+        RpcClient::builder()
+            .user_jwt("eyJ0eXAiOiJqd3QifQ.e30.c2ln", "SUNOTASEED")
+            .build()
+            .await,
+    );
+    assert!(msg.contains("user seed"), "{}", msg);
+    let user_seed = wascap::prelude::KeyPair::new_user().seed().unwrap();
+    let msg = invalid(
+        RpcClient::builder()
+            .user_jwt("eyJ0eXAiOiJqd3QifQ.e30.c2ln", user_seed)
+            .nats_url("http://127.0.0.1:4222")
+            .build()
+            .await,
+    );
+    assert!(msg.contains("server url"), "{}", msg);
+
+    // without urls, the default server is used
+    let servers = RpcClientBuilder::server_addresses(&[]).unwrap();
+    assert_eq!(servers.len(), 1);
+    assert_eq!((servers[0].host(), servers[0].port()), ("127.0.0.1", 4222));
+    let servers = RpcClientBuilder::server_addresses(&[
+        "nats://10.0.0.1:4222".to_string(),
+        "10.0.0.2:5222".to_string(),
+    ])
+    .unwrap();
+    assert_eq!((servers[1].host(), servers[1].port()), ("10.0.0.2", 5222));
+}
+
+/// message bus that fails the first `failures` requests
+#[cfg(test)]
+#[derive(Default)]
+struct FlakyBus {
+    failures: AtomicUsize,
+    requests: AtomicUsize,
+}
+
+#[cfg(test)]
+#[async_trait::async_trait]
+impl MessageBus for FlakyBus {
+    async fn publish(&self, _subject: &str, _data: &[u8]) -> RpcResult<()> {
+        Ok(())
+    }
+
+    async fn request(
+        &self,
+        _subject: &str,
+        _data: &[u8],
+        _timeout: Option<Duration>,
+    ) -> RpcResult<Vec<u8>> {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        if self
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok()
+        {
+            return Err(RpcError::Nats("no responders".to_string()));
+        }
+        crate::serialize(&InvocationResponse {
+            msg: b"ok".to_vec(),
+            ..Default::default()
+        })
+    }
+
+    async fn subscribe(&self, _subject: &str) -> RpcResult<Arc<dyn crate::bus::BusSubscription>> {
+        Err(RpcError::NotImplemented)
+    }
+
+    async fn queue_subscribe(
+        &self,
+        _subject: &str,
+        _queue: &str,
+    ) -> RpcResult<Arc<dyn crate::bus::BusSubscription>> {
+        Err(RpcError::NotImplemented)
+    }
+}
+
+#[tokio::test]
+async fn retry_idempotent_messages() {
+    let policy = RetryPolicy {
+        max_retries: 2,
+        backoff: Duration::from_millis(1),
+    };
+    // sends a message to a bus that fails `failures` times,
+    // and returns the result and the number of requests
+    let send = |failures: usize, opts: Option<SendOpts>, policy: Option<RetryPolicy>| async move {
+        let bus = Arc::new(FlakyBus::default());
+        bus.failures.store(failures, Ordering::SeqCst);
+        let mut builder = RpcClient::builder().bus(bus.clone());
+        if let Some(policy) = policy {
+            builder = builder.retry_policy(policy);
+        }
+        let client = builder.build().await.unwrap();
+        let target = WasmCloudEntity {
+            public_key: wascap::prelude::KeyPair::new_module().public_key(),
+            ..Default::default()
+        };
+        let message = Message {
+            method: "Echo.Echo",
+            arg: std::borrow::Cow::Borrowed(b"hello"),
+        };
+        let result = match opts {
+            Some(opts) => {
+                client
+                    .send_with_opts(WasmCloudEntity::default(), target, message, &opts, None)
+                    .await
+            }
+            None => {
+                client
+                    .send(WasmCloudEntity::default(), target, message)
+                    .await
+            }
+        };
+        (result, bus.requests.load(Ordering::SeqCst))
+    };
+    let idempotent = || Some(SendOpts::default().idempotent(true));
+
+    // succeeds on the last retry
+    let (result, requests) = send(2, idempotent(), Some(policy.clone())).await;
+    assert_eq!(result.unwrap(), b"ok");
+    assert_eq!(requests, 3);
+
+    // retries are exhausted
+    let (result, requests) = send(3, idempotent(), Some(policy.clone())).await;
+    assert!(matches!(result, Err(RpcError::Nats(_))));
+    assert_eq!(requests, 3);
+
+    let read_only = Some(SendOpts::default().read_only(true));
+    let (result, requests) = send(1, read_only, Some(policy.clone())).await;
+    assert!(result.is_ok());
+    assert_eq!(requests, 2);
+
+    // other messages aren't retried
+    let (result, requests) = send(1, Some(SendOpts::default()), Some(policy.clone())).await;
+    assert!(result.is_err());
+    assert_eq!(requests, 1);
+    let (result, requests) = send(1, None, Some(policy)).await;
+    assert!(result.is_err());
+    assert_eq!(requests, 1);
+
+    // no retries without a policy
+    let (result, requests) = send(1, idempotent(), None).await;
+    assert!(result.is_err());
+    assert_eq!(requests, 1);
+}
+
+#[tokio::test]
+async fn max_payload_shared_by_clones() {
+    let bus = Arc::new(crate::bus::InProcessBus::with_max_payload(4096));
+    let client = RpcClient::builder()
+        .bus(bus)
+        .max_payload(Some(2048))
+        .build()
+        .await
+        .unwrap();
+    assert_eq!(client.max_payload().await, Some(2048));
+
+    let clone = client.clone();
+    clone.set_max_payload(Some(16));
+    assert_eq!(client.max_payload().await, Some(16));
+    let target = WasmCloudEntity {
+        public_key: wascap::prelude::KeyPair::new_module().public_key(),
+        ..Default::default()
+    };
+    let message = Message {
+        method: "Echo.Echo",
+        arg: std::borrow::Cow::Borrowed(b"hello"),
+    };
+    let err = client
+        .send(WasmCloudEntity::default(), target, message)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, RpcError::PayloadTooLarge { allowed: 16, .. }),
+        "{}",
+        err
+    );
+
+    // without a configured limit, the bus limit applies
+    client.set_max_payload(None);
+    assert_eq!(clone.max_payload().await, Some(4096));
+}
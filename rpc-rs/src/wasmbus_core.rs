@@ -167,6 +167,20 @@ pub trait ActorReceiver: MessageDispatch + Actor {
             ))),
         }
     }
+
+    /// Describes the Actor service: its contract id and methods
+    fn service_descriptor(&self) -> &'static crate::ServiceDescriptor {
+        static DESCRIPTOR: crate::ServiceDescriptor = crate::ServiceDescriptor {
+            name: Cow::Borrowed("Actor"),
+            contract_id: None,
+            methods: Cow::Borrowed(&[crate::MethodDescriptor {
+                name: Cow::Borrowed("HealthRequest"),
+                input: Some(Cow::Borrowed("org.wasmcloud.core#HealthCheckRequest")),
+                output: Some(Cow::Borrowed("org.wasmcloud.core#HealthCheckResponse")),
+            }]),
+        };
+        &DESCRIPTOR
+    }
 }
 
 /// ActorSender sends messages to a Actor service
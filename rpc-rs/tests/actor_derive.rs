@@ -0,0 +1,208 @@
+//! call an actor derived with #[derive(Actor)] through its exports, with the test acting as host
+//!
+//! Actors only run in wasm32, but the derived exports also build natively.
+//! The test defines the `wasmbus` imports that a host provides, and calls `__guest_call`
+//! as the host does. Each test runs in its own thread, which has its own actor instance.
+#![cfg(test)]
+
+mod support;
+
+use host::{guest_call, ACTORS_MADE};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use support::echo::{Echo, EchoReceiver};
+use wascap::prelude::KeyPair;
+use wasmbus_rpc::{
+    actor::prelude::*,
+    core::WasmCloudEntity,
+    deserialize,
+    middleware::{AuthorizeLayer, MiddlewareStack},
+    serialize, HostCallOpts, HostContext,
+};
+
+thread_local! {
+    /// the Context of the last message the actor received
+    static LAST_CONTEXT: RefCell<Option<Context>> = const { RefCell::new(None) };
+}
+
+/// the imports a host provides to the actor
+mod host {
+    use std::cell::{Cell, RefCell};
+
+    thread_local! {
+        /// method and arg of the next message to the actor
+        static REQUEST: RefCell<(Vec<u8>, Vec<u8>)> = const { RefCell::new((Vec::new(), Vec::new())) };
+        /// the actor's response, or error message
+        static RESPONSE: RefCell<Option<Result<Vec<u8>, String>>> = const { RefCell::new(None) };
+        /// number of actors constructed in this thread
+        pub static ACTORS_MADE: Cell<usize> = const { Cell::new(0) };
+    }
+
+    #[no_mangle]
+    pub extern "C" fn __guest_request(op_ptr: *const u8, ptr: *const u8) {
+        REQUEST.with(|req| {
+            let (op, arg) = &*req.borrow();
+            unsafe {
+                std::ptr::copy_nonoverlapping(op.as_ptr(), op_ptr as *mut u8, op.len());
+                std::ptr::copy_nonoverlapping(arg.as_ptr(), ptr as *mut u8, arg.len());
+            }
+        })
+    }
+
+    #[no_mangle]
+    pub extern "C" fn __guest_response(ptr: *const u8, len: usize) {
+        let resp = unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec();
+        RESPONSE.with(|r| *r.borrow_mut() = Some(Ok(resp)));
+    }
+
+    #[no_mangle]
+    pub extern "C" fn __guest_error(ptr: *const u8, len: usize) {
+        let msg = unsafe { std::slice::from_raw_parts(ptr, len) };
+        let msg = String::from_utf8_lossy(msg).to_string();
+        RESPONSE.with(|r| *r.borrow_mut() = Some(Err(msg)));
+    }
+
+    /// sends a message to the actor, as the host does, and returns its response or error
+    pub fn guest_call(method: &str, arg: &[u8]) -> Result<Vec<u8>, String> {
+        REQUEST.with(|req| *req.borrow_mut() = (method.as_bytes().to_vec(), arg.to_vec()));
+        let ok = super::__guest_call(method.len() as i32, arg.len() as i32);
+        let resp = RESPONSE
+            .with(|r| r.borrow_mut().take())
+            .expect("actor responded");
+        assert_eq!(ok == 1, resp.is_ok());
+        resp
+    }
+}
+
+/// writes the HostContext for the next message, as the host does after calling `__guest_context`.
+/// Natively, the address `__guest_context` returns is truncated to 32 bits, so the context
+/// is written through `alloc_host_context`, which the export calls.
+fn set_host_context(hc: &HostContext) {
+    let buf = serialize(hc).unwrap();
+    assert_ne!(__guest_context(buf.len() as i32), 0);
+    let ptr = wasmbus_rpc::actor::alloc_host_context(buf.len());
+    unsafe { std::ptr::copy_nonoverlapping(buf.as_ptr(), ptr, buf.len()) };
+}
+
+/// sends a message to the actor's Echo service
+fn echo(arg: &str) -> Result<String, String> {
+    let resp = guest_call("Echo.Echo", &serialize(&arg).unwrap())?;
+    Ok(deserialize::<String>(&resp).unwrap())
+}
+
+#[derive(Actor)]
+#[services(Echo)]
+#[actor(init = "make_actor", middleware = "make_stack")]
+struct EchoActor {
+    prefix: String,
+    calls: AtomicUsize,
+}
+
+fn make_actor() -> EchoActor {
+    ACTORS_MADE.with(|n| n.set(n.get() + 1));
+    EchoActor {
+        prefix: "hello".to_string(),
+        calls: AtomicUsize::new(0),
+    }
+}
+
+/// rejects messages with the invocation id "forbidden"
+fn make_stack() -> MiddlewareStack {
+    MiddlewareStack::new().layer(AuthorizeLayer::new(
+        |ctx: &Context, _method: &str| match ctx.invocation_id.as_deref() {
+            Some("forbidden") => Err(RpcError::InvalidParameter("not authorized".to_string())),
+            _ => Ok(()),
+        },
+    ))
+}
+
+#[async_trait]
+impl Echo for EchoActor {
+    async fn echo(&self, ctx: &Context, arg: &String) -> RpcResult<String> {
+        LAST_CONTEXT.with(|c| *c.borrow_mut() = Some(ctx.clone()));
+        let calls = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+        Ok(format!("{} {} {}", self.prefix, arg, calls))
+    }
+}
+
+#[test]
+fn actor_constructed_once() {
+    assert_eq!(__actor_api_version(), wasmbus_rpc::WASMBUS_RPC_VERSION);
+    assert_eq!(ACTORS_MADE.with(Cell::get), 0);
+
+    // the actor is constructed with make_actor on the first message, and reused
+    assert_eq!(echo("world").unwrap(), "hello world 1");
+    assert_eq!(echo("again").unwrap(), "hello again 2");
+    assert_eq!(ACTORS_MADE.with(Cell::get), 1);
+
+    let err = guest_call("Echo.Shout", b"").unwrap_err();
+    assert!(err.contains("Echo.Shout"), "{}", err);
+}
+
+#[test]
+fn actor_context_from_host() {
+    let actor_key = KeyPair::new_module().public_key();
+    set_host_context(&HostContext {
+        origin: Some(WasmCloudEntity {
+            public_key: actor_key.clone(),
+            ..Default::default()
+        }),
+        invocation_id: Some("invocation-1".to_string()),
+        trace_context: HashMap::from([("traceparent".to_string(), "00-01-02-01".to_string())]),
+        log_level: None,
+    });
+    echo("world").unwrap();
+    let ctx = LAST_CONTEXT.with(|c| c.borrow_mut().take()).unwrap();
+    assert_eq!(ctx.actor.as_deref(), Some(actor_key.as_str()));
+    assert_eq!(ctx.origin.unwrap().public_key, actor_key);
+    assert_eq!(ctx.invocation_id.as_deref(), Some("invocation-1"));
+    assert_eq!(ctx.trace_context["traceparent"], "00-01-02-01");
+
+    // the host context applies to one message
+    echo("again").unwrap();
+    let ctx = LAST_CONTEXT.with(|c| c.borrow_mut().take()).unwrap();
+    assert_eq!(ctx.actor, None);
+    assert_eq!(ctx.invocation_id, None);
+}
+
+#[test]
+fn actor_host_call_opts() {
+    // no host call in progress
+    assert_eq!(__host_call_opts_len(), 0);
+
+    // options the actor sets while it sends a message with __host_call
+    let opts = HostCallOpts {
+        timeout_ms: Some(1500),
+        idempotent: true,
+        read_only: true,
+        trace_context: HashMap::from([("traceparent".to_string(), "00-01-02-01".to_string())]),
+    };
+    wasmbus_rpc::actor::set_host_call_opts(Some(&opts));
+    let (ptr, len) = wasmbus_rpc::actor::host_call_opts();
+    assert_eq!(__host_call_opts_len(), len as i32);
+    // natively, the address is truncated to 32 bits
+    assert_eq!(__host_call_opts(), ptr as i32);
+    let buf = unsafe { std::slice::from_raw_parts(ptr, len) };
+    assert_eq!(deserialize::<HostCallOpts>(buf).unwrap(), opts);
+
+    wasmbus_rpc::actor::set_host_call_opts(None);
+    assert_eq!(__host_call_opts_len(), 0);
+}
+
+#[test]
+fn actor_middleware() {
+    assert_eq!(echo("world").unwrap(), "hello world 1");
+
+    // the message is rejected by the middleware before it reaches the actor
+    set_host_context(&HostContext {
+        invocation_id: Some("forbidden".to_string()),
+        ..Default::default()
+    });
+    let err = echo("world").unwrap_err();
+    assert!(err.contains("not authorized"), "{}", err);
+
+    assert_eq!(echo("again").unwrap(), "hello again 2");
+}
@@ -0,0 +1,214 @@
+//! SchemaCodec conversion between json and the wire encoding of generated code,
+//! using the model in tests/smithy
+#![cfg(all(test, feature = "dynamic_json"))]
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{collections::HashMap, path::Path};
+use wasmbus_rpc::{
+    deserialize,
+    dynamic::{ModelSource, SchemaCodec},
+    serialize, RpcError,
+};
+
+/// Order, as declared by generated code
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+struct Order {
+    store: String,
+    items: Vec<Item>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tags: Option<HashMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+}
+
+/// Item, as declared by generated code
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+struct Item {
+    name: String,
+    #[serde(rename = "qty")]
+    quantity: u32,
+    #[serde(default)]
+    price: f64,
+    #[serde(with = "serde_bytes")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data: Option<Vec<u8>>,
+}
+
+fn codec() -> SchemaCodec {
+    let source = ModelSource::Path {
+        path: "tests/smithy".into(),
+        files: Vec::new(),
+    };
+    SchemaCodec::from_sources(&[source], Path::new(env!("CARGO_MANIFEST_DIR"))).unwrap()
+}
+
+#[test]
+fn encode_as_generated_code() {
+    let codec = codec();
+    let input = json!({
+        "store": "north",
+        "items": [
+            { "name": "apple", "qty": 3, "price": 0.5, "data": "AQID" },
+            // members may also be named by their declared name
+            { "name": "pear", "quantity": 7 },
+        ],
+        "tags": { "season": "fall" },
+    });
+    let buf = codec.encode_input("Inventory.Restock", &input).unwrap();
+    let order = deserialize::<Order>(&buf).unwrap();
+    assert_eq!(
+        order,
+        Order {
+            store: "north".to_string(),
+            items: vec![
+                Item {
+                    name: "apple".to_string(),
+                    quantity: 3,
+                    price: 0.5,
+                    data: Some(vec![1, 2, 3]),
+                },
+                Item {
+                    name: "pear".to_string(),
+                    quantity: 7,
+                    ..Default::default()
+                },
+            ],
+            tags: Some(HashMap::from([("season".to_string(), "fall".to_string())])),
+            note: None,
+        }
+    );
+
+    // operations without input have an empty payload
+    assert!(codec
+        .encode_input("Inventory.Clear", &json!({}))
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn decode_from_generated_code() {
+    let codec = codec();
+    let order = Order {
+        store: "south".to_string(),
+        items: vec![Item {
+            name: "plum".to_string(),
+            quantity: 12,
+            price: 1.25,
+            data: Some(vec![255]),
+        }],
+        tags: None,
+        note: Some("rush".to_string()),
+    };
+    let out = codec
+        .decode_output("Inventory.Restock", &serialize(&order).unwrap())
+        .unwrap();
+    // optional fields that aren't set are omitted, and blobs are base64
+    assert_eq!(
+        out,
+        json!({
+            "store": "south",
+            "items": [ { "name": "plum", "qty": 12, "price": 1.25, "data": "/w==" } ],
+            "note": "rush",
+        })
+    );
+    assert_eq!(
+        codec.decode_output("Inventory.Clear", &[]).unwrap(),
+        serde_json::Value::Null
+    );
+}
+
+#[test]
+fn round_trip() {
+    let codec = codec();
+    let input = json!({
+        "store": "east",
+        "items": [ { "name": "fig", "qty": 4294967295u32, "price": 2.0 } ],
+        "tags": { "a": "1", "b": "2" },
+        "note": "",
+    });
+    let buf = codec.encode_input("inventory.restock", &input).unwrap();
+    assert_eq!(
+        codec.decode_output("Inventory.Restock", &buf).unwrap(),
+        input
+    );
+}
+
+#[test]
+fn invalid_input() {
+    let codec = codec();
+    let err_msg = |input: serde_json::Value| match codec.encode_input("Inventory.Restock", &input) {
+        Err(RpcError::InvalidParameter(msg)) => msg,
+        other => panic!("expected InvalidParameter, got {:?}", other),
+    };
+
+    assert_eq!(
+        err_msg(json!({ "store": "x", "items": [], "colour": "red" })),
+        "input.colour: unknown field"
+    );
+    assert_eq!(
+        err_msg(json!({ "store": "x", "items": [ { "name": "a", "qty": 1, "size": 2 } ] })),
+        "input.items[0].size: unknown field"
+    );
+    assert_eq!(
+        err_msg(
+            json!({ "store": "x", "items": [ { "name": "a", "qty": 1 }, { "name": "b", "qty": "2" } ] })
+        ),
+        "input.items[1].qty: expected integer"
+    );
+    assert_eq!(
+        err_msg(json!({ "store": "x", "items": [ { "name": "a", "qty": -1 } ] })),
+        "input.items[0].qty: -1 is out of range (0..=4294967295)"
+    );
+    assert_eq!(
+        err_msg(json!({ "store": "x", "items": {} })),
+        "input.items: expected array"
+    );
+    assert_eq!(
+        err_msg(json!({ "store": "x", "items": [], "tags": { "k": 1 } })),
+        "input.tags[\"k\"]: expected string"
+    );
+    assert_eq!(
+        err_msg(json!({ "items": [] })),
+        "input.store: missing required field"
+    );
+
+    let err = codec
+        .encode_input("Inventory.Sell", &json!({}))
+        .unwrap_err();
+    assert!(matches!(err, RpcError::MethodNotHandled(_)), "{}", err);
+}
+
+#[test]
+fn invalid_output() {
+    let codec = codec();
+    #[derive(Serialize)]
+    struct WrongItem {
+        name: u32,
+        qty: u32,
+    }
+    #[derive(Serialize)]
+    struct WrongOrder {
+        store: String,
+        items: Vec<WrongItem>,
+    }
+    let buf = serialize(&WrongOrder {
+        store: "west".to_string(),
+        items: vec![WrongItem { name: 1, qty: 1 }],
+    })
+    .unwrap();
+    let err = codec.decode_output("Inventory.Restock", &buf).unwrap_err();
+    assert!(
+        matches!(&err, RpcError::Deser(msg) if msg.starts_with("output.items[0].name: expected string")),
+        "{}",
+        err
+    );
+
+    let buf = serialize(&HashMap::from([("items", Vec::<Item>::new())])).unwrap();
+    let err = codec.decode_output("Inventory.Restock", &buf).unwrap_err();
+    assert!(
+        matches!(&err, RpcError::Deser(msg) if msg == "output.store: missing required field"),
+        "{}",
+        err
+    );
+}
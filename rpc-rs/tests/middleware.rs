@@ -0,0 +1,202 @@
+//! dispatch and send messages through middleware stacks
+#![cfg(test)]
+
+use async_trait::async_trait;
+use std::{
+    borrow::Cow,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use wasmbus_rpc::{
+    middleware::{
+        AuthorizeLayer, CatchPanicLayer, DispatchNext, MetricsLayer, Middleware, MiddlewareStack,
+        SendNext, TimeoutLayer,
+    },
+    Context, Message, MessageDispatch, RpcError, RpcResult, SendOpts, Transport,
+};
+
+/// receiver that echoes its arg, and sleeps or panics for some methods
+struct Echo {}
+
+#[async_trait]
+impl MessageDispatch for Echo {
+    async fn dispatch(
+        &self,
+        _ctx: &Context,
+        message: Message<'_>,
+    ) -> Result<Message<'_>, RpcError> {
+        match message.method {
+            "Echo.Slow" => tokio::time::sleep(Duration::from_secs(2)).await,
+            "Echo.Panic" => panic!("echo panic"),
+            _ => {}
+        }
+        Ok(Message {
+            method: "Echo.Echo",
+            arg: Cow::Owned(message.arg.to_vec()),
+        })
+    }
+}
+
+/// transport that returns the message arg
+struct Loopback {}
+
+#[async_trait]
+impl Transport for Loopback {
+    async fn send(
+        &self,
+        _ctx: &Context,
+        req: Message<'_>,
+        _opts: Option<SendOpts>,
+    ) -> RpcResult<Vec<u8>> {
+        if req.method == "Echo.Slow" {
+            tokio::time::sleep(Duration::from_secs(2)).await;
+        }
+        Ok(req.arg.to_vec())
+    }
+
+    fn set_timeout(&self, _interval: Duration) {}
+}
+
+/// middleware that records when messages pass through it
+struct Trace {
+    name: &'static str,
+    events: Arc<Mutex<Vec<String>>>,
+}
+
+#[async_trait]
+impl Middleware for Trace {
+    async fn dispatch<'s>(
+        &self,
+        ctx: &Context,
+        message: Message<'_>,
+        next: DispatchNext<'s>,
+    ) -> RpcResult<Message<'s>> {
+        self.events.lock().unwrap().push(format!(">{}", self.name));
+        let result = next.run(ctx, message).await;
+        self.events.lock().unwrap().push(format!("<{}", self.name));
+        result
+    }
+
+    async fn send<'s>(
+        &self,
+        ctx: &Context,
+        message: Message<'_>,
+        opts: Option<SendOpts>,
+        next: SendNext<'s>,
+    ) -> RpcResult<Vec<u8>> {
+        self.events
+            .lock()
+            .unwrap()
+            .push(format!("send {}", self.name));
+        next.run(ctx, message, opts).await
+    }
+}
+
+fn message(method: &str) -> Message<'_> {
+    Message {
+        method,
+        arg: Cow::Borrowed(b"hello"),
+    }
+}
+
+#[tokio::test]
+async fn dispatch_layers() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let metrics = MetricsLayer::default();
+    let stack = MiddlewareStack::new()
+        .layer(Trace {
+            name: "outer",
+            events: events.clone(),
+        })
+        .layer(metrics.clone())
+        .layer(AuthorizeLayer::new(
+            |ctx: &Context, _method: &str| match ctx.actor.as_deref() {
+                Some("trusted") => Ok(()),
+                _ => Err(RpcError::InvalidParameter("not authorized".to_string())),
+            },
+        ))
+        .layer(Trace {
+            name: "inner",
+            events: events.clone(),
+        });
+    let echo = stack.wrap(Echo {});
+
+    let ctx = Context {
+        actor: Some("trusted".to_string()),
+        ..Default::default()
+    };
+    let resp = echo.dispatch(&ctx, message("Echo.Echo")).await.unwrap();
+    assert_eq!(resp.arg.as_ref(), b"hello");
+    assert_eq!(
+        events.lock().unwrap().as_slice(),
+        [">outer", ">inner", "<inner", "<outer"]
+    );
+
+    // the authorization check fails before the message reaches the inner layers
+    events.lock().unwrap().clear();
+    let err = echo
+        .dispatch(&Context::default(), message("Echo.Echo"))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, RpcError::InvalidParameter(_)));
+    assert_eq!(events.lock().unwrap().as_slice(), [">outer", "<outer"]);
+
+    let counts = metrics.snapshot();
+    assert_eq!(counts["Echo.Echo"].calls, 2);
+    assert_eq!(counts["Echo.Echo"].errors, 1);
+}
+
+#[tokio::test]
+async fn dispatch_timeout_and_panic() {
+    let stack = MiddlewareStack::new()
+        .layer(CatchPanicLayer::default())
+        .layer(TimeoutLayer::new(Duration::from_millis(100)));
+    let echo = stack.wrap(Echo {});
+    let ctx = Context::default();
+
+    let err = echo.dispatch(&ctx, message("Echo.Slow")).await.unwrap_err();
+    assert!(matches!(err, RpcError::Timeout(_)), "{}", err);
+
+    let err = echo
+        .dispatch(&ctx, message("Echo.Panic"))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("echo panic"), "{}", err);
+
+    // the receiver still handles messages after a panic
+    let resp = echo.dispatch(&ctx, message("Echo.Echo")).await.unwrap();
+    assert_eq!(resp.arg.as_ref(), b"hello");
+}
+
+#[tokio::test]
+async fn send_layers() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let stack = MiddlewareStack::new()
+        .layer(Trace {
+            name: "outer",
+            events: events.clone(),
+        })
+        .layer(TimeoutLayer::new(Duration::from_millis(100)))
+        // dispatch-only middleware doesn't apply to sent messages
+        .layer(AuthorizeLayer::new(|_: &Context, _: &str| {
+            Err(RpcError::Other("unreachable".to_string()))
+        }));
+    let transport = stack.wrap(Loopback {});
+    let ctx = Context::default();
+
+    let resp = transport
+        .send(&ctx, message("Echo.Echo"), None)
+        .await
+        .unwrap();
+    assert_eq!(resp, b"hello");
+
+    let err = transport
+        .send(&ctx, message("Echo.Slow"), None)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, RpcError::Timeout(_)), "{}", err);
+    assert_eq!(
+        events.lock().unwrap().as_slice(),
+        ["send outer", "send outer"]
+    );
+}
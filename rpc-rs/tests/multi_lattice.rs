@@ -0,0 +1,145 @@
+//! route messages to lattices with MultiLatticeClient, using an in-process bus for each lattice
+#![cfg(test)]
+
+use std::{borrow::Cow, sync::Arc, time::Duration};
+use wascap::prelude::KeyPair;
+use wasmbus_rpc::{
+    bus::{InProcessBus, MessageBus},
+    core::{Invocation, InvocationResponse, WasmCloudEntity},
+    deserialize, serialize, LatticeTarget, Message, MultiLatticeClient, RpcClient, RpcError,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// returns a bus on which every actor responds with the name of the lattice
+async fn lattice_bus(lattice: &'static str) -> InProcessBus {
+    let bus = InProcessBus::default();
+    let sub = bus
+        .subscribe(&format!("wasmbus.rpc.{}.*", lattice))
+        .await
+        .unwrap();
+    let responder = bus.clone();
+    tokio::spawn(async move {
+        while let Some(msg) = sub.next().await {
+            let inv = deserialize::<Invocation>(&msg.data).unwrap();
+            let resp = InvocationResponse {
+                msg: lattice.as_bytes().to_vec(),
+                invocation_id: inv.id,
+                error: None,
+            };
+            if let Some(reply) = msg.reply {
+                let _ = responder.publish(&reply, &serialize(&resp).unwrap()).await;
+            }
+        }
+    });
+    bus
+}
+
+fn message() -> Message<'static> {
+    Message {
+        method: "Echo.Echo",
+        arg: Cow::Borrowed(b"hello"),
+    }
+}
+
+#[tokio::test]
+async fn route_by_lattice() {
+    let actor = WasmCloudEntity::new_actor(KeyPair::new_module().public_key()).unwrap();
+    let edge_client = RpcClient::new_with_bus(
+        Arc::new(lattice_bus("edge").await),
+        "edge",
+        KeyPair::new_cluster(),
+        KeyPair::new_server().public_key(),
+        Some(TIMEOUT),
+    );
+    let client = MultiLatticeClient::builder()
+        .lattice(
+            "prod",
+            RpcClient::builder().bus(Arc::new(lattice_bus("prod").await)),
+        )
+        .client("edge", edge_client)
+        .build()
+        .await
+        .unwrap();
+    let mut lattices = client.lattices().collect::<Vec<_>>();
+    lattices.sort_unstable();
+    assert_eq!(lattices, ["edge", "prod"]);
+    assert_eq!(client.client("prod").unwrap().lattice_prefix(), "prod");
+
+    for lattice in ["prod", "edge"] {
+        let resp = client
+            .send(
+                WasmCloudEntity::default(),
+                LatticeTarget::new(lattice, actor.clone()),
+                message(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp, lattice.as_bytes());
+    }
+
+    let err = client
+        .send(
+            WasmCloudEntity::default(),
+            ("test", actor.clone()),
+            message(),
+        )
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, RpcError::InvalidParameter(e) if e.contains("unknown lattice 'test'")),
+        "{}",
+        err
+    );
+
+    // without a lattice qualifier, the default lattice is used
+    let err = client
+        .send(WasmCloudEntity::default(), actor.clone(), message())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, RpcError::InvalidParameter(_)), "{}", err);
+    let client = MultiLatticeClient::builder()
+        .lattice(
+            "prod",
+            RpcClient::builder().bus(Arc::new(lattice_bus("prod").await)),
+        )
+        .lattice(
+            "edge",
+            RpcClient::builder().bus(Arc::new(lattice_bus("edge").await)),
+        )
+        .default_lattice("edge")
+        .build()
+        .await
+        .unwrap();
+    let resp = client
+        .send_timeout(WasmCloudEntity::default(), actor, message(), TIMEOUT)
+        .await
+        .unwrap();
+    assert_eq!(resp, b"edge");
+}
+
+#[tokio::test]
+async fn client_lattice_must_match() {
+    let client = RpcClient::builder()
+        .bus(Arc::new(InProcessBus::default()))
+        .lattice_prefix("prod")
+        .build()
+        .await
+        .unwrap();
+    let err = MultiLatticeClient::builder()
+        .client("edge", client.clone())
+        .build()
+        .await
+        .err()
+        .unwrap();
+    assert!(
+        matches!(&err, RpcError::InvalidParameter(e) if e.contains("lattice prefix 'prod'")),
+        "{}",
+        err
+    );
+    assert!(MultiLatticeClient::builder()
+        .client("prod", client)
+        .build()
+        .await
+        .is_ok());
+}
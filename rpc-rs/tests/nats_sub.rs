@@ -1,26 +1,24 @@
 //! test nats subscriptions (queue and non-queue) with rpc_client
 #![cfg(test)]
 
+mod support;
+
 use std::{str::FromStr as _, time::Duration};
+use support::nats_server::TestNatsServer;
 use wasmbus_rpc::{RpcClient, RpcError, RpcResult};
 
-//const DEFAULT_NATS_ADDR: &str = "nats://127.0.0.1:4222";
-const TEST_NATS_ADDR: &str = "demo.nats.io";
 const LATTICE_PREFIX: &str = "test_nats_sub";
 const HOST_ID: &str = "HOST_test_nats_sub";
 
 /// create async nats client for test (sender or receiver)
-async fn make_client() -> RpcResult<RpcClient> {
-    let server_addr = wasmbus_rpc::anats::ServerAddress::from_str(TEST_NATS_ADDR).unwrap();
+async fn make_client(nats_url: &str) -> RpcResult<RpcClient> {
+    let server_addr = wasmbus_rpc::anats::ServerAddress::from_str(nats_url).unwrap();
     let nc = wasmbus_rpc::anats::Options::default()
         .max_reconnects(None)
         .connect(vec![server_addr])
         .await
         .map_err(|e| {
-            RpcError::ProviderInit(format!(
-                "nats connection to {} failed: {}",
-                TEST_NATS_ADDR, e
-            ))
+            RpcError::ProviderInit(format!("nats connection to {} failed: {}", nats_url, e))
         })?;
     let kp = wascap::prelude::KeyPair::new_user();
     let client = RpcClient::new(
@@ -131,13 +129,14 @@ async fn listen_queue(
 
 #[tokio::test]
 async fn simple_sub() -> Result<(), Box<dyn std::error::Error>> {
+    let server = TestNatsServer::start().await?;
     // create unique subscription name for this test
     let sub_name = uuid::Uuid::new_v4().to_string();
 
     let topic = format!("one_{}", &sub_name);
-    let l1 = listen(make_client().await?, &topic, "^abc").await;
+    let l1 = listen(make_client(&server.url()).await?, &topic, "^abc").await;
 
-    let sender = make_client().await.expect("creating sender");
+    let sender = make_client(&server.url()).await.expect("creating sender");
     sender.publish(&topic, b"abc").await.expect("send");
     sender.publish(&topic, b"exit").await.expect("send");
     let val = l1.await.expect("join");
@@ -150,22 +149,19 @@ async fn simple_sub() -> Result<(), Box<dyn std::error::Error>> {
 #[tokio::test]
 async fn test_message_size() -> Result<(), Box<dyn std::error::Error>> {
     if env_logger::try_init().is_err() {};
+    let server = TestNatsServer::start().await?;
     // create unique subscription name for this test
     let sub_name = uuid::Uuid::new_v4().to_string();
 
     let topic = format!("bin_{}", &sub_name);
-    let l1 = listen_bin(make_client().await?, &topic).await;
+    let l1 = listen_bin(make_client(&server.url()).await?, &topic).await;
 
     let mut pass_count = 0;
-    let sender = make_client().await.expect("creating bin sender");
+    let sender = make_client(&server.url())
+        .await
+        .expect("creating bin sender");
     const TEST_SIZES: &[u32] = &[
-        100, 200,
-        // NOTE: if using 'demo.nats.io' as the test server,
-        // don't abuse it by running this test - only use larger sizes
-        // if testing against a local nats server.
-        //
-        // 100_000, 200_000, 300_000, 400_000, 500_000, 600_000, 700_000, 800_000, 900_000,
-        //1_000_000, (1024 * 1024),
+        100, 200, 100_000, 500_000, 900_000,
         // The last size must be 1: signal to listen_bin to exit
         1,
     ];
@@ -233,18 +229,20 @@ async fn queue_sub() -> Result<(), Box<dyn std::error::Error>> {
     // This confirms that publishing to queue subscription divides the load,
     // and also confirms that a queue group name ('X') is only applicable
     // within a topic.
+    let server = TestNatsServer::start().await?;
     let sub_name = uuid::Uuid::new_v4().to_string();
     let topic_one = format!("one_{}", &sub_name);
     let topic_two = format!("two_{}", &sub_name);
 
     let queue_name = uuid::Uuid::new_v4().to_string();
 
-    let thread1 = listen_queue(make_client().await?, &topic_one, &queue_name, "^one").await;
-    let thread2 = listen_queue(make_client().await?, &topic_one, &queue_name, "^one").await;
-    let thread3 = listen_queue(make_client().await?, &topic_two, &queue_name, "^two").await;
-    sleep(2000).await;
+    let url = server.url();
+    let thread1 = listen_queue(make_client(&url).await?, &topic_one, &queue_name, "^one").await;
+    let thread2 = listen_queue(make_client(&url).await?, &topic_one, &queue_name, "^one").await;
+    let thread3 = listen_queue(make_client(&url).await?, &topic_two, &queue_name, "^two").await;
+    sleep(200).await;
 
-    let sender = make_client().await?;
+    let sender = make_client(&url).await?;
     const SPLIT_TOTAL: usize = 6;
     const SINGLE_TOTAL: usize = 6;
     for _ in 0..SPLIT_TOTAL {
@@ -275,3 +273,23 @@ async fn wait_for<O, F: futures::Future<Output = O>>(
     let res: O = tokio::time::timeout(timeout, f).await?;
     Ok(res)
 }
+
+/// messages larger than the server's max_payload are not delivered
+#[tokio::test]
+async fn max_payload_exceeded() -> Result<(), Box<dyn std::error::Error>> {
+    let server = TestNatsServer::start_with_max_payload(1024).await?;
+    let topic = format!("max_{}", uuid::Uuid::new_v4());
+    let l1 = listen_bin(make_client(&server.url()).await?, &topic).await;
+
+    let sender = make_client(&server.url()).await?;
+    let resp = sender.request(&topic, &[0u8; 1000]).await?;
+    assert_eq!(&resp, b"1000");
+    assert!(sender.request(&topic, &[0u8; 2000]).await.is_err());
+
+    // size 1 tells the listener to exit
+    let sender = make_client(&server.url()).await?;
+    let _ = sender.request(&topic, &[0u8; 1]).await?;
+    let val = wait_for(l1, TWO_SEC).await??;
+    assert_eq!(val, 2);
+    Ok(())
+}
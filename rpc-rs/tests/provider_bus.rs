@@ -0,0 +1,727 @@
+//! run a provider with an in-process message bus, with the test acting as host
+#![cfg(test)]
+
+use async_trait::async_trait;
+use std::{
+    borrow::Cow,
+    convert::Infallible,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::sync::Notify;
+use wascap::prelude::KeyPair;
+use wasmbus_rpc::{
+    bus::{InProcessBus, MessageBus},
+    core::{
+        HealthCheckResponse, HostData, Invocation, InvocationResponse, LinkDefinition,
+        WasmCloudEntity,
+    },
+    deserialize,
+    provider::{
+        prelude::provider_run_with_bus, HostBridge, ProviderDispatch, ProviderHandler,
+        ProviderTransport, StaticResolver, TransportTarget,
+    },
+    serialize, Context, InvocationBuilder, InvocationError, Message, MessageDispatch, RpcClient,
+    RpcError, Transport,
+};
+
+const LATTICE_PREFIX: &str = "test_provider_bus";
+const LINK_NAME: &str = "default";
+const CONTRACT_ID: &str = "wasmcloud:testing";
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Default)]
+struct EchoProvider {
+    links: Arc<Mutex<Vec<String>>>,
+    shutdown: Arc<AtomicBool>,
+    /// set when an "Echo.Slow" message is received
+    slow_started: Arc<AtomicBool>,
+    /// "Echo.Slow" responds when this is notified
+    slow_release: Arc<Notify>,
+}
+
+impl ProviderDispatch for EchoProvider {}
+
+#[async_trait]
+impl ProviderHandler for EchoProvider {
+    async fn put_link(&self, ld: &LinkDefinition) -> Result<bool, RpcError> {
+        self.links.lock().unwrap().push(ld.actor_id.clone());
+        Ok(true)
+    }
+
+    async fn delete_link(&self, actor_id: &str) {
+        self.links.lock().unwrap().retain(|id| id != actor_id);
+    }
+
+    async fn shutdown(&self) -> Result<(), Infallible> {
+        self.shutdown.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MessageDispatch for EchoProvider {
+    async fn dispatch(
+        &self,
+        _ctx: &Context,
+        message: Message<'_>,
+    ) -> Result<Message<'_>, RpcError> {
+        match message.method {
+            "Echo.Echo" => Ok(Message {
+                method: "Echo.Echo",
+                arg: Cow::Owned(message.arg.to_vec()),
+            }),
+            "Echo.Slow" => {
+                self.slow_started.store(true, Ordering::SeqCst);
+                self.slow_release.notified().await;
+                Ok(Message {
+                    method: "Echo.Slow",
+                    arg: Cow::Owned(message.arg.to_vec()),
+                })
+            }
+            _ => Err(RpcError::MethodNotHandled(message.method.to_string())),
+        }
+    }
+}
+
+fn topic(provider_key: &str, suffix: &str) -> String {
+    format!(
+        "wasmbus.rpc.{}.{}.{}.{}",
+        LATTICE_PREFIX, provider_key, LINK_NAME, suffix
+    )
+}
+
+/// waits until the provider has subscribed to all host and rpc topics
+async fn wait_for_provider(bus: &InProcessBus, provider_key: &str) {
+    let topics = [
+        topic(provider_key, "health"),
+        topic(provider_key, "linkdefs.put"),
+        topic(provider_key, "linkdefs.del"),
+        topic(provider_key, "shutdown"),
+        format!(
+            "wasmbus.rpc.{}.{}.{}",
+            LATTICE_PREFIX, provider_key, LINK_NAME
+        ),
+    ];
+    for _ in 0..100 {
+        if topics.iter().all(|t| bus.has_subscribers(t)) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("provider did not start");
+}
+
+#[tokio::test]
+async fn provider_lifecycle() {
+    let bus = InProcessBus::default();
+    let issuer = KeyPair::new_cluster();
+    let provider_key = KeyPair::new_service().public_key();
+    let actor_key = KeyPair::new_module().public_key();
+    let unlinked_actor_key = KeyPair::new_module().public_key();
+    let host_data = HostData {
+        host_id: "_TEST_".to_string(),
+        lattice_rpc_prefix: LATTICE_PREFIX.to_string(),
+        link_name: LINK_NAME.to_string(),
+        provider_key: provider_key.clone(),
+        cluster_issuers: vec![issuer.public_key()],
+        ..Default::default()
+    };
+    let provider = EchoProvider::default();
+
+    let host = async {
+        wait_for_provider(&bus, &provider_key).await;
+
+        // under test, the provider responds to health checks with json
+        let resp = bus
+            .request(&topic(&provider_key, "health"), b"", Some(TIMEOUT))
+            .await
+            .expect("health response");
+        let health: HealthCheckResponse = serde_json::from_slice(&resp).expect("health json");
+        assert!(health.healthy);
+
+        // only test providers may run beside it
+        let err = provider_run_with_bus(
+            EchoProvider::default(),
+            bridge_host_data(&issuer, &KeyPair::new_service().public_key()),
+            Arc::new(bus.clone()),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("already running"), "{}", err);
+
+        // put link, and wait for the provider to process it
+        let ld = LinkDefinition {
+            actor_id: actor_key.clone(),
+            provider_id: provider_key.clone(),
+            link_name: LINK_NAME.to_string(),
+            contract_id: CONTRACT_ID.to_string(),
+            ..Default::default()
+        };
+        bus.publish(
+            &topic(&provider_key, "linkdefs.put"),
+            &serde_json::to_vec(&ld).unwrap(),
+        )
+        .await
+        .expect("link put");
+        for _ in 0..100 {
+            if provider.links.lock().unwrap().contains(&actor_key) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(
+            provider.links.lock().unwrap().as_slice(),
+            std::slice::from_ref(&actor_key)
+        );
+
+        // send rpc from the linked actor
+        let client = RpcClient::new_with_bus(
+            Arc::new(bus.clone()),
+            LATTICE_PREFIX,
+            issuer,
+            KeyPair::new_server().public_key(),
+            Some(TIMEOUT),
+        );
+        let target = WasmCloudEntity {
+            public_key: provider_key.clone(),
+            contract_id: CONTRACT_ID.to_string(),
+            link_name: LINK_NAME.to_string(),
+        };
+        let resp = client
+            .send(
+                WasmCloudEntity::new_actor(&actor_key).unwrap(),
+                target.clone(),
+                Message {
+                    method: "Echo.Echo",
+                    arg: Cow::Borrowed(b"hello"),
+                },
+            )
+            .await
+            .expect("echo response");
+        assert_eq!(&resp, b"hello");
+
+        // rpc from an actor that isn't linked is rejected
+        let resp = client
+            .send(
+                WasmCloudEntity::new_actor(&unlinked_actor_key).unwrap(),
+                target,
+                Message {
+                    method: "Echo.Echo",
+                    arg: Cow::Borrowed(b"hello"),
+                },
+            )
+            .await;
+        assert!(matches!(resp, Err(RpcError::Rpc(s)) if s.contains("unlinked actor")));
+
+        // delete link
+        bus.publish(
+            &topic(&provider_key, "linkdefs.del"),
+            &serde_json::to_vec(&ld).unwrap(),
+        )
+        .await
+        .expect("link del");
+        for _ in 0..100 {
+            if provider.links.lock().unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(provider.links.lock().unwrap().is_empty());
+
+        // shutdown
+        let resp = bus
+            .request(&topic(&provider_key, "shutdown"), b"", Some(TIMEOUT))
+            .await
+            .expect("shutdown ack");
+        assert_eq!(&resp, b"shutting down");
+    };
+
+    let (run_result, ()) = tokio::join!(
+        provider_run_with_bus(provider.clone(), host_data, Arc::new(bus.clone())),
+        host
+    );
+    assert!(run_result.is_ok());
+    assert!(provider.shutdown.load(Ordering::SeqCst));
+}
+
+/// waits until the condition is true
+async fn wait_until<F: Fn() -> bool>(condition: F, what: &str) {
+    for _ in 0..200 {
+        if condition() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("timed out waiting until {}", what);
+}
+
+#[tokio::test]
+async fn shutdown_drains_in_flight_messages() {
+    let bus = InProcessBus::default();
+    let issuer = KeyPair::new_cluster();
+    let provider_key = KeyPair::new_service().public_key();
+    let actor_key = KeyPair::new_module().public_key();
+    let host_data = HostData {
+        host_id: "_TEST_".to_string(),
+        lattice_rpc_prefix: LATTICE_PREFIX.to_string(),
+        link_name: LINK_NAME.to_string(),
+        provider_key: provider_key.clone(),
+        cluster_issuers: vec![issuer.public_key()],
+        link_definitions: vec![LinkDefinition {
+            actor_id: actor_key.clone(),
+            provider_id: provider_key.clone(),
+            link_name: LINK_NAME.to_string(),
+            contract_id: CONTRACT_ID.to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let provider = EchoProvider::default();
+
+    let host = async {
+        wait_for_provider(&bus, &provider_key).await;
+        let client = RpcClient::new_with_bus(
+            Arc::new(bus.clone()),
+            LATTICE_PREFIX,
+            issuer,
+            KeyPair::new_server().public_key(),
+            Some(TIMEOUT),
+        );
+        let target = WasmCloudEntity {
+            public_key: provider_key.clone(),
+            contract_id: CONTRACT_ID.to_string(),
+            link_name: LINK_NAME.to_string(),
+        };
+        let send = |method: &'static str| {
+            let (client, target) = (client.clone(), target.clone());
+            let origin = WasmCloudEntity::new_actor(&actor_key).unwrap();
+            tokio::spawn(async move {
+                client
+                    .send(
+                        origin,
+                        target,
+                        Message {
+                            method,
+                            arg: Cow::Borrowed(b"hello"),
+                        },
+                    )
+                    .await
+            })
+        };
+
+        // a message is being dispatched when the host asks the provider to shut down
+        let slow = send("Echo.Slow");
+        wait_until(
+            || provider.slow_started.load(Ordering::SeqCst),
+            "dispatch started",
+        )
+        .await;
+        let shutdown = {
+            let (bus, topic) = (bus.clone(), topic(&provider_key, "shutdown"));
+            tokio::spawn(async move { bus.request(&topic, b"", Some(TIMEOUT)).await })
+        };
+        let rpc_topic = format!(
+            "wasmbus.rpc.{}.{}.{}",
+            LATTICE_PREFIX, provider_key, LINK_NAME
+        );
+        wait_until(|| !bus.has_subscribers(&rpc_topic), "rpc unsubscribed").await;
+        assert!(provider.shutdown.load(Ordering::SeqCst));
+
+        // new messages are rejected, and shutdown waits for the in-flight message
+        let resp = send("Echo.Echo").await.unwrap();
+        assert!(matches!(resp, Err(RpcError::Nats(_))), "{:?}", resp);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!shutdown.is_finished());
+        assert!(!slow.is_finished());
+
+        provider.slow_release.notify_one();
+        assert_eq!(slow.await.unwrap().unwrap(), b"hello");
+        assert_eq!(shutdown.await.unwrap().unwrap(), b"shutting down");
+    };
+
+    let (run_result, ()) = tokio::join!(
+        provider_run_with_bus(provider.clone(), host_data, Arc::new(bus.clone())),
+        host
+    );
+    assert!(run_result.is_ok());
+}
+
+#[tokio::test]
+async fn in_process_queue_group() {
+    let bus = InProcessBus::default();
+    let sub1 = bus.queue_subscribe("work.*", "workers").await.unwrap();
+    let sub2 = bus.queue_subscribe("work.*", "workers").await.unwrap();
+    let all = bus.subscribe("work.>").await.unwrap();
+
+    for _ in 0..4 {
+        bus.publish("work.item", b"x").await.unwrap();
+    }
+    // each message is delivered to one queue member, and to every plain subscriber
+    for _ in 0..2 {
+        assert!(sub1.next().await.is_some());
+        assert!(sub2.next().await.is_some());
+    }
+    for _ in 0..4 {
+        assert_eq!(all.next().await.unwrap().subject, "work.item");
+    }
+
+    let limited = InProcessBus::with_max_payload(4);
+    assert!(matches!(
+        limited.publish("work.item", b"too large").await,
+        Err(RpcError::PayloadTooLarge {
+            actual: 9,
+            allowed: 4
+        })
+    ));
+}
+
+/// host data for a HostBridge that signs invocations with the issuer's key
+fn bridge_host_data(issuer: &KeyPair, provider_key: &str) -> HostData {
+    HostData {
+        lattice_rpc_prefix: LATTICE_PREFIX.to_string(),
+        link_name: LINK_NAME.to_string(),
+        provider_key: provider_key.to_string(),
+        cluster_issuers: vec![issuer.public_key()],
+        invocation_seed: issuer.seed().unwrap(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn resolve_targets_and_origins() {
+    let issuer = Arc::new(KeyPair::new_cluster());
+    let provider_key = KeyPair::new_service().public_key();
+    let actor_key = KeyPair::new_module().public_key();
+    let kv_key = KeyPair::new_service().public_key();
+    let host_data = bridge_host_data(&issuer, &provider_key);
+    let bridge = HostBridge::new_with_bus(Arc::new(InProcessBus::default()), &host_data).unwrap();
+    let resolver = Arc::new(StaticResolver::default());
+    resolver.add_actor_alias("echo", &actor_key);
+    resolver.add_provider("wasmcloud:keyvalue", "default", &kv_key);
+    bridge.set_resolver(resolver);
+
+    let ld = LinkDefinition {
+        actor_id: actor_key.clone(),
+        provider_id: provider_key.clone(),
+        link_name: LINK_NAME.to_string(),
+        contract_id: CONTRACT_ID.to_string(),
+        ..Default::default()
+    };
+    let resolve = |target: TransportTarget| {
+        let (bridge, ld) = (&bridge, &ld);
+        async move { bridge.resolve_target(ld, &target).await }
+    };
+    assert_eq!(
+        resolve(TransportTarget::LinkedActor).await.unwrap(),
+        ld.actor_entity()
+    );
+    assert_eq!(
+        resolve(TransportTarget::Actor(actor_key.clone()))
+            .await
+            .unwrap(),
+        ld.actor_entity()
+    );
+    assert_eq!(
+        resolve(TransportTarget::ActorAlias("echo".to_string()))
+            .await
+            .unwrap(),
+        ld.actor_entity()
+    );
+    let kv = resolve(TransportTarget::Provider {
+        contract_id: "wasmcloud:keyvalue".to_string(),
+        link_name: "default".to_string(),
+    })
+    .await
+    .unwrap();
+    assert_eq!(kv.public_key, kv_key);
+    assert_eq!(
+        kv.url(),
+        format!("wasmbus://wasmcloud/keyvalue/default/{}", kv_key)
+    );
+    for target in [
+        TransportTarget::Actor("MNOTAKEY".to_string()),
+        TransportTarget::ActorAlias("nobody".to_string()),
+        TransportTarget::Provider {
+            contract_id: "wasmcloud:keyvalue".to_string(),
+            link_name: "other".to_string(),
+        },
+    ] {
+        let err = resolve(target.clone()).await.unwrap_err();
+        assert!(
+            matches!(err, RpcError::InvalidParameter(_)),
+            "{:?}: {}",
+            target,
+            err
+        );
+    }
+
+    // invocations from linked actors and from providers known to the resolver are accepted
+    let invocation = |origin: WasmCloudEntity| {
+        InvocationBuilder::new(issuer.clone(), KeyPair::new_server().public_key())
+            .origin(origin)
+            .target(ld.provider_entity())
+            .message(Message {
+                method: "Echo.Echo",
+                arg: Cow::Borrowed(b"hello"),
+            })
+            .build()
+            .unwrap()
+    };
+    let actor_inv = invocation(ld.actor_entity());
+    assert_eq!(
+        bridge.validate_invocation(&actor_inv).await,
+        Err(InvocationError::UnlinkedActor(actor_key.clone()))
+    );
+    bridge.put_link(ld.clone()).await;
+    bridge.validate_invocation(&actor_inv).await.unwrap();
+    bridge
+        .validate_invocation(&invocation(kv.clone()))
+        .await
+        .unwrap();
+
+    // providers the resolver doesn't know, or resolves to another key, are rejected
+    let unknown = WasmCloudEntity {
+        public_key: KeyPair::new_service().public_key(),
+        contract_id: "wasmcloud:blobstore".to_string(),
+        link_name: "default".to_string(),
+    };
+    let impostor = WasmCloudEntity {
+        public_key: KeyPair::new_service().public_key(),
+        ..kv
+    };
+    for origin in [unknown, impostor] {
+        assert_eq!(
+            bridge
+                .validate_invocation(&invocation(origin.clone()))
+                .await,
+            Err(InvocationError::UnknownProvider(origin.url()))
+        );
+    }
+}
+
+/// responds to rpc messages sent to the actor, as the host would, with the result of `respond`
+async fn serve_actor<F>(bus: &InProcessBus, actor_key: &str, respond: F)
+where
+    F: Fn(&Invocation) -> Result<Vec<u8>, String> + Send + 'static,
+{
+    let sub = bus
+        .subscribe(&format!("wasmbus.rpc.{}.{}", LATTICE_PREFIX, actor_key))
+        .await
+        .unwrap();
+    let bus = bus.clone();
+    tokio::spawn(async move {
+        while let Some(msg) = sub.next().await {
+            let inv = deserialize::<Invocation>(&msg.data).unwrap();
+            let (msg_out, error) = match respond(&inv) {
+                Ok(msg) => (msg, None),
+                Err(e) => (Vec::new(), Some(e)),
+            };
+            let resp = InvocationResponse {
+                msg: msg_out,
+                invocation_id: inv.id,
+                error,
+            };
+            if let Some(reply) = msg.reply {
+                let _ = bus.publish(&reply, &serialize(&resp).unwrap()).await;
+            }
+        }
+    });
+}
+
+#[tokio::test]
+async fn broadcast_to_linked_actors() {
+    let bus = InProcessBus::default();
+    let issuer = KeyPair::new_cluster();
+    let provider_key = KeyPair::new_service().public_key();
+    let host_data = bridge_host_data(&issuer, &provider_key);
+    let bridge = HostBridge::new_with_bus(Arc::new(bus.clone()), &host_data).unwrap();
+
+    // ok responds, failing returns an error, absent has no responder, skipped is filtered out
+    let [ok, failing, absent, skipped] = [(); 4].map(|_| KeyPair::new_module().public_key());
+    for actor_id in [&ok, &failing, &absent, &skipped] {
+        bridge
+            .put_link(LinkDefinition {
+                actor_id: actor_id.clone(),
+                provider_id: provider_key.clone(),
+                link_name: LINK_NAME.to_string(),
+                contract_id: CONTRACT_ID.to_string(),
+                ..Default::default()
+            })
+            .await;
+    }
+    serve_actor(&bus, &ok, |inv| Ok([b"ok:", &inv.msg[..]].concat())).await;
+    serve_actor(&bus, &failing, |_| Err("actor failed".to_string())).await;
+    serve_actor(&bus, &skipped, |inv| Ok(inv.msg.clone())).await;
+
+    let message = || Message {
+        method: "Echo.Echo",
+        arg: Cow::Borrowed(b"hello"),
+    };
+    let ctx = Context::default();
+
+    // one result per linked actor, and a failure doesn't prevent delivery to the others
+    let results = bridge.broadcast(&ctx, message()).await;
+    assert_eq!(results.len(), 4);
+    let result = |actor_id: &str| {
+        results
+            .iter()
+            .find(|r| r.actor_id == actor_id)
+            .map(|r| r.result.as_ref())
+            .unwrap()
+    };
+    assert_eq!(result(&ok).unwrap(), b"ok:hello");
+    assert_eq!(result(&skipped).unwrap(), b"hello");
+    assert!(matches!(result(&failing), Err(RpcError::Rpc(e)) if e == "actor failed"));
+    assert!(matches!(result(&absent), Err(RpcError::Nats(_))));
+
+    // only actors selected by the filter receive the message
+    bridge.set_broadcast_concurrency(1);
+    let results = bridge
+        .broadcast_filtered(&ctx, message(), |ld| ld.actor_id != skipped)
+        .await;
+    let mut actors = results
+        .iter()
+        .map(|r| r.actor_id.clone())
+        .collect::<Vec<_>>();
+    actors.sort();
+    let mut expected = vec![ok.clone(), failing.clone(), absent.clone()];
+    expected.sort();
+    assert_eq!(actors, expected);
+    assert_eq!(
+        results
+            .iter()
+            .filter(|r| r.result.is_ok())
+            .map(|r| r.actor_id.as_str())
+            .collect::<Vec<_>>(),
+        [ok.as_str()]
+    );
+}
+
+/// connects a HostBridge for the EchoProvider, and waits for its subscriptions
+async fn connect_bridge(bus: &InProcessBus, host_data: &HostData) -> &'static HostBridge {
+    let bridge: &'static HostBridge = Box::leak(Box::new(
+        HostBridge::new_with_bus(Arc::new(bus.clone()), host_data).unwrap(),
+    ));
+    let (shutdown_tx, _shutdown_rx) = tokio::sync::oneshot::channel();
+    let _join = bridge
+        .connect(EchoProvider::default(), shutdown_tx)
+        .await
+        .unwrap();
+    wait_for_provider(bus, &host_data.provider_key).await;
+    bridge
+}
+
+#[tokio::test]
+async fn provider_to_provider() {
+    let bus = InProcessBus::default();
+    let issuer = KeyPair::new_cluster();
+    let test_data = HostData {
+        lattice_rpc_prefix: LATTICE_PREFIX.to_string(),
+        ..HostData::for_test()
+    };
+
+    // the receiving provider trusts the issuers of both senders
+    let receiver_key = KeyPair::new_service().public_key();
+    let receiver_data = HostData {
+        cluster_issuers: vec![issuer.public_key(), test_data.cluster_issuers[0].clone()],
+        ..bridge_host_data(&issuer, &receiver_key)
+    };
+    let receiver = connect_bridge(&bus, &receiver_data).await;
+
+    // senders with test host data, and with a host id that isn't a host key
+    let default_data = bridge_host_data(&issuer, &KeyPair::new_service().public_key());
+    assert_eq!(default_data.host_id, "");
+    for sender_data in [test_data, default_data] {
+        let sender = HostBridge::new_with_bus(Arc::new(bus.clone()), &sender_data).unwrap();
+        let resolver = Arc::new(StaticResolver::default());
+        resolver.add_provider(CONTRACT_ID, LINK_NAME, &receiver_key);
+        sender.set_resolver(resolver);
+        let ld = LinkDefinition {
+            actor_id: KeyPair::new_module().public_key(),
+            provider_id: sender_data.provider_key.clone(),
+            link_name: LINK_NAME.to_string(),
+            contract_id: "wasmcloud:sender".to_string(),
+            ..Default::default()
+        };
+        let transport = ProviderTransport::new_with_target(
+            &ld,
+            Some(&sender),
+            TransportTarget::Provider {
+                contract_id: CONTRACT_ID.to_string(),
+                link_name: LINK_NAME.to_string(),
+            },
+        );
+        let ctx = Context::default();
+        let send = || {
+            transport.send(
+                &ctx,
+                Message {
+                    method: "Echo.Echo",
+                    arg: Cow::Borrowed(b"hello"),
+                },
+                None,
+            )
+        };
+
+        // rejected until the receiver's resolver knows the sender
+        let err = send().await.unwrap_err();
+        assert!(
+            matches!(&err, RpcError::Rpc(e) if e.contains(&ld.provider_entity().url())),
+            "{}",
+            err
+        );
+        let resolver = Arc::new(StaticResolver::default());
+        resolver.add_provider("wasmcloud:sender", LINK_NAME, &sender_data.provider_key);
+        receiver.set_resolver(resolver);
+        assert_eq!(send().await.unwrap(), b"hello");
+    }
+}
+
+#[tokio::test]
+async fn inbound_payload_limit() {
+    let bus = InProcessBus::default();
+    let issuer = KeyPair::new_cluster();
+    let provider_key = KeyPair::new_service().public_key();
+    let actor_key = KeyPair::new_module().public_key();
+    let bridge = connect_bridge(&bus, &bridge_host_data(&issuer, &provider_key)).await;
+    bridge.set_max_inbound_payload(Some(4096));
+    let ld = LinkDefinition {
+        actor_id: actor_key.clone(),
+        provider_id: provider_key.clone(),
+        link_name: LINK_NAME.to_string(),
+        contract_id: CONTRACT_ID.to_string(),
+        ..Default::default()
+    };
+    bridge.put_link(ld.clone()).await;
+
+    let client = RpcClient::new_with_bus(
+        Arc::new(bus.clone()),
+        LATTICE_PREFIX,
+        issuer,
+        KeyPair::new_server().public_key(),
+        Some(TIMEOUT),
+    );
+    let send = |arg: Vec<u8>| {
+        client.send(
+            ld.actor_entity(),
+            ld.provider_entity(),
+            Message {
+                method: "Echo.Echo",
+                arg: Cow::Owned(arg),
+            },
+        )
+    };
+    assert_eq!(send(b"hello".to_vec()).await.unwrap(), b"hello");
+
+    // the receiver's limit is reported to the sender as PayloadTooLarge
+    let err = send(vec![0; 8192]).await.unwrap_err();
+    assert!(
+        matches!(err, RpcError::PayloadTooLarge { actual, allowed: 4096 } if actual > 8192),
+        "{}",
+        err
+    );
+}
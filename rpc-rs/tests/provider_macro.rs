@@ -0,0 +1,244 @@
+//! providers generated with the #[provider] attribute macro and #[derive(Provider)]
+#![cfg(test)]
+
+mod support;
+
+use serde::Deserialize;
+use std::borrow::Cow;
+use support::echo::{Echo, EchoReceiver, ECHO_CONTRACT_ID};
+use wasmbus_rpc::{
+    core::{HealthCheckRequest, HealthCheckResponse, HostData},
+    deserialize,
+    provider::prelude::*,
+    serialize, ServiceDescriptor, INTROSPECT_METHOD,
+};
+// the Receiver trait is found through an aliased module
+use support::echo as talk;
+
+#[derive(Default, Deserialize)]
+struct EchoConfig {
+    prefix: String,
+}
+
+// main runs the provider behind the middleware from make_stack
+#[provider(services(Echo), config = "EchoConfig", middleware = "make_stack")]
+#[derive(Clone)]
+struct EchoProvider {
+    prefix: String,
+}
+
+fn make_stack() -> wasmbus_rpc::middleware::MiddlewareStack {
+    wasmbus_rpc::middleware::MiddlewareStack::new()
+        .layer(wasmbus_rpc::middleware::LoggingLayer::default())
+}
+
+impl From<EchoConfig> for EchoProvider {
+    fn from(config: EchoConfig) -> Self {
+        EchoProvider {
+            prefix: config.prefix,
+        }
+    }
+}
+
+#[async_trait]
+impl Echo for EchoProvider {
+    async fn echo(&self, _ctx: &Context, arg: &String) -> RpcResult<String> {
+        Ok(format!("{}{}", self.prefix, arg))
+    }
+}
+
+/// the bounds provider_main_with requires of the provider
+fn assert_provider<P: ProviderDispatch + Send + Sync + Clone + 'static>(_: &P) {}
+
+#[tokio::test]
+async fn provider_from_config() -> RpcResult<()> {
+    // the generated entry point
+    let _: fn() -> Result<(), Box<dyn std::error::Error>> = main;
+
+    let host_data = HostData {
+        config_json: Some(r#"{ "prefix": "hello " }"#.to_string()),
+        ..Default::default()
+    };
+    let config = host_data.config::<EchoConfig>()?.unwrap_or_default();
+    let provider = EchoProvider::from(config);
+    assert_provider(&provider);
+
+    let ctx = Context::default();
+    let arg = serialize(&"world")?;
+    let resp = MessageDispatch::dispatch(
+        &provider,
+        &ctx,
+        Message {
+            method: "Echo.Echo",
+            arg: Cow::Borrowed(&arg),
+        },
+    )
+    .await?;
+    assert_eq!(resp.method, "Echo.Echo");
+    assert_eq!(deserialize::<String>(&resp.arg)?, "hello world");
+
+    let err = MessageDispatch::dispatch(
+        &provider,
+        &ctx,
+        Message {
+            method: "KeyValue.Get",
+            arg: Cow::Borrowed(&arg),
+        },
+    )
+    .await
+    .unwrap_err();
+    assert!(matches!(err, RpcError::MethodNotHandled(_)), "{}", err);
+
+    // the default ProviderHandler accepts links
+    assert!(provider.put_link(&LinkDefinition::default()).await?);
+    Ok(())
+}
+
+/// provider with qualified and renamed services, which lists their methods
+#[derive(Clone, Default, Provider)]
+#[services(talk::Echo as "Shout", wasmbus_rpc::core::Actor)]
+#[introspect]
+struct ShoutProvider {}
+
+impl ProviderDispatch for ShoutProvider {}
+
+#[async_trait]
+impl ProviderHandler for ShoutProvider {}
+
+#[async_trait]
+impl Echo for ShoutProvider {
+    async fn echo(&self, _ctx: &Context, arg: &String) -> RpcResult<String> {
+        Ok(arg.to_uppercase())
+    }
+}
+
+#[async_trait]
+impl wasmbus_rpc::core::Actor for ShoutProvider {
+    async fn health_request(
+        &self,
+        _ctx: &Context,
+        _arg: &HealthCheckRequest,
+    ) -> RpcResult<HealthCheckResponse> {
+        Ok(HealthCheckResponse {
+            healthy: true,
+            message: Some("shouting".to_string()),
+        })
+    }
+}
+
+#[tokio::test]
+async fn qualified_and_renamed_services() -> RpcResult<()> {
+    let provider = ShoutProvider::default();
+    let ctx = Context::default();
+
+    // dispatched by the name given with `as`
+    let arg = serialize(&"hello")?;
+    let resp = MessageDispatch::dispatch(
+        &provider,
+        &ctx,
+        Message {
+            method: "Shout.Echo",
+            arg: Cow::Borrowed(&arg),
+        },
+    )
+    .await?;
+    assert_eq!(deserialize::<String>(&resp.arg)?, "HELLO");
+
+    // the trait name isn't dispatched if the service is renamed
+    let err = MessageDispatch::dispatch(
+        &provider,
+        &ctx,
+        Message {
+            method: "Echo.Echo",
+            arg: Cow::Borrowed(&arg),
+        },
+    )
+    .await
+    .unwrap_err();
+    assert!(matches!(err, RpcError::MethodNotHandled(_)), "{}", err);
+
+    // dispatched by the last segment of the qualified path
+    let arg = serialize(&HealthCheckRequest::default())?;
+    let resp = MessageDispatch::dispatch(
+        &provider,
+        &ctx,
+        Message {
+            method: "Actor.HealthRequest",
+            arg: Cow::Borrowed(&arg),
+        },
+    )
+    .await?;
+    let health = deserialize::<HealthCheckResponse>(&resp.arg)?;
+    assert_eq!(health.message.as_deref(), Some("shouting"));
+    Ok(())
+}
+
+/// a service generated before codegen added `service_descriptor()` to Receivers
+mod legacy {
+    use wasmbus_rpc::{Context, Message, MessageDispatch, RpcError, RpcResult};
+
+    pub trait Ping {}
+
+    #[async_trait::async_trait]
+    pub trait PingReceiver: MessageDispatch + Ping {
+        async fn dispatch(&self, _ctx: &Context, message: &Message<'_>) -> RpcResult<Message<'_>> {
+            Err(RpcError::MethodNotHandled(format!(
+                "Ping::{}",
+                message.method
+            )))
+        }
+    }
+}
+
+/// without #[introspect], derived dispatch doesn't use `service_descriptor()`
+#[derive(Clone, Default, Provider)]
+#[services(legacy::Ping)]
+struct LegacyProvider {}
+
+impl legacy::Ping for LegacyProvider {}
+
+/// returns the provider's response to an introspection message
+async fn introspect_response<P: MessageDispatch>(
+    provider: &P,
+) -> RpcResult<Vec<ServiceDescriptor>> {
+    let resp = provider
+        .dispatch(
+            &Context::default(),
+            Message {
+                method: INTROSPECT_METHOD,
+                arg: Cow::Borrowed(&[]),
+            },
+        )
+        .await?;
+    deserialize(&resp.arg)
+}
+
+#[tokio::test]
+async fn introspect_services() -> RpcResult<()> {
+    let provider = ShoutProvider::default();
+    let services = MessageDispatch::services(&provider);
+    assert_eq!(introspect_response(&provider).await?, services);
+
+    let names = services.iter().map(|s| s.name.as_ref()).collect::<Vec<_>>();
+    assert_eq!(names, ["Shout", "Actor"]);
+    assert_eq!(services[0].contract_id.as_deref(), Some(ECHO_CONTRACT_ID));
+    assert!(services[0].has_method("Shout.Echo"));
+    assert!(!services[0].has_method("Echo.Echo"));
+    assert!(services[1].has_method("Actor.HealthRequest"));
+
+    // without #[introspect], services are listed without their methods
+    let provider = EchoProvider {
+        prefix: String::new(),
+    };
+    let services = MessageDispatch::services(&provider);
+    assert_eq!(introspect_response(&provider).await?, services);
+    assert_eq!(services.len(), 1);
+    assert_eq!(services[0].name, "Echo");
+    assert_eq!(services[0].contract_id, None);
+    assert!(services[0].methods.is_empty());
+
+    let services = introspect_response(&LegacyProvider::default()).await?;
+    assert_eq!(services.len(), 1);
+    assert_eq!(services[0].name, "Ping");
+    Ok(())
+}
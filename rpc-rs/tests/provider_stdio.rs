@@ -0,0 +1,91 @@
+//! run a provider in stdio mode, with a script of json requests
+#![cfg(test)]
+
+use async_trait::async_trait;
+use std::borrow::Cow;
+use wascap::prelude::KeyPair;
+use wasmbus_rpc::{
+    core::HostData,
+    provider::{prelude::provider_run_stdio, ProviderDispatch, ProviderHandler},
+    Context, Message, MessageDispatch, RpcError,
+};
+
+#[derive(Clone, Default)]
+struct EchoProvider {}
+
+impl ProviderDispatch for EchoProvider {}
+
+#[async_trait]
+impl ProviderHandler for EchoProvider {}
+
+#[async_trait]
+impl MessageDispatch for EchoProvider {
+    async fn dispatch(
+        &self,
+        _ctx: &Context,
+        message: Message<'_>,
+    ) -> Result<Message<'_>, RpcError> {
+        match message.method {
+            "Echo.Echo" => Ok(Message {
+                method: "Echo.Echo",
+                arg: Cow::Owned(message.arg.to_vec()),
+            }),
+            _ => Err(RpcError::MethodNotHandled(message.method.to_string())),
+        }
+    }
+}
+
+#[tokio::test]
+async fn stdio_script() {
+    let actor = KeyPair::new_module().public_key();
+    let script = format!(
+        r#"# link, then send messages
+{{"op":"link_put", "actor":"{actor}", "contract_id":"wasmcloud:testing"}}
+{{"op":"health"}}
+{{"id":1, "actor":"{actor}", "method":"Echo.Echo", "args":{{"greeting":"hello"}}}}
+{{"id":2, "actor":"{actor}", "method":"Echo.Missing"}}
+
+{{"op":"link_del", "actor":"{actor}"}}
+{{"id":3, "actor":"{actor}", "method":"Echo.Echo", "args":"hello"}}
+not json
+{{"op":"shutdown"}}
+"#,
+        actor = actor
+    );
+    let mut output = Vec::new();
+    provider_run_stdio(
+        EchoProvider::default(),
+        HostData::for_test(),
+        script.as_bytes(),
+        &mut output,
+    )
+    .await
+    .expect("provider run");
+
+    let responses = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(responses.len(), 8);
+    assert_eq!(responses[0]["result"], serde_json::Value::Null);
+    assert!(responses[0].get("error").is_none());
+    assert_eq!(responses[1]["result"]["healthy"], true);
+    assert_eq!(responses[2]["id"], 1);
+    assert_eq!(responses[2]["result"]["greeting"], "hello");
+    assert!(responses[3]["error"]
+        .as_str()
+        .unwrap()
+        .contains("Echo.Missing"));
+    assert!(responses[4].get("error").is_none());
+    // no longer linked
+    assert!(responses[5]["error"]
+        .as_str()
+        .unwrap()
+        .contains("unlinked actor"));
+    assert!(responses[6]["error"]
+        .as_str()
+        .unwrap()
+        .contains("invalid request"));
+    assert_eq!(responses[7]["result"], "shutting down");
+}
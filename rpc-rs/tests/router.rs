@@ -0,0 +1,131 @@
+//! dispatch messages to handlers registered with a Router
+#![cfg(test)]
+
+use std::borrow::Cow;
+use wasmbus_rpc::{
+    deserialize, introspect, router::Router, serialize, Context, Message, MessageDispatch,
+    RpcError, RpcResult, SendOpts, ServiceDescriptor, Transport,
+};
+
+/// transport that dispatches messages to a router in the same process
+struct Local(Router);
+
+#[async_trait::async_trait]
+impl Transport for Local {
+    async fn send(
+        &self,
+        ctx: &Context,
+        req: Message<'_>,
+        _opts: Option<SendOpts>,
+    ) -> RpcResult<Vec<u8>> {
+        Ok(self.0.dispatch(ctx, req).await?.arg.into_owned())
+    }
+
+    fn set_timeout(&self, _interval: std::time::Duration) {}
+}
+
+fn gateway(prefix: &str) -> Router {
+    let prefix = prefix.to_string();
+    Router::new()
+        .contract_id("wasmcloud:example:gateway")
+        .route("Gateway.Greet", move |_ctx: Context, name: String| {
+            let greeting = format!("{} {}", prefix, name);
+            async move { Ok(greeting) }
+        })
+        .route(
+            "Gateway.Sum",
+            |_ctx: Context, values: Vec<i32>| async move { Ok(values.iter().sum::<i32>()) },
+        )
+        .route_raw(
+            "Echo.Echo",
+            |_ctx: Context, arg: Vec<u8>| async move { Ok(arg) },
+        )
+}
+
+#[tokio::test]
+async fn route_messages() -> RpcResult<()> {
+    let router = gateway("hello");
+    let ctx = Context::default();
+
+    let arg = serialize(&"world")?;
+    let resp = router
+        .dispatch(
+            &ctx,
+            Message {
+                method: "Gateway.Greet",
+                arg: Cow::Borrowed(&arg),
+            },
+        )
+        .await?;
+    assert_eq!(deserialize::<String>(&resp.arg)?, "hello world");
+
+    let arg = serialize(&vec![1, 2, 3])?;
+    let resp = router
+        .dispatch(
+            &ctx,
+            Message {
+                method: "Gateway.Sum",
+                arg: Cow::Borrowed(&arg),
+            },
+        )
+        .await?;
+    assert_eq!(deserialize::<i32>(&resp.arg)?, 6);
+
+    let resp = router
+        .dispatch(
+            &ctx,
+            Message {
+                method: "Echo.Echo",
+                arg: Cow::Borrowed(b"raw bytes"),
+            },
+        )
+        .await?;
+    assert_eq!(resp.arg.as_ref(), b"raw bytes");
+
+    // an arg that doesn't deserialize to the handler's input type
+    let arg = serialize(&"not a list")?;
+    let err = router
+        .dispatch(
+            &ctx,
+            Message {
+                method: "Gateway.Sum",
+                arg: Cow::Borrowed(&arg),
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(err, RpcError::Deser(_)), "{}", err);
+
+    let err = router
+        .dispatch(
+            &ctx,
+            Message {
+                method: "Gateway.Missing",
+                arg: Cow::Borrowed(b""),
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(err, RpcError::MethodNotHandled(_)), "{}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn introspect_router() -> RpcResult<()> {
+    let mut router = gateway("hi");
+    assert!(router.remove("Gateway.Sum"));
+    assert!(!router.has_route("Gateway.Sum"));
+
+    let services = introspect(&Local(router.clone()), &Context::default()).await?;
+    assert_eq!(services, router.services());
+    let names = services.iter().map(|s| s.name.as_ref()).collect::<Vec<_>>();
+    assert_eq!(names, ["Echo", "Gateway"]);
+    let gateway: &ServiceDescriptor = &services[1];
+    assert_eq!(
+        gateway.contract_id.as_deref(),
+        Some("wasmcloud:example:gateway")
+    );
+    assert!(gateway.has_method("Gateway.Greet"));
+    assert!(!gateway.has_method("Gateway.Sum"));
+    Ok(())
+}
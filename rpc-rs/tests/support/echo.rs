@@ -0,0 +1,56 @@
+//! Echo service trait and receiver, as generated by weld-codegen
+//! for an interface with one operation, `Echo(String) -> String`
+#![allow(dead_code, clippy::ptr_arg)]
+
+use async_trait::async_trait;
+use std::borrow::Cow;
+use wasmbus_rpc::{
+    deserialize, serialize, Context, Message, MessageDispatch, MethodDescriptor, RpcError,
+    RpcResult, ServiceDescriptor,
+};
+
+/// contract id in the Echo service descriptor
+pub const ECHO_CONTRACT_ID: &str = "wasmcloud:testing:echo";
+
+#[async_trait]
+pub trait Echo {
+    async fn echo(&self, ctx: &Context, arg: &String) -> RpcResult<String>;
+}
+
+/// EchoReceiver receives messages defined in the Echo service trait
+#[doc(hidden)]
+#[async_trait]
+pub trait EchoReceiver: MessageDispatch + Echo {
+    async fn dispatch(&self, ctx: &Context, message: &Message<'_>) -> RpcResult<Message<'_>> {
+        match message.method {
+            "Echo" => {
+                let value: String = deserialize(message.arg.as_ref())
+                    .map_err(|e| RpcError::Deser(format!("message '{}': {}", message.method, e)))?;
+                let resp = Echo::echo(self, ctx, &value).await?;
+                let buf = serialize(&resp)?;
+                Ok(Message {
+                    method: "Echo.Echo",
+                    arg: Cow::Owned(buf),
+                })
+            }
+            _ => Err(RpcError::MethodNotHandled(format!(
+                "Echo::{}",
+                message.method
+            ))),
+        }
+    }
+
+    /// Describes the Echo service: its contract id and methods
+    fn service_descriptor(&self) -> &'static ServiceDescriptor {
+        static DESCRIPTOR: ServiceDescriptor = ServiceDescriptor {
+            name: Cow::Borrowed("Echo"),
+            contract_id: Some(Cow::Borrowed(ECHO_CONTRACT_ID)),
+            methods: Cow::Borrowed(&[MethodDescriptor {
+                name: Cow::Borrowed("Echo"),
+                input: Some(Cow::Borrowed("smithy.api#String")),
+                output: Some(Cow::Borrowed("smithy.api#String")),
+            }]),
+        };
+        &DESCRIPTOR
+    }
+}
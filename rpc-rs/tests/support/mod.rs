@@ -0,0 +1,3 @@
+//! shared support code for integration tests
+pub mod echo;
+pub mod nats_server;
@@ -0,0 +1,268 @@
+//! Minimal nats server for integration tests
+//!
+//! The server listens on a localhost port and implements enough of the
+//! nats client protocol for RpcClient and HostBridge:
+//! INFO, CONNECT, PING/PONG, SUB/UNSUB (with queue groups and auto-unsubscribe),
+//! PUB, and MSG delivery with reply subjects, so request-reply inboxes work.
+//! A message larger than max_payload is rejected with
+//! `-ERR 'Maximum Payload Violation'` and the connection is closed, as nats-server does.
+//!
+//! Not supported: authentication, tls, headers (HPUB), and clustering.
+//!
+//! ```ignore
+//! let server = TestNatsServer::start().await?;
+//! let client = RpcClient::builder().nats_url(server.url()).build().await?;
+//! ```
+#![allow(dead_code)]
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+    task::JoinHandle,
+};
+use wasmbus_rpc::bus::subject_matches;
+
+/// max_payload advertised by the server, unless set with `start_with_max_payload`
+pub const DEFAULT_MAX_PAYLOAD: usize = 1024 * 1024;
+
+/// A nats server running in the current tokio runtime.
+/// The server stops accepting connections when dropped.
+pub struct TestNatsServer {
+    addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl TestNatsServer {
+    /// Starts a server on an unused localhost port
+    pub async fn start() -> std::io::Result<Self> {
+        Self::start_with_max_payload(DEFAULT_MAX_PAYLOAD).await
+    }
+
+    /// Starts a server that limits messages to max_payload bytes
+    pub async fn start_with_max_payload(max_payload: usize) -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State::default()));
+        let task = tokio::spawn(async move {
+            let mut client_id = 0u64;
+            while let Ok((stream, _)) = listener.accept().await {
+                client_id += 1;
+                tokio::spawn(handle_client(
+                    stream,
+                    client_id,
+                    addr,
+                    max_payload,
+                    state.clone(),
+                ));
+            }
+        });
+        Ok(TestNatsServer { addr, task })
+    }
+
+    /// Returns the server url, for example "nats://127.0.0.1:43210"
+    pub fn url(&self) -> String {
+        format!("nats://{}", self.addr)
+    }
+
+    /// Returns the server's socket address
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for TestNatsServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[derive(Default)]
+struct State {
+    subs: Vec<Subscription>,
+    /// round-robin counters for queue groups, keyed by (subject, queue)
+    queues: HashMap<(String, String), usize>,
+}
+
+struct Subscription {
+    client_id: u64,
+    sid: String,
+    subject: String,
+    queue: Option<String>,
+    /// remaining messages before auto-unsubscribe
+    max_msgs: Option<u64>,
+    tx: mpsc::UnboundedSender<Vec<u8>>,
+}
+
+impl State {
+    /// delivers the message to matching subscriptions:
+    /// every plain subscription, and one member of each queue group
+    fn publish(&mut self, subject: &str, reply: Option<&str>, payload: &[u8]) {
+        let mut targets = Vec::new();
+        let mut groups: HashMap<(String, String), Vec<usize>> = HashMap::new();
+        for (i, sub) in self.subs.iter().enumerate() {
+            if !subject_matches(&sub.subject, subject) {
+                continue;
+            }
+            match &sub.queue {
+                Some(queue) => groups
+                    .entry((sub.subject.clone(), queue.clone()))
+                    .or_default()
+                    .push(i),
+                None => targets.push(i),
+            }
+        }
+        for (key, members) in groups.into_iter() {
+            let counter = self.queues.entry(key).or_default();
+            targets.push(members[*counter % members.len()]);
+            *counter = counter.wrapping_add(1);
+        }
+        for i in targets.into_iter() {
+            let sub = &mut self.subs[i];
+            let mut frame = match reply {
+                Some(reply) => format!(
+                    "MSG {} {} {} {}\r\n",
+                    subject,
+                    &sub.sid,
+                    reply,
+                    payload.len()
+                ),
+                None => format!("MSG {} {} {}\r\n", subject, &sub.sid, payload.len()),
+            }
+            .into_bytes();
+            frame.extend_from_slice(payload);
+            frame.extend_from_slice(b"\r\n");
+            let _ = sub.tx.send(frame);
+            if let Some(remaining) = sub.max_msgs.as_mut() {
+                *remaining = remaining.saturating_sub(1);
+            }
+        }
+        self.subs.retain(|s| s.max_msgs != Some(0));
+    }
+
+    fn unsubscribe(&mut self, client_id: u64, sid: &str, max_msgs: Option<u64>) {
+        match max_msgs {
+            Some(max) => {
+                for sub in self
+                    .subs
+                    .iter_mut()
+                    .filter(|s| s.client_id == client_id && s.sid == sid)
+                {
+                    sub.max_msgs = Some(max);
+                }
+                self.subs.retain(|s| s.max_msgs != Some(0));
+            }
+            None => self
+                .subs
+                .retain(|s| !(s.client_id == client_id && s.sid == sid)),
+        }
+    }
+}
+
+async fn handle_client(
+    stream: TcpStream,
+    client_id: u64,
+    addr: SocketAddr,
+    max_payload: usize,
+    state: Arc<Mutex<State>>,
+) {
+    let (read, mut write) = stream.into_split();
+    // all writes to the client go through this channel, so that messages
+    // published by other clients are not interleaved with responses
+    let (tx, mut rx) = mpsc::unbounded_channel::<Vec<u8>>();
+    let writer = tokio::spawn(async move {
+        while let Some(buf) = rx.recv().await {
+            if write.write_all(&buf).await.is_err() {
+                break;
+            }
+        }
+    });
+    let info = format!(
+        "INFO {{\"server_id\":\"test\",\"server_name\":\"test\",\"version\":\"2.6.0\",\
+         \"proto\":1,\"go\":\"go1.17\",\"host\":\"{}\",\"port\":{},\"headers\":false,\"max_payload\":{},\
+         \"client_id\":{}}}\r\n",
+        addr.ip(),
+        addr.port(),
+        max_payload,
+        client_id
+    );
+    let _ = tx.send(info.into_bytes());
+
+    let mut reader = BufReader::new(read);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        let mut parts = line.split_whitespace();
+        let op = parts.next().unwrap_or_default().to_ascii_uppercase();
+        let args = parts.collect::<Vec<&str>>();
+        match (op.as_str(), args.as_slice()) {
+            ("", _) | ("CONNECT", _) | ("PONG", _) => {}
+            ("PING", _) => {
+                let _ = tx.send(b"PONG\r\n".to_vec());
+            }
+            ("SUB", [subject, sid]) | ("SUB", [subject, _, sid]) => {
+                let queue = if args.len() == 3 {
+                    Some(args[1].to_string())
+                } else {
+                    None
+                };
+                state.lock().unwrap().subs.push(Subscription {
+                    client_id,
+                    sid: sid.to_string(),
+                    subject: subject.to_string(),
+                    queue,
+                    max_msgs: None,
+                    tx: tx.clone(),
+                });
+            }
+            ("UNSUB", [sid]) => state.lock().unwrap().unsubscribe(client_id, sid, None),
+            ("UNSUB", [sid, max]) => {
+                let max = max.parse::<u64>().ok();
+                state.lock().unwrap().unsubscribe(client_id, sid, max)
+            }
+            ("PUB", [subject, size]) | ("PUB", [subject, _, size]) => {
+                let reply = if args.len() == 3 { Some(args[1]) } else { None };
+                let size = match size.parse::<usize>() {
+                    Ok(size) => size,
+                    Err(_) => {
+                        let _ = tx.send(b"-ERR 'Invalid Message Size'\r\n".to_vec());
+                        break;
+                    }
+                };
+                if size > max_payload {
+                    let _ = tx.send(b"-ERR 'Maximum Payload Violation'\r\n".to_vec());
+                    break;
+                }
+                // payload is followed by \r\n
+                let mut payload = vec![0u8; size + 2];
+                if reader.read_exact(&mut payload).await.is_err() {
+                    break;
+                }
+                payload.truncate(size);
+                state.lock().unwrap().publish(subject, reply, &payload);
+            }
+            _ => {
+                let _ = tx.send(b"-ERR 'Unknown Protocol Operation'\r\n".to_vec());
+                break;
+            }
+        }
+    }
+    // remove this client's subscriptions, which drops their senders,
+    // then let the writer flush any remaining output before closing
+    state
+        .lock()
+        .unwrap()
+        .subs
+        .retain(|s| s.client_id != client_id);
+    drop(tx);
+    let _ = writer.await;
+}
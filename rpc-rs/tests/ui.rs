@@ -0,0 +1,8 @@
+//! compile errors reported by the derive and attribute macros
+#![cfg(test)]
+
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
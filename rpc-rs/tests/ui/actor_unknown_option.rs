@@ -0,0 +1,8 @@
+use wasmbus_rpc::actor::prelude::*;
+
+#[derive(Actor, Default)]
+#[services(Actor)]
+#[actor(init = "make_actor", threads = 4)]
+struct HealthActor {}
+
+fn main() {}
@@ -0,0 +1,15 @@
+// the services are not used after the error
+#![allow(unused_imports)]
+
+use wasmbus_rpc::{core::Actor, provider::prelude::*};
+
+mod health {
+    pub use wasmbus_rpc::core::{Actor, ActorReceiver};
+}
+
+// both services are dispatched by the name "Actor"
+#[derive(Clone, Default, Provider)]
+#[services(Actor, health::Actor)]
+struct HealthProvider {}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use wasmbus_rpc::{
+    core::{Actor, ActorReceiver},
+    provider::prelude::*,
+};
+
+// the provider lists the Actor service, but doesn't implement it
+#[provider(services(Actor))]
+#[derive(Clone, Default)]
+struct HealthProvider {}
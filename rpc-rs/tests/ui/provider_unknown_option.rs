@@ -0,0 +1,7 @@
+use wasmbus_rpc::provider::prelude::*;
+
+#[provider(threads = 4)]
+#[derive(Clone, Default)]
+struct EmptyProvider {}
+
+fn main() {}
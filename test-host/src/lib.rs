@@ -0,0 +1,875 @@
+//! Test host for compiled wasmbus actors
+//!
+//! [TestHost] loads an actor's `.wasm` file with an embedded runtime (wasmtime),
+//! and implements the `wasmbus` imports that a wasmCloud host would provide.
+//! Messages the actor sends to capability providers are dispatched to mock
+//! [MessageDispatch] implementations, keyed by link name and contract id,
+//! and messages to other actors are dispatched to mocks keyed by public key or call alias.
+//!
+//! ```ignore
+//! let host = TestHost::builder()
+//!     .provider("default", "wasmcloud:keyvalue", Arc::new(MockKeyValue::default()))
+//!     .build_from_file("target/wasm32-unknown-unknown/release/my_actor.wasm")?;
+//! let resp = host.call("HttpServer.HandleRequest", &serialize(&request)?).await?;
+//! ```
+//!
+//! One actor instance is created per TestHost, and reused for each call,
+//! as it is in a wasmCloud host. Actors that support it receive the origin,
+//! invocation id, and trace context passed to [TestHost::call_with_context].
+
+use async_trait::async_trait;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use wasmbus_rpc::{
+    Context, HostCallOpts, HostContext, Message, MessageDispatch, RpcError, RpcResult, SendOpts,
+    Transport, WASMBUS_CONTEXT_API_VERSION, WASMBUS_HOST_CALL_OPTS_API_VERSION,
+};
+use wasmtime::{Caller, Config, Engine, Linker, Memory, Module, Store, TypedFunc};
+
+/// wasm import module for host functions
+const WASMBUS_MODULE: &str = "wasmbus";
+
+/// public key of the actor under test, unless set with `TestHostBuilder::actor_id`
+pub const DEFAULT_ACTOR_ID: &str = "MDNVOT5ZDVAESLL4E3CCNQVBUPWEDF5LM2LRGBK3GF4UWWHF57FU47GY";
+
+/// A mock actor or capability provider
+pub type MockDispatch = Arc<dyn MessageDispatch + Send + Sync>;
+
+/// Builder for [TestHost]
+pub struct TestHostBuilder {
+    providers: HashMap<(String, String), MockDispatch>,
+    actors: HashMap<String, MockDispatch>,
+    actor_id: String,
+    timeout: Option<Duration>,
+    log_level: Option<log::LevelFilter>,
+}
+
+impl Default for TestHostBuilder {
+    fn default() -> Self {
+        TestHostBuilder {
+            providers: HashMap::new(),
+            actors: HashMap::new(),
+            actor_id: DEFAULT_ACTOR_ID.to_string(),
+            timeout: None,
+            log_level: None,
+        }
+    }
+}
+
+impl TestHostBuilder {
+    /// Adds a mock capability provider, which receives the actor's messages
+    /// sent to the link name and contract id
+    #[must_use]
+    pub fn provider<T1: ToString, T2: ToString>(
+        mut self,
+        link_name: T1,
+        contract_id: T2,
+        dispatch: MockDispatch,
+    ) -> Self {
+        self.providers
+            .insert((link_name.to_string(), contract_id.to_string()), dispatch);
+        self
+    }
+
+    /// Adds a mock actor, which receives the actor's messages sent to the id
+    /// (public key or call alias)
+    #[must_use]
+    pub fn actor<T: ToString>(mut self, id: T, dispatch: MockDispatch) -> Self {
+        self.actors.insert(id.to_string(), dispatch);
+        self
+    }
+
+    /// Sets the public key of the actor under test. Mocks receive it in `Context::actor`
+    #[must_use]
+    pub fn actor_id<T: ToString>(mut self, id: T) -> Self {
+        self.actor_id = id.to_string();
+        self
+    }
+
+    /// Sets the time limit for each call to the actor. None waits indefinitely (the default).
+    /// An actor still running when the time is up is interrupted: it traps,
+    /// and later calls use the same instance.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the maximum level of `log` records the actor sends to the host.
+    /// If None, the actor's default is used ("info"). Requires an actor that accepts a HostContext.
+    #[must_use]
+    pub fn log_level(mut self, level: Option<log::LevelFilter>) -> Self {
+        self.log_level = level;
+        self
+    }
+
+    /// Loads the actor from a `.wasm` file and returns the host
+    pub fn build_from_file<P: AsRef<Path>>(self, path: P) -> RpcResult<TestHost> {
+        let wasm = std::fs::read(path.as_ref()).map_err(|e| {
+            RpcError::InvalidParameter(format!("reading actor {}: {}", path.as_ref().display(), e))
+        })?;
+        self.build(&wasm)
+    }
+
+    /// Loads the actor from wasm binary (or text) and returns the host
+    pub fn build(self, wasm: &[u8]) -> RpcResult<TestHost> {
+        // epochs let a call that times out interrupt the actor
+        let engine = Engine::new(Config::new().epoch_interruption(true))
+            .map_err(|e| RpcError::HostError(format!("creating wasm engine: {}", e)))?;
+        let module = Module::new(&engine, wasm)
+            .map_err(|e| RpcError::InvalidParameter(format!("invalid actor module: {}", e)))?;
+        let logs = Arc::new(Mutex::new(Vec::new()));
+        let state = HostState {
+            providers: self.providers,
+            actors: self.actors,
+            actor_id: self.actor_id,
+            logs: logs.clone(),
+            handle: None,
+            op: String::new(),
+            request: Vec::new(),
+            guest_response: None,
+            guest_error: None,
+            host_response: Vec::new(),
+            host_error: String::new(),
+            host_call_opts: false,
+        };
+        let mut store = Store::new(&engine, state);
+        store.set_epoch_deadline(1);
+        let linker = make_linker(&engine)?;
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .map_err(|e| RpcError::InvalidParameter(format!("instantiating actor: {}", e)))?;
+        let guest_call = instance
+            .get_typed_func::<(i32, i32), i32, _>(&mut store, "__guest_call")
+            .map_err(|e| {
+                RpcError::InvalidParameter(format!("actor does not export __guest_call: {}", e))
+            })?;
+        // actors built before the context api don't export __guest_context
+        let api_version =
+            match instance.get_typed_func::<(), u32, _>(&mut store, "__actor_api_version") {
+                Ok(func) => func.call(&mut store, ()).map_err(|e| {
+                    RpcError::InvalidParameter(format!("actor __actor_api_version failed: {}", e))
+                })?,
+                Err(_) => 0,
+            };
+        store.data_mut().host_call_opts = api_version >= WASMBUS_HOST_CALL_OPTS_API_VERSION;
+        let guest_context = if api_version >= WASMBUS_CONTEXT_API_VERSION {
+            let func = instance
+                .get_typed_func::<i32, i32, _>(&mut store, "__guest_context")
+                .map_err(|e| {
+                    RpcError::InvalidParameter(format!(
+                        "actor api version {} requires __guest_context: {}",
+                        api_version, e
+                    ))
+                })?;
+            let memory = instance.get_memory(&mut store, "memory").ok_or_else(|| {
+                RpcError::InvalidParameter("actor does not export memory".to_string())
+            })?;
+            Some((func, memory))
+        } else {
+            None
+        };
+        Ok(TestHost {
+            engine,
+            instance: Arc::new(Mutex::new(ActorInstance {
+                store,
+                guest_call,
+                guest_context,
+            })),
+            logs,
+            timeout: Arc::new(Mutex::new(self.timeout)),
+            log_level: self.log_level,
+        })
+    }
+}
+
+/// Runs a compiled actor, with mock providers and actors.
+/// Clones share the same actor instance.
+#[derive(Clone)]
+pub struct TestHost {
+    engine: Engine,
+    instance: Arc<Mutex<ActorInstance>>,
+    logs: Arc<Mutex<Vec<String>>>,
+    timeout: Arc<Mutex<Option<Duration>>>,
+    log_level: Option<log::LevelFilter>,
+}
+
+impl TestHost {
+    /// Returns a builder for adding mocks and loading the actor
+    pub fn builder() -> TestHostBuilder {
+        TestHostBuilder::default()
+    }
+
+    /// Sends a message to the actor, and returns its response.
+    /// The arg must already be serialized, for example, with `wasmbus_rpc::serialize`.
+    /// Calls are processed one at a time.
+    pub async fn call(&self, method: &str, arg: &[u8]) -> RpcResult<Vec<u8>> {
+        self.call_with_context(&Context::default(), method, arg)
+            .await
+    }
+
+    /// Sends a message to the actor, with the origin, invocation id, and trace context
+    /// from `ctx`, and returns its response. Actors built with an api version
+    /// before `WASMBUS_CONTEXT_API_VERSION` receive a default Context.
+    pub async fn call_with_context(
+        &self,
+        ctx: &Context,
+        method: &str,
+        arg: &[u8],
+    ) -> RpcResult<Vec<u8>> {
+        let instance = self.instance.clone();
+        let handle = tokio::runtime::Handle::current();
+        let host_context = wasmbus_rpc::serialize(&HostContext {
+            log_level: self.log_level.map(|level| level.to_string()),
+            ..HostContext::from(ctx)
+        })?;
+        let (op, arg) = (method.to_string(), arg.to_vec());
+        let state = Arc::new(Mutex::new(CallState::Waiting));
+        let call_state = state.clone();
+        // the actor runs on a blocking thread, so that mocks can be awaited from host functions
+        let task = tokio::task::spawn_blocking(move || {
+            let mut instance = instance
+                .lock()
+                .map_err(|_| RpcError::HostError("actor instance panicked".to_string()))?;
+            // the deadline is reached if the call times out while running
+            instance.store.set_epoch_deadline(1);
+            {
+                let mut state = call_state.lock().unwrap();
+                if *state == CallState::Done {
+                    return Err(RpcError::Timeout("actor call".to_string()));
+                }
+                *state = CallState::Running;
+            }
+            let result = instance.guest_call(handle, &host_context, op, arg);
+            *call_state.lock().unwrap() = CallState::Done;
+            result
+        });
+        let timeout = *self.timeout.lock().unwrap();
+        let joined = match timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, task).await {
+                Ok(joined) => joined,
+                Err(_) => {
+                    // interrupt the actor if it's running, or cancel the call if it hasn't started
+                    let mut state = state.lock().unwrap();
+                    if *state == CallState::Running {
+                        self.engine.increment_epoch();
+                    }
+                    *state = CallState::Done;
+                    return Err(RpcError::Timeout(format!("actor call {}", method)));
+                }
+            },
+            None => task.await,
+        };
+        joined.map_err(|e| RpcError::HostError(format!("actor call {}: {}", method, e)))?
+    }
+
+    /// Returns the messages the actor has logged with `console_log` or the `log` crate
+    pub fn logs(&self) -> Vec<String> {
+        self.logs.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Transport for TestHost {
+    async fn send(
+        &self,
+        ctx: &Context,
+        req: Message<'_>,
+        _opts: Option<SendOpts>,
+    ) -> RpcResult<Vec<u8>> {
+        self.call_with_context(ctx, req.method, &req.arg).await
+    }
+
+    fn set_timeout(&self, interval: Duration) {
+        *self.timeout.lock().unwrap() = Some(interval);
+    }
+}
+
+/// progress of a call, so that a call that times out interrupts only its own actor call
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CallState {
+    Waiting,
+    Running,
+    Done,
+}
+
+struct ActorInstance {
+    store: Store<HostState>,
+    guest_call: TypedFunc<(i32, i32), i32>,
+    /// `__guest_context` export and memory, if the actor accepts a HostContext
+    guest_context: Option<(TypedFunc<i32, i32>, Memory)>,
+}
+
+impl ActorInstance {
+    fn guest_call(
+        &mut self,
+        handle: tokio::runtime::Handle,
+        host_context: &[u8],
+        op: String,
+        arg: Vec<u8>,
+    ) -> RpcResult<Vec<u8>> {
+        if let Some((guest_context, memory)) = self.guest_context.as_ref() {
+            let ptr = guest_context
+                .call(&mut self.store, host_context.len() as i32)
+                .map_err(|e| RpcError::ActorHandler(format!("actor trapped: {}", e)))?;
+            memory
+                .write(&mut self.store, ptr as u32 as usize, host_context)
+                .map_err(|e| RpcError::HostError(format!("writing context: {}", e)))?;
+        }
+        let (op_len, req_len) = (op.len() as i32, arg.len() as i32);
+        let state = self.store.data_mut();
+        state.handle = Some(handle);
+        state.op = op;
+        state.request = arg;
+        state.guest_response = None;
+        state.guest_error = None;
+        let result = match self.guest_call.call(&mut self.store, (op_len, req_len)) {
+            Ok(result) => result,
+            Err(trap) => {
+                let state = self.store.data_mut();
+                state.handle = None;
+                // actors report panics with __guest_error before trapping
+                return Err(RpcError::ActorHandler(match state.guest_error.take() {
+                    Some(error) => error,
+                    None => format!("actor trapped: {}", trap),
+                }));
+            }
+        };
+        let state = self.store.data_mut();
+        state.handle = None;
+        match (
+            result,
+            state.guest_response.take(),
+            state.guest_error.take(),
+        ) {
+            (1, resp, _) => Ok(resp.unwrap_or_default()),
+            (_, _, Some(error)) => Err(RpcError::ActorHandler(error)),
+            _ => Err(RpcError::ActorHandler(
+                "actor call failed without an error message".to_string(),
+            )),
+        }
+    }
+}
+
+struct HostState {
+    providers: HashMap<(String, String), MockDispatch>,
+    actors: HashMap<String, MockDispatch>,
+    actor_id: String,
+    logs: Arc<Mutex<Vec<String>>>,
+    /// runtime for awaiting mocks, set during a call
+    handle: Option<tokio::runtime::Handle>,
+    /// method and arg of the current call
+    op: String,
+    request: Vec<u8>,
+    guest_response: Option<Vec<u8>>,
+    guest_error: Option<String>,
+    /// result of the actor's most recent host call
+    host_response: Vec<u8>,
+    host_error: String,
+    /// the actor exports `__host_call_opts`
+    host_call_opts: bool,
+}
+
+impl HostState {
+    /// Sends the actor's message to a mock. Provider targets have a binding (link name)
+    /// and namespace (contract id); actor targets have no binding, and the namespace is the actor id.
+    fn host_call(
+        &self,
+        binding: &str,
+        namespace: &str,
+        op: &str,
+        arg: Vec<u8>,
+        opts: HostCallOpts,
+    ) -> RpcResult<Vec<u8>> {
+        let mock = if binding.is_empty() {
+            self.actors
+                .get(namespace)
+                .ok_or_else(|| RpcError::HostError(format!("no mock actor '{}'", namespace)))?
+        } else {
+            self.providers
+                .get(&(binding.to_string(), namespace.to_string()))
+                .ok_or_else(|| {
+                    RpcError::HostError(format!(
+                        "no mock provider for link name '{}' and contract id '{}'",
+                        binding, namespace
+                    ))
+                })?
+        };
+        let handle = self
+            .handle
+            .as_ref()
+            .ok_or_else(|| RpcError::HostError("host call outside of actor call".to_string()))?;
+        let ctx = Context {
+            actor: Some(self.actor_id.clone()),
+            trace_context: opts.trace_context,
+            ..Default::default()
+        };
+        let dispatch = mock.dispatch(
+            &ctx,
+            Message {
+                method: op,
+                arg: Cow::Owned(arg),
+            },
+        );
+        let resp = match opts.timeout_ms {
+            Some(ms) => handle
+                .block_on(tokio::time::timeout(Duration::from_millis(ms), dispatch))
+                .map_err(|_| RpcError::Timeout(format!("host call {}", op)))?,
+            None => handle.block_on(dispatch),
+        }?;
+        Ok(resp.arg.to_vec())
+    }
+}
+
+fn memory(caller: &mut Caller<'_, HostState>) -> Option<Memory> {
+    caller.get_export("memory").and_then(|e| e.into_memory())
+}
+
+fn read_bytes(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> Option<Vec<u8>> {
+    let memory = memory(caller)?;
+    let (ptr, len) = (ptr as u32 as usize, len as u32 as usize);
+    if ptr.checked_add(len)? > memory.data_size(&*caller) {
+        return None;
+    }
+    let mut buf = vec![0u8; len];
+    memory.read(&*caller, ptr, &mut buf).ok()?;
+    Some(buf)
+}
+
+fn read_string(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> Option<String> {
+    read_bytes(caller, ptr, len).map(|b| String::from_utf8_lossy(&b).to_string())
+}
+
+/// Reads the options for the actor's host call in progress, if the actor provides them
+fn read_host_call_opts(caller: &mut Caller<'_, HostState>) -> HostCallOpts {
+    if !caller.data().host_call_opts {
+        return HostCallOpts::default();
+    }
+    let mut call_export = |name: &str| -> Option<i32> {
+        let func = caller.get_export(name)?.into_func()?;
+        let func = func.typed::<(), i32, _>(&*caller).ok()?;
+        func.call(&mut *caller, ()).ok()
+    };
+    let (len, ptr) = match (
+        call_export("__host_call_opts_len"),
+        call_export("__host_call_opts"),
+    ) {
+        (Some(len), Some(ptr)) => (len, ptr),
+        _ => {
+            log::error!("actor api version requires __host_call_opts exports");
+            return HostCallOpts::default();
+        }
+    };
+    if len == 0 {
+        return HostCallOpts::default();
+    }
+    read_bytes(caller, ptr, len)
+        .and_then(|buf| wasmbus_rpc::deserialize(&buf).ok())
+        .unwrap_or_default()
+}
+
+fn write_bytes(caller: &mut Caller<'_, HostState>, ptr: i32, data: &[u8]) {
+    if let Some(memory) = memory(caller) {
+        if let Err(e) = memory.write(&mut *caller, ptr as u32 as usize, data) {
+            log::error!("writing to actor memory: {}", e);
+        }
+    }
+}
+
+/// Defines the wasmbus host functions
+fn make_linker(engine: &Engine) -> RpcResult<Linker<HostState>> {
+    fn err<E: std::fmt::Display>(e: E) -> RpcError {
+        RpcError::Other(format!("defining host functions: {}", e))
+    }
+    let mut linker = Linker::new(engine);
+
+    linker
+        .func_wrap(
+            WASMBUS_MODULE,
+            "__guest_request",
+            |mut caller: Caller<'_, HostState>, op_ptr: i32, ptr: i32| {
+                let (op, request) = {
+                    let state = caller.data();
+                    (state.op.clone(), state.request.clone())
+                };
+                write_bytes(&mut caller, op_ptr, op.as_bytes());
+                write_bytes(&mut caller, ptr, &request);
+            },
+        )
+        .map_err(err)?;
+    linker
+        .func_wrap(
+            WASMBUS_MODULE,
+            "__guest_response",
+            |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+                let resp = read_bytes(&mut caller, ptr, len);
+                caller.data_mut().guest_response = resp;
+            },
+        )
+        .map_err(err)?;
+    linker
+        .func_wrap(
+            WASMBUS_MODULE,
+            "__guest_error",
+            |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+                let error = read_string(&mut caller, ptr, len);
+                caller.data_mut().guest_error = error;
+            },
+        )
+        .map_err(err)?;
+    linker
+        .func_wrap(
+            WASMBUS_MODULE,
+            "__host_call",
+            |mut caller: Caller<'_, HostState>,
+             bd_ptr: i32,
+             bd_len: i32,
+             ns_ptr: i32,
+             ns_len: i32,
+             op_ptr: i32,
+             op_len: i32,
+             ptr: i32,
+             len: i32|
+             -> i32 {
+                let args = (
+                    read_string(&mut caller, bd_ptr, bd_len),
+                    read_string(&mut caller, ns_ptr, ns_len),
+                    read_string(&mut caller, op_ptr, op_len),
+                    read_bytes(&mut caller, ptr, len),
+                );
+                let opts = read_host_call_opts(&mut caller);
+                let result = match args {
+                    (Some(binding), Some(namespace), Some(op), Some(arg)) => caller
+                        .data()
+                        .host_call(&binding, &namespace, &op, arg, opts),
+                    _ => Err(RpcError::HostError(
+                        "host call arguments out of bounds".to_string(),
+                    )),
+                };
+                let state = caller.data_mut();
+                match result {
+                    Ok(resp) => {
+                        state.host_response = resp;
+                        state.host_error.clear();
+                        1
+                    }
+                    Err(e) => {
+                        state.host_response.clear();
+                        state.host_error = e.to_string();
+                        0
+                    }
+                }
+            },
+        )
+        .map_err(err)?;
+    linker
+        .func_wrap(
+            WASMBUS_MODULE,
+            "__host_response",
+            |mut caller: Caller<'_, HostState>, ptr: i32| {
+                let resp = caller.data().host_response.clone();
+                write_bytes(&mut caller, ptr, &resp);
+            },
+        )
+        .map_err(err)?;
+    linker
+        .func_wrap(
+            WASMBUS_MODULE,
+            "__host_response_len",
+            |caller: Caller<'_, HostState>| -> i32 { caller.data().host_response.len() as i32 },
+        )
+        .map_err(err)?;
+    linker
+        .func_wrap(
+            WASMBUS_MODULE,
+            "__host_error",
+            |mut caller: Caller<'_, HostState>, ptr: i32| {
+                let error = caller.data().host_error.clone();
+                write_bytes(&mut caller, ptr, error.as_bytes());
+            },
+        )
+        .map_err(err)?;
+    linker
+        .func_wrap(
+            WASMBUS_MODULE,
+            "__host_error_len",
+            |caller: Caller<'_, HostState>| -> i32 { caller.data().host_error.len() as i32 },
+        )
+        .map_err(err)?;
+    linker
+        .func_wrap(
+            WASMBUS_MODULE,
+            "__console_log",
+            |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+                if let Some(line) = read_string(&mut caller, ptr, len) {
+                    log::info!("actor: {}", &line);
+                    caller.data().logs.lock().unwrap().push(line);
+                }
+            },
+        )
+        .map_err(err)?;
+    Ok(linker)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// actor that logs a message, then forwards its request to the
+    /// "wasmcloud:test" provider and returns the provider's response
+    const FORWARDING_ACTOR: &str = r#"
+    (module
+      (import "wasmbus" "__guest_request" (func $guest_request (param i32 i32)))
+      (import "wasmbus" "__guest_response" (func $guest_response (param i32 i32)))
+      (import "wasmbus" "__guest_error" (func $guest_error (param i32 i32)))
+      (import "wasmbus" "__host_call"
+        (func $host_call (param i32 i32 i32 i32 i32 i32 i32 i32) (result i32)))
+      (import "wasmbus" "__host_response" (func $host_response (param i32)))
+      (import "wasmbus" "__host_response_len" (func $host_response_len (result i32)))
+      (import "wasmbus" "__host_error" (func $host_error (param i32)))
+      (import "wasmbus" "__host_error_len" (func $host_error_len (result i32)))
+      (import "wasmbus" "__console_log" (func $console_log (param i32 i32)))
+      (memory (export "memory") 1)
+      (data (i32.const 0) "default")
+      (data (i32.const 16) "wasmcloud:test")
+      (data (i32.const 32) "Test.Upper")
+      (data (i32.const 48) "forwarding")
+      (func (export "__guest_call") (param $op_len i32) (param $req_len i32) (result i32)
+        (call $guest_request (i32.const 1024) (i32.const 2048))
+        (call $console_log (i32.const 48) (i32.const 10))
+        (if (i32.eqz (call $host_call
+              (i32.const 0) (i32.const 7) (i32.const 16) (i32.const 14)
+              (i32.const 32) (i32.const 10) (i32.const 2048) (local.get $req_len)))
+          (then
+            (call $host_error (i32.const 4096))
+            (call $guest_error (i32.const 4096) (call $host_error_len))
+            (return (i32.const 0))))
+        (call $host_response (i32.const 4096))
+        (call $guest_response (i32.const 4096) (call $host_response_len))
+        (i32.const 1)))
+    "#;
+
+    /// api version 1 actor that returns the serialized HostContext it received
+    const CONTEXT_ACTOR: &str = r#"
+    (module
+      (import "wasmbus" "__guest_request" (func $guest_request (param i32 i32)))
+      (import "wasmbus" "__guest_response" (func $guest_response (param i32 i32)))
+      (memory (export "memory") 1)
+      (global $context_len (mut i32) (i32.const 0))
+      (func (export "__actor_api_version") (result i32) (i32.const 1))
+      (func (export "__guest_context") (param $len i32) (result i32)
+        (global.set $context_len (local.get $len))
+        (i32.const 8192))
+      (func (export "__guest_call") (param $op_len i32) (param $req_len i32) (result i32)
+        (call $guest_request (i32.const 1024) (i32.const 2048))
+        (call $guest_response (i32.const 8192) (global.get $context_len))
+        (i32.const 1)))
+    "#;
+
+    /// api version 2 actor that forwards its request to the "wasmcloud:test" provider,
+    /// with host call options in a data segment
+    const OPTS_ACTOR: &str = r#"
+    (module
+      (import "wasmbus" "__guest_request" (func $guest_request (param i32 i32)))
+      (import "wasmbus" "__guest_response" (func $guest_response (param i32 i32)))
+      (import "wasmbus" "__guest_error" (func $guest_error (param i32 i32)))
+      (import "wasmbus" "__host_call"
+        (func $host_call (param i32 i32 i32 i32 i32 i32 i32 i32) (result i32)))
+      (import "wasmbus" "__host_response" (func $host_response (param i32)))
+      (import "wasmbus" "__host_response_len" (func $host_response_len (result i32)))
+      (import "wasmbus" "__host_error" (func $host_error (param i32)))
+      (import "wasmbus" "__host_error_len" (func $host_error_len (result i32)))
+      (memory (export "memory") 1)
+      (data (i32.const 0) "default")
+      (data (i32.const 16) "wasmcloud:test")
+      (data (i32.const 8192) "OPTS")
+      (func (export "__actor_api_version") (result i32) (i32.const 2))
+      (func (export "__guest_context") (param $len i32) (result i32) (i32.const 12288))
+      (func (export "__host_call_opts_len") (result i32) (i32.const OPTS_LEN))
+      (func (export "__host_call_opts") (result i32) (i32.const 8192))
+      (func (export "__guest_call") (param $op_len i32) (param $req_len i32) (result i32)
+        (call $guest_request (i32.const 1024) (i32.const 2048))
+        (if (i32.eqz (call $host_call
+              (i32.const 0) (i32.const 7) (i32.const 16) (i32.const 14)
+              (i32.const 1024) (local.get $op_len) (i32.const 2048) (local.get $req_len)))
+          (then
+            (call $host_error (i32.const 4096))
+            (call $guest_error (i32.const 4096) (call $host_error_len))
+            (return (i32.const 0))))
+        (call $host_response (i32.const 4096))
+        (call $guest_response (i32.const 4096) (call $host_response_len))
+        (i32.const 1)))
+    "#;
+
+    /// actor that reports a panic, then traps
+    const PANICKING_ACTOR: &str = r#"
+    (module
+      (import "wasmbus" "__guest_error" (func $guest_error (param i32 i32)))
+      (memory (export "memory") 1)
+      (data (i32.const 0) "actor panicked at 'boom', src/lib.rs:1:1")
+      (func (export "__guest_call") (param $op_len i32) (param $req_len i32) (result i32)
+        (call $guest_error (i32.const 0) (i32.const 40))
+        unreachable))
+    "#;
+
+    /// actor that loops forever if the message has an arg, and otherwise returns an empty response
+    const LOOPING_ACTOR: &str = r#"
+    (module
+      (memory (export "memory") 1)
+      (func (export "__guest_call") (param $op_len i32) (param $req_len i32) (result i32)
+        (if (local.get $req_len)
+          (then (loop $forever (br $forever))))
+        (i32.const 1)))
+    "#;
+
+    struct Upper;
+
+    #[async_trait]
+    impl MessageDispatch for Upper {
+        async fn dispatch(&self, ctx: &Context, message: Message<'_>) -> RpcResult<Message<'_>> {
+            assert_eq!(ctx.actor.as_deref(), Some(DEFAULT_ACTOR_ID));
+            match message.method {
+                "Test.Upper" => Ok(Message {
+                    method: "Test.Upper",
+                    arg: Cow::Owned(message.arg.to_ascii_uppercase()),
+                }),
+                "Test.Trace" => Ok(Message {
+                    method: "Test.Trace",
+                    arg: Cow::Owned(
+                        ctx.trace_context
+                            .get("traceparent")
+                            .cloned()
+                            .unwrap_or_default()
+                            .into_bytes(),
+                    ),
+                }),
+                "Test.Slow" => {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    Ok(Message {
+                        method: "Test.Slow",
+                        arg: Cow::Owned(message.arg.to_vec()),
+                    })
+                }
+                _ => Err(RpcError::MethodNotHandled(message.method.to_string())),
+            }
+        }
+    }
+
+    #[test]
+    fn default_actor_id() {
+        wasmbus_rpc::core::validate_public_key(DEFAULT_ACTOR_ID, wasmbus_rpc::core::KeyType::Actor)
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn forward_to_mock_provider() {
+        let host = TestHost::builder()
+            .provider("default", "wasmcloud:test", Arc::new(Upper))
+            .build(FORWARDING_ACTOR.as_bytes())
+            .expect("load actor");
+        let resp = host.call("Forward.Forward", b"hello").await.unwrap();
+        assert_eq!(&resp, b"HELLO");
+        // the instance is reused
+        let resp = host.call("Forward.Forward", b"again").await.unwrap();
+        assert_eq!(&resp, b"AGAIN");
+        assert_eq!(host.logs(), vec!["forwarding", "forwarding"]);
+
+        let unmocked = TestHost::builder()
+            .build(FORWARDING_ACTOR.as_bytes())
+            .expect("load actor");
+        let err = unmocked
+            .call("Forward.Forward", b"hello")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("no mock provider"));
+    }
+
+    #[tokio::test]
+    async fn pass_host_call_opts() {
+        let opts = wasmbus_rpc::serialize(&HostCallOpts {
+            timeout_ms: Some(100),
+            trace_context: HashMap::from([("traceparent".to_string(), "00-abc".to_string())]),
+            ..Default::default()
+        })
+        .unwrap();
+        let wat = OPTS_ACTOR
+            .replace(
+                "\"OPTS\"",
+                &format!(
+                    "\"{}\"",
+                    opts.iter()
+                        .map(|b| format!("\\{:02x}", b))
+                        .collect::<String>()
+                ),
+            )
+            .replace("OPTS_LEN", &opts.len().to_string());
+        let host = TestHost::builder()
+            .provider("default", "wasmcloud:test", Arc::new(Upper))
+            .build(wat.as_bytes())
+            .expect("load actor");
+        let resp = host.call("Test.Trace", b"").await.unwrap();
+        assert_eq!(&resp, b"00-abc");
+        let err = host.call("Test.Slow", b"").await.unwrap_err();
+        assert!(matches!(err, RpcError::ActorHandler(msg) if msg.contains("timeout")));
+    }
+
+    #[tokio::test]
+    async fn report_panic() {
+        let host = TestHost::builder()
+            .build(PANICKING_ACTOR.as_bytes())
+            .expect("load actor");
+        let err = host.call("Any.Method", b"").await.unwrap_err();
+        assert!(err.to_string().contains("actor panicked at 'boom'"));
+    }
+
+    #[tokio::test]
+    async fn interrupt_on_timeout() {
+        let host = TestHost::builder()
+            .timeout(Some(Duration::from_millis(100)))
+            .build(LOOPING_ACTOR.as_bytes())
+            .expect("load actor");
+        for _ in 0..2 {
+            let err = host.call("Loop.Forever", b"loop").await.unwrap_err();
+            assert!(matches!(err, RpcError::Timeout(_)), "{}", err);
+            // the actor was interrupted, so the next call runs
+            let resp = host.call("Loop.Once", b"").await.unwrap();
+            assert!(resp.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn send_host_context() {
+        let host = TestHost::builder()
+            .build(CONTEXT_ACTOR.as_bytes())
+            .expect("load actor");
+        let ctx = Context {
+            origin: Some(wasmbus_rpc::core::WasmCloudEntity {
+                public_key: "MCALLER".to_string(),
+                ..Default::default()
+            }),
+            invocation_id: Some("inv-1".to_string()),
+            trace_context: HashMap::from([("traceparent".to_string(), "00-abc".to_string())]),
+            ..Default::default()
+        };
+        let resp = host
+            .call_with_context(&ctx, "Any.Method", b"")
+            .await
+            .unwrap();
+        let received: HostContext = wasmbus_rpc::deserialize(&resp).unwrap();
+        assert_eq!(received, HostContext::from(&ctx));
+        assert_eq!(Context::from(received).actor.as_deref(), Some("MCALLER"));
+
+        let host = TestHost::builder()
+            .log_level(Some(log::LevelFilter::Debug))
+            .build(CONTEXT_ACTOR.as_bytes())
+            .expect("load actor");
+        let resp = host.call("Any.Method", b"").await.unwrap();
+        let received: HostContext = wasmbus_rpc::deserialize(&resp).unwrap();
+        assert_eq!(received.log_level.as_deref(), Some("DEBUG"));
+    }
+}